@@ -0,0 +1,16 @@
+//! Arbitrary bytes as a GeoTIFF, then a few lookups against whatever
+//! transform the tags produced.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use virtual_elevation_analyzer::DEMProcessor;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mut dem) = DEMProcessor::new(data, Some("N47E007.tif".to_string())) {
+        let bounds = dem.get_bounds();
+        let _ = dem.batch_lookup(
+            vec![bounds[1], bounds[3], 47.5, f64::NAN],
+            vec![bounds[0], bounds[2], 7.5, f64::INFINITY],
+        );
+    }
+});
@@ -0,0 +1,21 @@
+//! Arbitrary bytes through every FIT entry point: the security scan, all
+//! three parse modes, session splitting and the data-section fingerprint.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use virtual_elevation_analyzer::{
+    fingerprint_fit_file, parse_fit_file_with_mode, FitParseMode, SecurityValidator,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = SecurityValidator::new().inspect_fit_data(data);
+    let _ = fingerprint_fit_file(data);
+
+    for mode in [FitParseMode::Lenient, FitParseMode::Strict, FitParseMode::Recover] {
+        if let Ok(parsed) = parse_fit_file_with_mode(data, mode) {
+            for index in 0..parsed.sessions().len() {
+                let _ = parsed.session(index);
+            }
+        }
+    }
+});
@@ -0,0 +1,20 @@
+//! Arbitrary text as a .prj (WKT) file next to a projected world file.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use virtual_elevation_analyzer::DEMProcessor;
+
+const RASTER: &[u8] = include_bytes!("../corpus/dem_tiff/seed-f32-4x4.tif");
+const WORLD_FILE: &str = "30.0\n0.0\n0.0\n-30.0\n500000.0\n3700000.0\n";
+
+fuzz_target!(|data: &[u8]| {
+    let prj = String::from_utf8_lossy(data).into_owned();
+    if let Ok(mut dem) = DEMProcessor::new_with_world_file(
+        RASTER,
+        None,
+        Some(WORLD_FILE.to_string()),
+        Some(prj),
+    ) {
+        let _ = dem.batch_lookup(vec![33.4, 33.5], vec![-87.0, -86.9]);
+    }
+});
@@ -0,0 +1,15 @@
+//! Arbitrary text as a .tfw world file next to a known-good 4x4 raster.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use virtual_elevation_analyzer::DEMProcessor;
+
+const RASTER: &[u8] = include_bytes!("../corpus/dem_tiff/seed-f32-4x4.tif");
+
+fuzz_target!(|data: &[u8]| {
+    let world_file = String::from_utf8_lossy(data).into_owned();
+    if let Ok(mut dem) = DEMProcessor::new_with_world_file(RASTER, None, Some(world_file), None) {
+        let bounds = dem.get_bounds();
+        let _ = dem.batch_lookup(vec![bounds[1], bounds[3]], vec![bounds[0], bounds[2]]);
+    }
+});
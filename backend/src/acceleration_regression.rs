@@ -0,0 +1,220 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::virtual_elevation::{grade_from_elevation, VirtualElevationCalculator};
+
+const G: f64 = 9.807;
+const MIN_SPEED: f64 = 2.0; // P/v blows up when nearly stopped
+
+/// Martin/Chung "shortcut" regression: P·eta/v − m·a − m·g·sin(θ) = ½·rho·CdA·va² + Crr·m·g.
+/// Slope against va² gives ½·rho·CdA, the intercept gives Crr·m·g.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct RegressionResult {
+    slope: f64,
+    intercept: f64,
+    slope_std_error: f64,
+    intercept_std_error: f64,
+    r2: f64,
+    cda: f64,
+    crr: f64,
+    cda_std_error: f64,
+    crr_std_error: f64,
+    sample_count: usize,
+    assumed_flat: bool,
+    sample_indices: Vec<usize>,
+    x_values: Vec<f64>,
+    y_values: Vec<f64>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl RegressionResult {
+    /// Regression slope (N per (m/s)²) = ½·rho·CdA
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn slope(&self) -> f64 { self.slope }
+
+    /// Regression intercept (N) = Crr·m·g
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn intercept(&self) -> f64 { self.intercept }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn slope_std_error(&self) -> f64 { self.slope_std_error }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn intercept_std_error(&self) -> f64 { self.intercept_std_error }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn r2(&self) -> f64 { self.r2 }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda(&self) -> f64 { self.cda }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr(&self) -> f64 { self.crr }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda_std_error(&self) -> f64 { self.cda_std_error }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr_std_error(&self) -> f64 { self.crr_std_error }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn sample_count(&self) -> usize { self.sample_count }
+
+    /// True when no elevation was available and the road was taken as flat
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn assumed_flat(&self) -> bool { self.assumed_flat }
+
+    /// Data index of each scatter point
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn sample_indices(&self) -> Vec<usize> { self.sample_indices.clone() }
+
+    /// Scatter x: apparent velocity squared (m²/s²)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn x_values(&self) -> Vec<f64> { self.x_values.clone() }
+
+    /// Scatter y: P·eta/v − m·a − m·g·sin(θ) (N)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn y_values(&self) -> Vec<f64> { self.y_values.clone() }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl VirtualElevationCalculator {
+    /// Regression (shortcut) method over the trim region, as a cross-check of the VE fit
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn acceleration_regression(&self, trim_start: usize, trim_end: usize) -> Result<RegressionResult> {
+        let (start, end) = self.validate_segments(&[(trim_start, trim_end)])?[0];
+        let inputs = self.slope_inputs();
+        let params = self.params();
+        let mass = params.system_mass;
+
+        let (elevation, assumed_flat) = match self.reference_elevation() {
+            Some(reference) => (reference, false),
+            None => (vec![0.0; inputs.velocity.len()], true),
+        };
+        let grade = grade_from_elevation(&inputs.velocity, &elevation, self.dt());
+
+        let mut sample_indices = Vec::new();
+        let mut x_values = Vec::new();
+        let mut y_values = Vec::new();
+        let mut rho_sum = 0.0;
+
+        for (i, &v) in inputs.velocity.iter().enumerate().take(end + 1).skip(start + 1) {
+            if v < MIN_SPEED {
+                continue;
+            }
+
+            let x = inputs.apparent_velocity[i].powi(2);
+            let y = inputs.power[i] * params.eta / v - mass * inputs.acceleration[i] - mass * G * grade[i];
+            if x.is_finite() && y.is_finite() {
+                sample_indices.push(i);
+                x_values.push(x);
+                y_values.push(y);
+                rho_sum += inputs.rho[i];
+            }
+        }
+
+        let n = x_values.len();
+        if n < 3 {
+            return Err(Error::InsufficientData("Not enough moving samples in the trim region for a regression".to_string()));
+        }
+
+        let line = ordinary_least_squares(&x_values, &y_values)
+            .ok_or_else(|| Error::InsufficientData("Speed doesn't vary enough in the trim region to separate CdA from Crr".to_string()))?;
+
+        // Aero term uses the mean density of the samples in the regression
+        let rho = rho_sum / n as f64;
+
+        Ok(RegressionResult {
+            slope: line.slope,
+            intercept: line.intercept,
+            slope_std_error: line.slope_std_error,
+            intercept_std_error: line.intercept_std_error,
+            r2: line.r2,
+            cda: 2.0 * line.slope / rho,
+            crr: line.intercept / (mass * G),
+            cda_std_error: 2.0 * line.slope_std_error / rho,
+            crr_std_error: line.intercept_std_error / (mass * G),
+            sample_count: n,
+            assumed_flat,
+            sample_indices,
+            x_values,
+            y_values,
+        })
+    }
+}
+
+struct LinearFit {
+    slope: f64,
+    intercept: f64,
+    slope_std_error: f64,
+    intercept_std_error: f64,
+    r2: f64,
+}
+
+/// y = intercept + slope·x with classical OLS standard errors
+fn ordinary_least_squares(x: &[f64], y: &[f64]) -> Option<LinearFit> {
+    let n = x.len() as f64;
+    let mean_x = x.iter().sum::<f64>() / n;
+    let mean_y = y.iter().sum::<f64>() / n;
+
+    let sxx: f64 = x.iter().map(|xi| (xi - mean_x).powi(2)).sum();
+    let sxy: f64 = x.iter().zip(y).map(|(xi, yi)| (xi - mean_x) * (yi - mean_y)).sum();
+    let syy: f64 = y.iter().map(|yi| (yi - mean_y).powi(2)).sum();
+    if sxx <= f64::EPSILON * n {
+        return None;
+    }
+
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+    let rss: f64 = x.iter().zip(y)
+        .map(|(xi, yi)| (yi - intercept - slope * xi).powi(2))
+        .sum();
+    let sigma2 = if x.len() > 2 { rss / (n - 2.0) } else { 0.0 };
+
+    Some(LinearFit {
+        slope,
+        intercept,
+        slope_std_error: (sigma2 / sxx).sqrt(),
+        intercept_std_error: (sigma2 * (1.0 / n + mean_x * mean_x / sxx)).sqrt(),
+        r2: if syy > 0.0 { 1.0 - rss / syy } else { 0.0 },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{rolling_course, CDA, CRR, SAMPLES_PER_RUN};
+
+    #[test]
+    fn test_regression_recovers_cda_and_crr() {
+        let (calculator, segments) = rolling_course(&[6.0, 9.0, 12.0], 1.0, 0.0);
+        let end = segments.last().unwrap().1;
+        let result = calculator.acceleration_regression(1, end).unwrap();
+
+        assert!((result.cda - CDA).abs() < 1e-3, "cda {}", result.cda);
+        assert!((result.crr - CRR).abs() < 1e-4, "crr {}", result.crr);
+        assert!(result.r2 > 0.99);
+        assert!(!result.assumed_flat);
+        assert_eq!(result.x_values.len(), result.sample_count);
+    }
+
+    #[test]
+    fn test_too_few_samples_rejected() {
+        let (calculator, _) = rolling_course(&[9.0], 1.0, 0.0);
+        assert!(calculator.acceleration_regression(10, 12).is_err());
+        assert!(calculator.acceleration_regression(1, SAMPLES_PER_RUN - 1).is_ok());
+    }
+
+    #[test]
+    fn test_ols_standard_errors() {
+        let x = [1.0, 2.0, 3.0, 4.0];
+        let y = [2.1, 3.9, 6.1, 7.9];
+        let fit = ordinary_least_squares(&x, &y).unwrap();
+
+        assert!((fit.slope - 1.96).abs() < 1e-9);
+        assert!((fit.intercept - 0.1).abs() < 1e-9);
+        assert!(fit.slope_std_error > 0.0 && fit.slope_std_error < 0.1);
+    }
+}
@@ -1,4 +1,6 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
+use crate::error::{Error, Result};
 
 // Gas constants (J/(kg·K))
 const RD: f64 = 287.0531;  // Specific gas constant for dry air
@@ -6,10 +8,11 @@ const RV: f64 = 461.4964;  // Specific gas constant for water vapor
 
 /// Air density calculator using meteorological data
 /// Based on formulas from https://www.gribble.org/cycling/air_density.html
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
 pub struct AirDensityCalculator;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl AirDensityCalculator {
 
     /// Calculate saturation vapor pressure using Tetens formula
@@ -19,7 +22,7 @@ impl AirDensityCalculator {
     ///
     /// # Returns
     /// Saturation vapor pressure in hPa (hectopascals)
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn saturation_vapor_pressure(temp_c: f64) -> f64 {
         // Tetens formula: Es = 6.1078 * exp(17.27 * T / (T + 237.3))
         6.1078 * (17.27 * temp_c / (temp_c + 237.3)).exp()
@@ -37,23 +40,19 @@ impl AirDensityCalculator {
     ///
     /// # Errors
     /// Returns error if inputs are invalid
-    #[wasm_bindgen]
-    pub fn calculate_dew_point(temp_c: f64, humidity_percent: f64) -> Result<f64, JsValue> {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn calculate_dew_point(temp_c: f64, humidity_percent: f64) -> Result<f64> {
         // Validate inputs
         if !temp_c.is_finite() || !humidity_percent.is_finite() {
-            return Err(JsValue::from_str("Invalid input: non-finite values"));
+            return Err(Error::InvalidInput("Invalid input: non-finite values".to_string()));
         }
 
-        if humidity_percent < 0.0 || humidity_percent > 100.0 {
-            return Err(JsValue::from_str(
-                &format!("Invalid humidity: {}% (must be 0-100%)", humidity_percent)
-            ));
+        if !(0.0..=100.0).contains(&humidity_percent) {
+            return Err(Error::InvalidInput(format!("Invalid humidity: {}% (must be 0-100%)", humidity_percent)));
         }
 
-        if temp_c < -100.0 || temp_c > 60.0 {
-            return Err(JsValue::from_str(
-                &format!("Invalid temperature: {}°C (must be -100 to 60°C)", temp_c)
-            ));
+        if !(-100.0..=60.0).contains(&temp_c) {
+            return Err(Error::InvalidInput(format!("Invalid temperature: {}°C (must be -100 to 60°C)", temp_c)));
         }
 
         // Magnus-Tetens constants
@@ -69,14 +68,12 @@ impl AirDensityCalculator {
 
         // Sanity check
         if !dew_point.is_finite() {
-            return Err(JsValue::from_str("Dew point calculation resulted in invalid value"));
+            return Err(Error::InvalidInput("Dew point calculation resulted in invalid value".to_string()));
         }
 
         // Dew point must be <= temperature
         if dew_point > temp_c + 0.1 {
-            return Err(JsValue::from_str(
-                &format!("Calculated dew point ({}°C) exceeds temperature ({}°C)", dew_point, temp_c)
-            ));
+            return Err(Error::InvalidInput(format!("Calculated dew point ({}°C) exceeds temperature ({}°C)", dew_point, temp_c)));
         }
 
         Ok(dew_point)
@@ -99,36 +96,30 @@ impl AirDensityCalculator {
     ///
     /// # Errors
     /// Returns error if inputs are invalid or out of reasonable ranges
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn calculate_air_density(
         temp_c: f64,
         pressure_hpa: f64,
         dew_point_c: f64
-    ) -> Result<f64, JsValue> {
+    ) -> Result<f64> {
         // Validate inputs
         if !temp_c.is_finite() || !pressure_hpa.is_finite() || !dew_point_c.is_finite() {
-            return Err(JsValue::from_str("Invalid input: non-finite values"));
+            return Err(Error::InvalidInput("Invalid input: non-finite values".to_string()));
         }
 
         // Validate pressure range (reasonable atmospheric pressure)
         if pressure_hpa <= 0.0 || pressure_hpa > 1100.0 {
-            return Err(JsValue::from_str(
-                &format!("Invalid pressure: {} hPa (must be 0-1100 hPa)", pressure_hpa)
-            ));
+            return Err(Error::InvalidInput(format!("Invalid pressure: {} hPa (must be 0-1100 hPa)", pressure_hpa)));
         }
 
         // Validate temperature range
-        if temp_c < -100.0 || temp_c > 60.0 {
-            return Err(JsValue::from_str(
-                &format!("Invalid temperature: {}°C (must be -100 to 60°C)", temp_c)
-            ));
+        if !(-100.0..=60.0).contains(&temp_c) {
+            return Err(Error::InvalidInput(format!("Invalid temperature: {}°C (must be -100 to 60°C)", temp_c)));
         }
 
         // Dew point must be <= temperature
         if dew_point_c > temp_c {
-            return Err(JsValue::from_str(
-                &format!("Dew point ({}°C) cannot exceed temperature ({}°C)", dew_point_c, temp_c)
-            ));
+            return Err(Error::InvalidInput(format!("Dew point ({}°C) cannot exceed temperature ({}°C)", dew_point_c, temp_c)));
         }
 
         // Convert temperature to Kelvin
@@ -152,10 +143,8 @@ impl AirDensityCalculator {
         let rho = (pd_pa / (RD * temp_k)) + (pv_pa / (RV * temp_k));
 
         // Sanity check result
-        if !rho.is_finite() || rho < 0.5 || rho > 2.0 {
-            return Err(JsValue::from_str(
-                &format!("Calculated air density out of range: {} kg/m³", rho)
-            ));
+        if !rho.is_finite() || !(0.5..=2.0).contains(&rho) {
+            return Err(Error::InvalidInput(format!("Calculated air density out of range: {} kg/m³", rho)));
         }
 
         Ok(rho)
@@ -174,12 +163,12 @@ impl AirDensityCalculator {
     ///
     /// # Errors
     /// Returns error if inputs are invalid
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn calculate_air_density_from_humidity(
         temp_c: f64,
         pressure_hpa: f64,
         humidity_percent: f64
-    ) -> Result<f64, JsValue> {
+    ) -> Result<f64> {
         // First calculate dew point from temperature and humidity
         let dew_point = Self::calculate_dew_point(temp_c, humidity_percent)?;
 
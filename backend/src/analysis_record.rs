@@ -0,0 +1,302 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::utils;
+use crate::virtual_elevation::{VEData, VEParameters, VEResult, VirtualElevationCalculator};
+
+/// Current `AnalysisRecord` schema. Bump when a field changes meaning or becomes
+/// required; records from newer schemas are rejected rather than misread.
+pub const ANALYSIS_RECORD_VERSION: u32 = 1;
+
+/// Summary numbers of a VE run, stored so a re-run can be checked against them
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
+pub struct AnalysisMetrics {
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
+    r2: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
+    rmse: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
+    ve_elevation_diff: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
+    actual_elevation_diff: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
+    virtual_distance_air: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
+    virtual_distance_ground: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
+    vd_difference_percent: f64,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl AnalysisMetrics {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn r2(&self) -> f64 { self.r2 }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn rmse(&self) -> f64 { self.rmse }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn ve_elevation_diff(&self) -> f64 { self.ve_elevation_diff }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn actual_elevation_diff(&self) -> f64 { self.actual_elevation_diff }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn virtual_distance_air(&self) -> f64 { self.virtual_distance_air }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn virtual_distance_ground(&self) -> f64 { self.virtual_distance_ground }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn vd_difference_percent(&self) -> f64 { self.vd_difference_percent }
+}
+
+impl From<&VEResult> for AnalysisMetrics {
+    fn from(result: &VEResult) -> AnalysisMetrics {
+        AnalysisMetrics {
+            r2: result.r2(),
+            rmse: result.rmse(),
+            ve_elevation_diff: result.ve_elevation_diff(),
+            actual_elevation_diff: result.actual_elevation_diff(),
+            virtual_distance_air: result.virtual_distance_air(),
+            virtual_distance_ground: result.virtual_distance_ground(),
+            vd_difference_percent: result.vd_difference_percent(),
+        }
+    }
+}
+
+impl AnalysisMetrics {
+    /// Bitwise equality (NaN equals NaN), the test for a bit-for-bit re-run
+    fn identical(&self, other: &AnalysisMetrics) -> bool {
+        let bits = |m: &AnalysisMetrics| [
+            m.r2, m.rmse, m.ve_elevation_diff, m.actual_elevation_diff,
+            m.virtual_distance_air, m.virtual_distance_ground, m.vd_difference_percent,
+        ].map(f64::to_bits);
+        bits(self) == bits(other)
+    }
+}
+
+/// Everything needed to reproduce one VE analysis: the ride data, parameters,
+/// trim region and chosen CdA/Crr, with the metrics they produced and the crate
+/// version that produced them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
+pub struct AnalysisRecord {
+    schema_version: u32,
+    crate_version: String,
+    #[serde(default)]
+    file_name: Option<String>,
+    #[serde(default)]
+    laps: Vec<usize>,
+    #[serde(default)]
+    notes: String,
+    data: VEData,
+    params: VEParameters,
+    air_speed_calibration: f64,
+    trim_start: usize,
+    trim_end: usize,
+    cda: f64,
+    crr: f64,
+    metrics: AnalysisMetrics,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl AnalysisRecord {
+    /// Run the analysis and record it together with its inputs
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(
+        data: VEData,
+        params: VEParameters,
+        air_speed_calibration: f64,
+        cda: f64,
+        crr: f64,
+        trim_start: usize,
+        trim_end: usize,
+    ) -> AnalysisRecord {
+        let mut record = AnalysisRecord {
+            schema_version: ANALYSIS_RECORD_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            file_name: None,
+            laps: Vec::new(),
+            notes: String::new(),
+            data,
+            params,
+            air_speed_calibration,
+            trim_start,
+            trim_end,
+            cda,
+            crr,
+            metrics: AnalysisMetrics {
+                r2: f64::NAN,
+                rmse: f64::NAN,
+                ve_elevation_diff: f64::NAN,
+                actual_elevation_diff: f64::NAN,
+                virtual_distance_air: f64::NAN,
+                virtual_distance_ground: f64::NAN,
+                vd_difference_percent: f64::NAN,
+            },
+        };
+        record.metrics = AnalysisMetrics::from(&record.rerun());
+        record
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn schema_version(&self) -> u32 { self.schema_version }
+
+    /// Version of this crate that produced the record
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crate_version(&self) -> String { self.crate_version.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn file_name(&self) -> Option<String> { self.file_name.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter))]
+    pub fn set_file_name(&mut self, file_name: Option<String>) { self.file_name = file_name; }
+
+    /// 1-based lap numbers the trim region was taken from, if any
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn laps(&self) -> Vec<usize> { self.laps.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter))]
+    pub fn set_laps(&mut self, laps: Vec<usize>) { self.laps = laps; }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn notes(&self) -> String { self.notes.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter))]
+    pub fn set_notes(&mut self, notes: String) { self.notes = notes; }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn data(&self) -> VEData { self.data.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn params(&self) -> VEParameters { self.params.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn air_speed_calibration(&self) -> f64 { self.air_speed_calibration }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn trim_start(&self) -> usize { self.trim_start }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn trim_end(&self) -> usize { self.trim_end }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda(&self) -> f64 { self.cda }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr(&self) -> f64 { self.crr }
+
+    /// Metrics as computed when the record was created
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn metrics(&self) -> AnalysisMetrics { self.metrics }
+
+    /// Re-run the recorded analysis with this build of the crate
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn rerun(&self) -> VEResult {
+        let mut calculator = VirtualElevationCalculator::new(self.data.clone(), self.params.clone());
+        calculator.set_air_speed_calibration(self.air_speed_calibration);
+        calculator.calculate_virtual_elevation(self.cda, self.crr, self.trim_start, self.trim_end)
+    }
+
+    /// True when a re-run reproduces the stored metrics bit for bit
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn reproduces(&self) -> bool {
+        self.metrics.identical(&AnalysisMetrics::from(&self.rerun()))
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_json(&self) -> Result<String> {
+        utils::to_json(self, "analysis record")
+    }
+
+    /// Parse a record, rejecting schemas newer than this build understands
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_json(json: &str) -> Result<AnalysisRecord> {
+        let record: AnalysisRecord = utils::from_json(json, "analysis record")?;
+        if record.schema_version == 0 || record.schema_version > ANALYSIS_RECORD_VERSION {
+            return Err(Error::InvalidInput(format!(
+                "Unsupported analysis record version {} (this build reads up to {})",
+                record.schema_version, ANALYSIS_RECORD_VERSION
+            )));
+        }
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{CDA, CRR, MASS, RHO};
+
+    fn ride_with_gaps() -> VEData {
+        let n = 120;
+        let velocity: Vec<f64> = (0..n).map(|i| 9.0 + (i as f64 * 0.1).sin()).collect();
+        let altitude: Vec<f64> = (0..n).map(|i| if i % 17 == 0 { f64::NAN } else { 50.0 + 0.01 * i as f64 }).collect();
+        VEData::new(
+            (0..n).map(|i| i as f64).collect(),
+            (0..n).map(|i| 180.0 + (i % 7) as f64 * 0.1).collect(),
+            velocity,
+            vec![f64::NAN; n],
+            vec![f64::NAN; n],
+            altitude,
+            vec![],
+            vec![],
+            vec![],
+        )
+    }
+
+    fn params() -> VEParameters {
+        let mut params = VEParameters::new();
+        params.system_mass = MASS;
+        params.rho = RHO;
+        params
+    }
+
+    #[test]
+    fn test_ve_types_round_trip_with_nan() {
+        let data = ride_with_gaps();
+        let restored = VEData::from_json(&data.to_json().unwrap()).unwrap();
+        assert_eq!(restored.to_json().unwrap(), data.to_json().unwrap());
+
+        let params = params();
+        let restored = VEParameters::from_json(&params.to_json().unwrap()).unwrap();
+        assert_eq!(restored.system_mass, params.system_mass);
+        assert_eq!(restored.cda, None);
+
+        let result = VirtualElevationCalculator::new(data, params).calculate_virtual_elevation(CDA, CRR, 0, 119);
+        let restored = VEResult::from_json(&result.to_json().unwrap()).unwrap();
+        assert_eq!(
+            restored.virtual_elevation().iter().map(|v| v.to_bits()).collect::<Vec<_>>(),
+            result.virtual_elevation().iter().map(|v| v.to_bits()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_record_round_trip_reproduces() {
+        let mut record = AnalysisRecord::new(ride_with_gaps(), params(), 1.0, CDA, CRR, 5, 110);
+        record.set_laps(vec![2]);
+        record.set_notes("aero helmet".to_string());
+
+        let restored = AnalysisRecord::from_json(&record.to_json().unwrap()).unwrap();
+        assert_eq!(restored.schema_version(), ANALYSIS_RECORD_VERSION);
+        assert_eq!(restored.crate_version(), env!("CARGO_PKG_VERSION"));
+        assert_eq!(restored.laps(), vec![2]);
+        assert!(restored.metrics().identical(&record.metrics()));
+        assert!(restored.reproduces());
+    }
+
+    #[test]
+    fn test_newer_schema_rejected() {
+        let record = AnalysisRecord::new(ride_with_gaps(), params(), 1.0, CDA, CRR, 0, 119);
+        let mut json: serde_json::Value = serde_json::from_str(&record.to_json().unwrap()).unwrap();
+        json["schema_version"] = (ANALYSIS_RECORD_VERSION + 1).into();
+
+        let err = AnalysisRecord::from_json(&json.to_string()).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput(_)));
+    }
+}
@@ -0,0 +1,170 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use crate::error::Result;
+use crate::fit_parser::{LapData, ParsedFitFile};
+use crate::virtual_elevation::{CdaCrrFit, SlopeInputs, VEData, VEParameters, VEResult, VirtualElevationCalculator};
+
+/// A ride loaded once for interactive analysis. The CdA/Crr-independent terms of the
+/// slope equation (acceleration, rider directions, effective wind, apparent velocity,
+/// air density) are cached, so moving a slider only re-runs the VE integration.
+#[derive(Clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
+pub struct AnalysisSession {
+    calculator: VirtualElevationCalculator,
+    laps: Vec<LapData>,
+    rider_directions: Vec<f64>,
+    inputs: SlopeInputs,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl AnalysisSession {
+    /// Take ownership of a parsed file; its record arrays are moved, not copied
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(fit_file: ParsedFitFile, params: VEParameters) -> AnalysisSession {
+        let (fit_data, laps) = fit_file.into_parts();
+        Self::with_laps(fit_data.into(), params, laps)
+    }
+
+    /// Session over ride data that didn't come from a FIT file (CSV, resampled logs)
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_ve_data(data: VEData, params: VEParameters) -> AnalysisSession {
+        Self::with_laps(data, params, Vec::new())
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn record_count(&self) -> usize {
+        self.calculator.record_count()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn laps(&self) -> Vec<LapData> {
+        self.laps.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn params(&self) -> VEParameters {
+        self.calculator.params().clone()
+    }
+
+    /// Replace the parameters, recomputing only the cached terms they affect
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn update_params(&mut self, params: VEParameters) {
+        let wind_changed = params.wind_speed != self.calculator.params().wind_speed
+            || params.wind_direction != self.calculator.params().wind_direction;
+        let rho_changed = params.rho != self.calculator.params().rho;
+        *self.calculator.params_mut() = params;
+
+        if wind_changed {
+            self.refresh_wind();
+        }
+        if rho_changed {
+            self.inputs.rho = self.calculator.rho_series();
+        }
+    }
+
+    /// Air speed calibration factor (1.0 = no adjustment)
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_air_speed_calibration(&mut self, calibration: f64) {
+        self.calculator.set_air_speed_calibration(calibration);
+        self.inputs.apparent_velocity = self.calculator.get_apparent_velocity(&self.inputs.effective_wind);
+    }
+
+    /// Per-datapoint air density (e.g. from weather data), overriding `params.rho`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_rho_array(&mut self, rho_array: Vec<f64>) {
+        self.calculator.set_rho_array(rho_array);
+        self.inputs.rho = self.calculator.rho_series();
+    }
+
+    /// Replace the reference elevation (e.g. with DEM lookups); VE itself is unaffected
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_altitude(&mut self, altitude: Vec<f64>) {
+        self.calculator.set_altitude(altitude);
+    }
+
+    /// VE profile and metrics for the given coefficients over `trim_start..=trim_end`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn evaluate(&self, cda: f64, crr: f64, trim_start: usize, trim_end: usize) -> VEResult {
+        self.calculator.ve_result(&self.inputs, cda, crr, trim_start, trim_end)
+    }
+
+    /// Fit CdA/Crr to the reference elevation in the trim region
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn optimize_cda_crr(&self, trim_start: usize, trim_end: usize) -> Result<CdaCrrFit> {
+        self.calculator.optimize_cda_crr(trim_start, trim_end)
+    }
+}
+
+impl AnalysisSession {
+    fn with_laps(data: VEData, params: VEParameters, laps: Vec<LapData>) -> AnalysisSession {
+        let mut session = Self::from_calculator(VirtualElevationCalculator::new(data, params));
+        session.laps = laps;
+        session
+    }
+
+    /// Session around an already configured calculator (air speed calibration, rho array, ...)
+    pub fn from_calculator(calculator: VirtualElevationCalculator) -> AnalysisSession {
+        let rider_directions = calculator.calculate_rider_directions();
+        let inputs = calculator.slope_inputs_with(Some(&rider_directions));
+
+        AnalysisSession {
+            calculator,
+            laps: Vec::new(),
+            rider_directions,
+            inputs,
+        }
+    }
+
+    /// The calculator in its current state, for the other analyses (coast-down, Monte Carlo, ...)
+    pub fn calculator(&self) -> &VirtualElevationCalculator {
+        &self.calculator
+    }
+
+    fn refresh_wind(&mut self) {
+        self.inputs.effective_wind = self.calculator.effective_wind_with(Some(&self.rider_directions));
+        self.inputs.apparent_velocity = self.calculator.get_apparent_velocity(&self.inputs.effective_wind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{rolling_course, CDA, CRR};
+
+    fn session_and_calculator() -> (AnalysisSession, VirtualElevationCalculator) {
+        let (calculator, _) = rolling_course(&[8.0, 11.0], 1.0, 0.0);
+        let session = AnalysisSession::from_calculator(calculator.clone());
+        (session, calculator)
+    }
+
+    #[test]
+    fn test_evaluate_matches_calculator() {
+        let (session, calculator) = session_and_calculator();
+        let end = session.record_count() - 1;
+
+        let cached = session.evaluate(CDA, CRR, 10, end);
+        let direct = calculator.calculate_virtual_elevation(CDA, CRR, 10, end);
+        assert_eq!(cached.virtual_elevation(), direct.virtual_elevation());
+        assert_eq!(cached.r2(), direct.r2());
+        assert!(cached.rmse() < 1e-6);
+    }
+
+    #[test]
+    fn test_update_params_refreshes_wind_and_rho() {
+        let (mut session, mut calculator) = session_and_calculator();
+        let end = session.record_count() - 1;
+
+        let mut params = session.params();
+        params.wind_speed = Some(2.0);
+        params.wind_direction = Some(90.0);
+        params.rho = 1.1;
+        session.update_params(params.clone());
+        *calculator.params_mut() = params;
+
+        let cached = session.evaluate(CDA, CRR, 0, end);
+        let direct = calculator.calculate_virtual_elevation(CDA, CRR, 0, end);
+        assert_eq!(cached.apparent_velocity(), direct.apparent_velocity());
+        assert_eq!(cached.virtual_elevation(), direct.virtual_elevation());
+    }
+}
@@ -0,0 +1,441 @@
+//! Batch virtual elevation analysis over a folder of FIT, GPX and TCX files.
+//!
+//! ```text
+//! ve-analyze <fit-dir> [--config <config.toml|config.json>] [--out <dir>]
+//! ```
+//!
+//! Each `.fit`, `.gpx` or `.tcx` file is parsed, given the `[defaults]` parameters overridden by its
+//! `[files."name.fit"]` entry, fitted for CdA/Crr over its trim region and written
+//! to `<out>/<name>.json`, e.g. `ride.fit.json`. One row per file goes to `<out>/summary.csv`.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use serde::{Deserialize, Serialize};
+use virtual_elevation_analyzer::{
+    parse_activity_file, AirDensityCalculator, CdaCrrFit, ParsedFitFile, VEData, VEParameters, VEResult,
+    VirtualElevationCalculator,
+};
+
+const USAGE: &str = "Usage: ve-analyze <fit-dir> [--config <config.toml|config.json>] [--out <dir>]";
+
+/// Analysis settings for one file. Every field is optional so a file entry
+/// only needs the values that differ from `[defaults]`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileSettings {
+    system_mass: Option<f64>,
+    eta: Option<f64>,
+    rho: Option<f64>,
+    /// Weather used to compute rho when rho isn't given (all three required)
+    temperature_c: Option<f64>,
+    pressure_hpa: Option<f64>,
+    humidity_percent: Option<f64>,
+    wind_speed: Option<f64>,
+    wind_direction: Option<f64>,
+    velodrome: Option<bool>,
+    /// Fix CdA or Crr instead of fitting it
+    cda: Option<f64>,
+    crr: Option<f64>,
+    cda_min: Option<f64>,
+    cda_max: Option<f64>,
+    crr_min: Option<f64>,
+    crr_max: Option<f64>,
+    air_speed_calibration: Option<f64>,
+    /// Record indices of the trim region (inclusive)
+    trim_start: Option<usize>,
+    trim_end: Option<usize>,
+    /// 1-based lap numbers; the trim region runs from the first lap's start to the last lap's end
+    laps: Option<Vec<usize>>,
+    /// 1-based session of a multisport file; defaults to the first cycling session.
+    /// Trims and lap numbers are relative to the selected session.
+    session: Option<usize>,
+}
+
+impl FileSettings {
+    /// Values set in `self` win over `defaults`
+    fn merged_over(&self, defaults: &FileSettings) -> FileSettings {
+        FileSettings {
+            system_mass: self.system_mass.or(defaults.system_mass),
+            eta: self.eta.or(defaults.eta),
+            rho: self.rho.or(defaults.rho),
+            temperature_c: self.temperature_c.or(defaults.temperature_c),
+            pressure_hpa: self.pressure_hpa.or(defaults.pressure_hpa),
+            humidity_percent: self.humidity_percent.or(defaults.humidity_percent),
+            wind_speed: self.wind_speed.or(defaults.wind_speed),
+            wind_direction: self.wind_direction.or(defaults.wind_direction),
+            velodrome: self.velodrome.or(defaults.velodrome),
+            cda: self.cda.or(defaults.cda),
+            crr: self.crr.or(defaults.crr),
+            cda_min: self.cda_min.or(defaults.cda_min),
+            cda_max: self.cda_max.or(defaults.cda_max),
+            crr_min: self.crr_min.or(defaults.crr_min),
+            crr_max: self.crr_max.or(defaults.crr_max),
+            air_speed_calibration: self.air_speed_calibration.or(defaults.air_speed_calibration),
+            trim_start: self.trim_start.or(defaults.trim_start),
+            trim_end: self.trim_end.or(defaults.trim_end),
+            laps: self.laps.clone().or_else(|| defaults.laps.clone()),
+            session: self.session.or(defaults.session),
+        }
+    }
+
+    fn parameters(&self) -> Result<VEParameters, Box<dyn Error>> {
+        let mut params = VEParameters::new();
+        if let Some(v) = self.system_mass { params.system_mass = v; }
+        if let Some(v) = self.eta { params.eta = v; }
+        if let Some(v) = self.cda_min { params.cda_min = v; }
+        if let Some(v) = self.cda_max { params.cda_max = v; }
+        if let Some(v) = self.crr_min { params.crr_min = v; }
+        if let Some(v) = self.crr_max { params.crr_max = v; }
+        if let Some(v) = self.velodrome { params.velodrome = v; }
+        params.cda = self.cda;
+        params.crr = self.crr;
+        params.wind_speed = self.wind_speed;
+        params.wind_direction = self.wind_direction;
+
+        params.rho = match (self.rho, self.temperature_c, self.pressure_hpa, self.humidity_percent) {
+            (Some(rho), ..) => rho,
+            (None, Some(temp), Some(pressure), Some(humidity)) => {
+                AirDensityCalculator::calculate_air_density_from_humidity(temp, pressure, humidity)?
+            }
+            (None, None, None, None) => params.rho,
+            _ => return Err("rho needs temperature_c, pressure_hpa and humidity_percent together".into()),
+        };
+
+        Ok(params)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BatchConfig {
+    defaults: FileSettings,
+    /// Per-file overrides keyed by file name
+    files: BTreeMap<String, FileSettings>,
+}
+
+impl BatchConfig {
+    fn load(path: &Path) -> Result<BatchConfig, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let is_json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let config = if is_json {
+            serde_json::from_str(&text)?
+        } else {
+            toml::from_str(&text)?
+        };
+        Ok(config)
+    }
+
+    fn settings_for(&self, file_name: &str) -> FileSettings {
+        match self.files.get(file_name) {
+            Some(settings) => settings.merged_over(&self.defaults),
+            None => self.defaults.clone(),
+        }
+    }
+}
+
+/// Everything written to a file's result JSON
+#[derive(Serialize)]
+struct FileReport {
+    file: String,
+    /// 1-based session analyzed, for multisport files
+    session: Option<usize>,
+    record_count: usize,
+    settings: FileSettings,
+    parameters: VEParameters,
+    trim_start: usize,
+    trim_end: usize,
+    fit: CdaCrrFit,
+    result: VEResult,
+}
+
+struct Args {
+    input_dir: PathBuf,
+    config: Option<PathBuf>,
+    out_dir: PathBuf,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut input_dir = None;
+    let mut config = None;
+    let mut out_dir = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--config" | "-c" => config = Some(PathBuf::from(iter.next().ok_or("--config needs a path")?)),
+            "--out" | "-o" => out_dir = Some(PathBuf::from(iter.next().ok_or("--out needs a path")?)),
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            flag if flag.starts_with('-') => return Err(format!("Unknown option {}\n{}", flag, USAGE)),
+            path if input_dir.is_none() => input_dir = Some(PathBuf::from(path)),
+            extra => return Err(format!("Unexpected argument {}\n{}", extra, USAGE)),
+        }
+    }
+
+    let input_dir = input_dir.ok_or_else(|| USAGE.to_string())?;
+    let out_dir = out_dir.unwrap_or_else(|| input_dir.join("ve-results"));
+    Ok(Args { input_dir, config, out_dir })
+}
+
+fn fit_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path.extension().is_some_and(|ext| ["fit", "gpx", "tcx"].iter().any(|known| ext.eq_ignore_ascii_case(known)))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Record index range covered by the given 1-based laps
+fn lap_trim(parsed: &ParsedFitFile, laps: &[usize]) -> Result<(usize, usize), Box<dyn Error>> {
+    let all_laps = parsed.laps();
+    let mut start_time = f64::INFINITY;
+    let mut end_time = f64::NEG_INFINITY;
+    for &lap in laps {
+        let data = lap.checked_sub(1)
+            .and_then(|i| all_laps.get(i))
+            .ok_or_else(|| format!("Lap {} not in file ({} laps)", lap, all_laps.len()))?;
+        start_time = start_time.min(data.start_time());
+        end_time = end_time.max(data.end_time());
+    }
+
+    let timestamps = parsed.fit_data().timestamps();
+    let start = timestamps.iter().position(|&t| t >= start_time);
+    let end = timestamps.iter().rposition(|&t| t <= end_time);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => Ok((start, end)),
+        _ => Err("Selected laps contain no records".into()),
+    }
+}
+
+/// Restrict a multisport file to one session: the configured one, else the first cycling
+/// session. Single-session files are analyzed whole.
+fn select_session(parsed: ParsedFitFile, session: Option<usize>) -> Result<(ParsedFitFile, Option<usize>), Box<dyn Error>> {
+    let index = match session {
+        Some(number) => Some(number.checked_sub(1).ok_or("Sessions are numbered from 1")?),
+        None if parsed.sessions().len() > 1 => Some(
+            parsed.find_session("cycling").ok_or("Multisport file has no cycling session; set session")?,
+        ),
+        None => None,
+    };
+
+    match index {
+        Some(index) => Ok((parsed.session(index)?, Some(index + 1))),
+        None => Ok((parsed, None)),
+    }
+}
+
+fn analyze_file(path: &Path, settings: &FileSettings) -> Result<FileReport, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    let (parsed, session) = select_session(parse_activity_file(&bytes)?, settings.session)?;
+    let fit_data = parsed.fit_data();
+    let record_count = fit_data.record_count();
+    if record_count < 3 {
+        return Err("File has fewer than 3 records".into());
+    }
+
+    let (trim_start, trim_end) = match &settings.laps {
+        Some(laps) if !laps.is_empty() => lap_trim(&parsed, laps)?,
+        _ => (
+            settings.trim_start.unwrap_or(0),
+            settings.trim_end.unwrap_or(record_count - 1).min(record_count - 1),
+        ),
+    };
+
+    let parameters = settings.parameters()?;
+    let data = VEData::from(fit_data);
+    let mut calculator = VirtualElevationCalculator::new(data, parameters.clone());
+    if let Some(calibration) = settings.air_speed_calibration {
+        calculator.set_air_speed_calibration(calibration);
+    }
+
+    let fit = calculator.optimize_cda_crr(trim_start, trim_end)?;
+    let result = calculator.calculate_virtual_elevation(fit.cda(), fit.crr(), trim_start, trim_end);
+
+    Ok(FileReport {
+        file: file_name(path),
+        session,
+        record_count,
+        settings: settings.clone(),
+        parameters,
+        trim_start,
+        trim_end,
+        fit,
+        result,
+    })
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+const SUMMARY_HEADER: [&str; 18] = [
+    "file", "status", "records", "trim_start", "trim_end", "system_mass", "eta", "rho",
+    "cda", "crr", "cda_std_error", "crr_std_error", "fit_rmse", "r2", "rmse",
+    "ve_elevation_diff", "actual_elevation_diff", "error",
+];
+
+fn summary_row(file: &str, outcome: &Result<FileReport, Box<dyn Error>>) -> Vec<String> {
+    match outcome {
+        Ok(report) => {
+            let mut row = vec![
+                file.to_string(),
+                "ok".to_string(),
+                report.record_count.to_string(),
+                report.trim_start.to_string(),
+                report.trim_end.to_string(),
+            ];
+            row.extend([
+                report.parameters.system_mass,
+                report.parameters.eta,
+                report.parameters.rho,
+                report.fit.cda(),
+                report.fit.crr(),
+                report.fit.cda_std_error(),
+                report.fit.crr_std_error(),
+                report.fit.rmse(),
+                report.result.r2(),
+                report.result.rmse(),
+                report.result.ve_elevation_diff(),
+                report.result.actual_elevation_diff(),
+            ].iter().map(|v| v.to_string()));
+            row.push(String::new());
+            row
+        }
+        Err(e) => {
+            let mut row = vec![file.to_string(), "error".to_string()];
+            row.resize(SUMMARY_HEADER.len() - 1, String::new());
+            row.push(e.to_string());
+            row
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_line(fields: &[String]) -> String {
+    let mut line = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",");
+    line.push('\n');
+    line
+}
+
+fn run(args: &Args) -> Result<usize, Box<dyn Error>> {
+    let config = match &args.config {
+        Some(path) => BatchConfig::load(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+        None => BatchConfig::default(),
+    };
+
+    let files = fit_files(&args.input_dir)?;
+    if files.is_empty() {
+        return Err(format!("No .fit, .gpx or .tcx files in {}", args.input_dir.display()).into());
+    }
+    fs::create_dir_all(&args.out_dir)?;
+
+    let header: Vec<String> = SUMMARY_HEADER.iter().map(|h| h.to_string()).collect();
+    let mut summary = csv_line(&header);
+    let mut failures = 0;
+
+    for path in &files {
+        let name = file_name(path);
+        let outcome = analyze_file(path, &config.settings_for(&name));
+
+        match &outcome {
+            Ok(report) => {
+                // The full name, so ride.fit and ride.gpx in one folder don't share a result
+                let json_path = args.out_dir.join(format!("{}.json", name));
+                fs::write(&json_path, serde_json::to_string_pretty(report)?)?;
+                eprintln!("{}: CdA {:.4}, Crr {:.5}", name, report.fit.cda(), report.fit.crr());
+            }
+            Err(e) => {
+                failures += 1;
+                eprintln!("{}: {}", name, e);
+            }
+        }
+        summary.push_str(&csv_line(&summary_row(&name, &outcome)));
+    }
+
+    fs::write(args.out_dir.join("summary.csv"), summary)?;
+    eprintln!("{} of {} files analyzed, results in {}", files.len() - failures, files.len(), args.out_dir.display());
+    Ok(failures)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&args) {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("ve-analyze: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_settings_override_defaults() {
+        let config: BatchConfig = toml::from_str(r#"
+            [defaults]
+            system_mass = 80.0
+            eta = 0.97
+            trim_start = 10
+
+            [files."ride.fit"]
+            system_mass = 85.0
+            laps = [2, 3]
+        "#).unwrap();
+
+        let ride = config.settings_for("ride.fit");
+        assert_eq!(ride.system_mass, Some(85.0));
+        assert_eq!(ride.eta, Some(0.97));
+        assert_eq!(ride.trim_start, Some(10));
+        assert_eq!(ride.laps, Some(vec![2, 3]));
+        assert_eq!(config.settings_for("other.fit").system_mass, Some(80.0));
+    }
+
+    #[test]
+    fn test_rho_from_weather() {
+        let settings = FileSettings {
+            temperature_c: Some(15.0),
+            pressure_hpa: Some(1013.25),
+            humidity_percent: Some(50.0),
+            ..Default::default()
+        };
+        let rho = settings.parameters().unwrap().rho;
+        assert!((rho - 1.225).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_parse_args_and_csv_quoting() {
+        let args: Vec<String> = ["rides", "--config", "c.toml"].iter().map(|s| s.to_string()).collect();
+        let parsed = parse_args(&args).unwrap();
+        assert_eq!(parsed.out_dir, PathBuf::from("rides/ve-results"));
+        assert!(parse_args(&[]).is_err());
+
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,322 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::optimizer::levenberg_marquardt;
+use crate::virtual_elevation::{grade_from_elevation, SlopeInputs, VirtualElevationCalculator};
+
+const G: f64 = 9.807;
+
+/// How coasting segments are picked out of the ride
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct CoastDownOptions {
+    /// Samples at or below this power (W) count as coasting
+    pub max_power: f64,
+    /// Minimum coasting segment length in samples
+    pub min_duration: usize,
+    /// Coasting below this speed (m/s) is ignored (track stands, stops)
+    pub min_speed: f64,
+    /// Minimum speed lost over a segment (m/s) for it to count as a deceleration
+    pub min_speed_drop: f64,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CoastDownOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> CoastDownOptions {
+        CoastDownOptions {
+            max_power: 1.0,
+            min_duration: 8,
+            min_speed: 3.0,
+            min_speed_drop: 0.5,
+        }
+    }
+}
+
+impl Default for CoastDownOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// CdA/Crr fitted to zero-power deceleration curves
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct CoastDownResult {
+    cda: f64,
+    crr: f64,
+    cda_std_error: f64,
+    crr_std_error: f64,
+    rmse: f64,
+    segment_starts: Vec<usize>,
+    segment_ends: Vec<usize>,
+    modelled_speed: Vec<f64>,
+    residuals: Vec<f64>,
+    assumed_flat: bool,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CoastDownResult {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda(&self) -> f64 { self.cda }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr(&self) -> f64 { self.crr }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda_std_error(&self) -> f64 { self.cda_std_error }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr_std_error(&self) -> f64 { self.crr_std_error }
+
+    /// RMS speed error over all coasting samples (m/s)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn rmse(&self) -> f64 { self.rmse }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn segment_starts(&self) -> Vec<usize> { self.segment_starts.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn segment_ends(&self) -> Vec<usize> { self.segment_ends.clone() }
+
+    /// Simulated speed per sample (NaN outside coasting segments)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn modelled_speed(&self) -> Vec<f64> { self.modelled_speed.clone() }
+
+    /// Modelled minus measured speed per sample (NaN outside coasting segments)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn residuals(&self) -> Vec<f64> { self.residuals.clone() }
+
+    /// True when no elevation was available and the road was taken as flat
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn assumed_flat(&self) -> bool { self.assumed_flat }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl VirtualElevationCalculator {
+    /// Fit CdA/Crr from coast-down segments (power ≈ 0, decelerating) by simulating the
+    /// speed decay from each segment's starting speed. `elevation` overrides the altitude
+    /// in the data, e.g. with DEM lookups.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn analyze_coast_down(&self, options: &CoastDownOptions, elevation: Option<Vec<f64>>) -> Result<CoastDownResult> {
+        let inputs = self.slope_inputs();
+        let n = inputs.velocity.len();
+
+        if !inputs.power.iter().any(|&p| p > options.max_power) {
+            return Err(Error::InsufficientData("Coast-down detection needs power data to tell coasting from pedalling".to_string()));
+        }
+
+        let segments = detect_coast_segments(&inputs, options);
+        if segments.is_empty() {
+            return Err(Error::InsufficientData("No zero-power decelerating segments found".to_string()));
+        }
+
+        let (elevation, assumed_flat) = match elevation {
+            Some(elevation) if elevation.len() == n => (elevation, false),
+            Some(elevation) => {
+                return Err(Error::InvalidInput(format!("Elevation has {} points but the data has {}", elevation.len(), n)));
+            }
+            None => match self.reference_elevation() {
+                Some(reference) => (reference, false),
+                None => (vec![0.0; n], true),
+            },
+        };
+        let grade = grade_from_elevation(&inputs.velocity, &elevation, self.dt());
+
+        let params = self.params();
+        let model = CoastModel {
+            inputs: &inputs,
+            grade: &grade,
+            mass: params.system_mass,
+            dt: self.dt(),
+        };
+
+        let fit = levenberg_marquardt(
+            |p| model.residuals(&segments, p[0], p[1]),
+            &[(params.cda_min + params.cda_max) / 2.0, (params.crr_min + params.crr_max) / 2.0],
+            &[(params.cda_min, params.cda_max), (params.crr_min, params.crr_max)],
+        );
+
+        let (cda, crr) = (fit.params[0], fit.params[1]);
+        let mut modelled_speed = vec![f64::NAN; n];
+        let mut residuals = vec![f64::NAN; n];
+        for &(start, end) in &segments {
+            let speed = model.simulate(start, end, cda, crr);
+            for (offset, v) in speed.into_iter().enumerate() {
+                modelled_speed[start + offset] = v;
+                residuals[start + offset] = v - inputs.velocity[start + offset];
+            }
+        }
+
+        Ok(CoastDownResult {
+            cda,
+            crr,
+            cda_std_error: fit.std_errors[0],
+            crr_std_error: fit.std_errors[1],
+            rmse: fit.rmse(),
+            segment_starts: segments.iter().map(|s| s.0).collect(),
+            segment_ends: segments.iter().map(|s| s.1).collect(),
+            modelled_speed,
+            residuals,
+            assumed_flat,
+        })
+    }
+}
+
+/// Runs of near-zero power above the minimum speed that lose enough speed to be a coast-down
+fn detect_coast_segments(inputs: &SlopeInputs, options: &CoastDownOptions) -> Vec<(usize, usize)> {
+    let n = inputs.velocity.len();
+    let coasting = |i: usize| inputs.power[i] <= options.max_power && inputs.velocity[i] >= options.min_speed;
+
+    let mut segments = Vec::new();
+    let mut i = 0;
+    while i < n {
+        if !coasting(i) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i + 1 < n && coasting(i + 1) {
+            i += 1;
+        }
+        let end = i;
+
+        if end + 1 - start >= options.min_duration.max(2)
+            && inputs.velocity[start] - inputs.velocity[end] >= options.min_speed_drop
+        {
+            segments.push((start, end));
+        }
+        i += 1;
+    }
+
+    segments
+}
+
+/// Zero-power equation of motion: m·dv/dt = -½·rho·CdA·va² - Crr·m·g - m·g·sin(θ)
+struct CoastModel<'a> {
+    inputs: &'a SlopeInputs,
+    grade: &'a [f64],
+    mass: f64,
+    dt: f64,
+}
+
+impl CoastModel<'_> {
+    fn acceleration(&self, i: usize, v: f64, cda: f64, crr: f64) -> f64 {
+        // Keep the measured wind component, but let air speed follow the simulated ground speed
+        let wind = self.inputs.apparent_velocity[i] - self.inputs.velocity[i];
+        let va = v + wind;
+        -(0.5 * self.inputs.rho[i] * cda * va * va.abs()) / self.mass - crr * G - G * self.grade[i]
+    }
+
+    /// Heun-integrated speed over [start, end], starting from the measured speed
+    fn simulate(&self, start: usize, end: usize, cda: f64, crr: f64) -> Vec<f64> {
+        let mut speed = Vec::with_capacity(end - start + 1);
+        let mut v = self.inputs.velocity[start];
+        speed.push(v);
+
+        for i in (start + 1)..=end {
+            let a0 = self.acceleration(i - 1, v, cda, crr);
+            let predicted = v + a0 * self.dt;
+            let a1 = self.acceleration(i, predicted, cda, crr);
+            v = (v + 0.5 * (a0 + a1) * self.dt).max(0.0);
+            speed.push(v);
+        }
+
+        speed
+    }
+
+    fn residuals(&self, segments: &[(usize, usize)], cda: f64, crr: f64) -> Vec<f64> {
+        segments.iter()
+            .flat_map(|&(start, end)| {
+                self.simulate(start, end, cda, crr)
+                    .into_iter()
+                    .zip(&self.inputs.velocity[start..=end])
+                    .skip(1)
+                    .map(|(modelled, measured)| modelled - measured)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{CDA, CRR, MASS, RHO};
+    use crate::virtual_elevation::{VEData, VEParameters};
+
+    /// Pedal up to speed at 300 W, then coast; repeated on a gentle downhill and uphill.
+    /// Coasting is integrated with fine sub-steps so the fitted model isn't checking itself.
+    fn coast_down_ride() -> VirtualElevationCalculator {
+        let mut velocity = Vec::new();
+        let mut power = Vec::new();
+        let mut altitude = Vec::new();
+        let mut h = 50.0;
+
+        for &grade in &[-0.01, 0.005, 0.0] {
+            for _ in 0..30 {
+                velocity.push(12.0);
+                power.push(300.0);
+                h += 12.0 * grade;
+                altitude.push(h);
+            }
+
+            let mut v: f64 = 12.0;
+            for _ in 0..40 {
+                for _ in 0..100 {
+                    let a = -(0.5 * RHO * CDA * v * v) / MASS - CRR * G - G * grade;
+                    v += a * 0.01;
+                    h += v * 0.01 * grade;
+                }
+                velocity.push(v);
+                power.push(0.0);
+                altitude.push(h);
+            }
+        }
+
+        let n = velocity.len();
+        let data = VEData::new(
+            (0..n).map(|i| i as f64).collect(),
+            power,
+            velocity,
+            vec![],
+            vec![],
+            altitude,
+            vec![],
+            vec![],
+            vec![],
+        );
+        let mut params = VEParameters::new();
+        params.system_mass = MASS;
+        params.rho = RHO;
+        VirtualElevationCalculator::new(data, params)
+    }
+
+    #[test]
+    fn test_detects_coasting_segments() {
+        let calculator = coast_down_ride();
+        let result = calculator.analyze_coast_down(&CoastDownOptions::new(), None).unwrap();
+
+        assert_eq!(result.segment_starts, vec![30, 100, 170]);
+        assert_eq!(result.segment_ends, vec![69, 139, 209]);
+        assert!(!result.assumed_flat);
+        assert!(result.residuals[0].is_nan());
+    }
+
+    #[test]
+    fn test_recovers_cda_and_crr() {
+        let calculator = coast_down_ride();
+        let result = calculator.analyze_coast_down(&CoastDownOptions::new(), None).unwrap();
+
+        assert!((result.cda - CDA).abs() < 0.01, "cda {}", result.cda);
+        assert!((result.crr - CRR).abs() < 0.001, "crr {}", result.crr);
+        assert!(result.rmse < 0.05);
+    }
+
+    #[test]
+    fn test_elevation_override_length_checked() {
+        let calculator = coast_down_ride();
+        assert!(calculator.analyze_coast_down(&CoastDownOptions::new(), Some(vec![0.0; 5])).is_err());
+    }
+}
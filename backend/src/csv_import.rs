@@ -0,0 +1,685 @@
+//! CSV sensor-log import. A [`CsvImportConfig`] maps columns onto record channels and
+//! converts their units; rows then go through the same record/lap assembly as FIT, GPX
+//! and TCX files. Channels FIT has no field for (yaw, humidity, pressure, reference CdA)
+//! are returned alongside.
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::fit_parser::{FitData, FitDiagnostics, LapData, ParsedFitFile};
+use crate::fitparser_wrapper::{FitFileId, FitMessages, FitRecord};
+use crate::security::MAX_TRACK_FILE_SIZE;
+use crate::track_import::{fill_distance, fill_speed, lap_from_samples, parse_time};
+use crate::utils;
+
+/// Rows a resampled table may have: eleven days at 1 Hz, and a bound on what a glitched
+/// timer or a tiny `resample_interval` can make the grid allocate
+const MAX_RESAMPLED_ROWS: usize = 1_000_000;
+
+/// One CSV column and its conversion to the channel's unit: `value * scale + offset`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CsvColumn {
+    /// Header text, matched after trimming whitespace
+    pub name: String,
+    #[serde(default = "unit_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub offset: f64,
+    /// Fail the import when the column is missing instead of leaving the channel out
+    #[serde(default)]
+    pub required: bool,
+}
+
+fn unit_scale() -> f64 {
+    1.0
+}
+
+impl CsvColumn {
+    pub fn new(name: &str, scale: f64) -> Self {
+        CsvColumn { name: name.to_string(), scale, offset: 0.0, required: false }
+    }
+
+    /// The same column, marked required
+    pub fn required(self) -> Self {
+        CsvColumn { required: true, ..self }
+    }
+
+    fn convert(&self, value: f64) -> f64 {
+        value * self.scale + self.offset
+    }
+}
+
+/// How the time column is written
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvTimeFormat {
+    /// Seconds after the column's scale and offset, e.g. a millisecond timer with scale 0.001
+    #[default]
+    Number,
+    /// ISO 8601 date-time; without an offset it is taken as UTC
+    Iso8601,
+    /// chrono `strftime` pattern such as `"%d.%m.%Y %H:%M:%S"`, UTC unless it contains `%z`
+    Custom(String),
+}
+
+/// Column layout of a CSV sensor log. Channels left as None are not imported.
+/// The wasm and Python entry points take it as JSON, e.g.
+/// `{"time": {"name": "secs"}, "speed": {"name": "kph", "scale": 0.27778}, "power": {"name": "watts"}}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CsvImportConfig {
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    pub time: CsvColumn,
+    #[serde(default)]
+    pub time_format: CsvTimeFormat,
+    /// Resample onto this interval (seconds) when the rows are unevenly spaced
+    #[serde(default)]
+    pub resample_interval: Option<f64>,
+    /// Ground speed, m/s
+    #[serde(default)]
+    pub speed: Option<CsvColumn>,
+    /// W
+    #[serde(default)]
+    pub power: Option<CsvColumn>,
+    /// Apparent wind speed from an air-speed sensor, m/s
+    #[serde(default)]
+    pub air_speed: Option<CsvColumn>,
+    /// Yaw of the apparent wind, degrees from straight ahead
+    #[serde(default)]
+    pub wind_angle: Option<CsvColumn>,
+    /// Degrees
+    #[serde(default)]
+    pub latitude: Option<CsvColumn>,
+    /// Degrees
+    #[serde(default)]
+    pub longitude: Option<CsvColumn>,
+    /// m
+    #[serde(default)]
+    pub altitude: Option<CsvColumn>,
+    /// Cumulative, m; computed from the positions when not mapped
+    #[serde(default)]
+    pub distance: Option<CsvColumn>,
+    #[serde(default)]
+    pub heart_rate: Option<CsvColumn>,
+    #[serde(default)]
+    pub cadence: Option<CsvColumn>,
+    /// °C
+    #[serde(default)]
+    pub temperature: Option<CsvColumn>,
+    /// % RH
+    #[serde(default)]
+    pub humidity: Option<CsvColumn>,
+    /// hPa
+    #[serde(default)]
+    pub pressure: Option<CsvColumn>,
+    /// m², e.g. the sensor's own CdA estimate
+    #[serde(default)]
+    pub cda_reference: Option<CsvColumn>,
+    /// Lap number; each run of rows with the same number becomes a lap
+    #[serde(default)]
+    pub lap: Option<CsvColumn>,
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+impl CsvImportConfig {
+    /// Only a time column; map the other channels by setting their fields
+    pub fn new(time: CsvColumn) -> Self {
+        CsvImportConfig {
+            delimiter: default_delimiter(),
+            time,
+            time_format: CsvTimeFormat::Number,
+            resample_interval: None,
+            speed: None,
+            power: None,
+            air_speed: None,
+            wind_angle: None,
+            latitude: None,
+            longitude: None,
+            altitude: None,
+            distance: None,
+            heart_rate: None,
+            cadence: None,
+            temperature: None,
+            humidity: None,
+            pressure: None,
+            cda_reference: None,
+            lap: None,
+        }
+    }
+
+    /// Gibli aero sensor export: millisecond timer, speed in cm/s, wind in km/h,
+    /// positions in 1e-7 degrees, altitude in mm and pressure in Pa, resampled to 1 Hz
+    pub fn gibli() -> Self {
+        CsvImportConfig {
+            resample_interval: Some(1.0),
+            speed: Some(CsvColumn::new("ANT+ Speed (cm/s)", 0.01).required()),
+            power: Some(CsvColumn::new("Power (w)", 1.0).required()),
+            air_speed: Some(CsvColumn::new("Wind Magnitude (km/h)", 1.0 / 3.6).required()),
+            wind_angle: Some(CsvColumn::new("Wind Angle (deg)", 1.0)),
+            latitude: Some(CsvColumn::new("Latitude", 1e-7).required()),
+            longitude: Some(CsvColumn::new("Longitude", 1e-7).required()),
+            altitude: Some(CsvColumn::new("GPS Altitude (mm)", 0.001).required()),
+            temperature: Some(CsvColumn::new("Temperature", 1.0)),
+            humidity: Some(CsvColumn::new("Humidity (%RH)", 1.0)),
+            pressure: Some(CsvColumn::new("Barometric Pressure (Pa)", 0.01)),
+            cda_reference: Some(CsvColumn::new("CdA", 1.0)),
+            lap: Some(CsvColumn::new("Lap Number", 1.0)),
+            ..CsvImportConfig::new(CsvColumn::new("Timer (ms)", 0.001).required())
+        }
+    }
+
+    fn column(&self, channel: Channel) -> Option<&CsvColumn> {
+        match channel {
+            Channel::Speed => self.speed.as_ref(),
+            Channel::Power => self.power.as_ref(),
+            Channel::AirSpeed => self.air_speed.as_ref(),
+            Channel::WindAngle => self.wind_angle.as_ref(),
+            Channel::Latitude => self.latitude.as_ref(),
+            Channel::Longitude => self.longitude.as_ref(),
+            Channel::Altitude => self.altitude.as_ref(),
+            Channel::Distance => self.distance.as_ref(),
+            Channel::HeartRate => self.heart_rate.as_ref(),
+            Channel::Cadence => self.cadence.as_ref(),
+            Channel::Temperature => self.temperature.as_ref(),
+            Channel::Humidity => self.humidity.as_ref(),
+            Channel::Pressure => self.pressure.as_ref(),
+            Channel::CdaReference => self.cda_reference.as_ref(),
+            Channel::Lap => self.lap.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Channel {
+    Speed,
+    Power,
+    AirSpeed,
+    WindAngle,
+    Latitude,
+    Longitude,
+    Altitude,
+    Distance,
+    HeartRate,
+    Cadence,
+    Temperature,
+    Humidity,
+    Pressure,
+    CdaReference,
+    Lap,
+}
+
+const CHANNELS: [Channel; 15] = [
+    Channel::Speed,
+    Channel::Power,
+    Channel::AirSpeed,
+    Channel::WindAngle,
+    Channel::Latitude,
+    Channel::Longitude,
+    Channel::Altitude,
+    Channel::Distance,
+    Channel::HeartRate,
+    Channel::Cadence,
+    Channel::Temperature,
+    Channel::Humidity,
+    Channel::Pressure,
+    Channel::CdaReference,
+    Channel::Lap,
+];
+
+/// An imported CSV: the records and laps as a [`ParsedFitFile`], plus the channels
+/// FIT has no field for. Those are empty when the column is absent and NaN where a
+/// cell was blank.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
+pub struct CsvImport {
+    parsed: ParsedFitFile,
+    wind_angle: Vec<f64>,
+    humidity: Vec<f64>,
+    pressure: Vec<f64>,
+    cda_reference: Vec<f64>,
+    lap_number: Vec<f64>,
+    temperature: Vec<f64>,
+    skipped_rows: usize,
+    resampled: bool,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CsvImport {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn parsed(&self) -> ParsedFitFile {
+        self.parsed.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn fit_data(&self) -> FitData {
+        self.parsed.fit_data()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn laps(&self) -> Vec<LapData> {
+        self.parsed.laps()
+    }
+
+    /// Yaw of the apparent wind, degrees
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn wind_angle(&self) -> Vec<f64> {
+        self.wind_angle.clone()
+    }
+
+    /// °C, NaN for blank cells (the FitData copy has 0 there)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn temperature(&self) -> Vec<f64> {
+        self.temperature.clone()
+    }
+
+    /// Relative humidity, %
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn humidity(&self) -> Vec<f64> {
+        self.humidity.clone()
+    }
+
+    /// Barometric pressure, hPa
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn pressure(&self) -> Vec<f64> {
+        self.pressure.clone()
+    }
+
+    /// CdA computed by the sensor itself, m²
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda_reference(&self) -> Vec<f64> {
+        self.cda_reference.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn lap_number(&self) -> Vec<f64> {
+        self.lap_number.clone()
+    }
+
+    /// Temperature, humidity and pressure are all present, enough for per-record air density
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn has_environmental_data(&self) -> bool {
+        !self.temperature.is_empty() && !self.humidity.is_empty() && !self.pressure.is_empty()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn has_cda_reference(&self) -> bool {
+        self.average_cda_reference().is_some()
+    }
+
+    /// Mean of the non-blank reference CdA values
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn average_cda_reference(&self) -> Option<f64> {
+        let values: Vec<f64> = self.cda_reference.iter().copied().filter(|v| v.is_finite()).collect();
+        (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+    }
+
+    /// Rows dropped for a missing, unreadable or non-increasing time
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn skipped_rows(&self) -> usize {
+        self.skipped_rows
+    }
+
+    /// The rows were unevenly spaced and have been interpolated onto `resample_interval`
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn resampled(&self) -> bool {
+        self.resampled
+    }
+}
+
+/// Parse a Gibli aero sensor CSV export
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn parse_csv_file(file_data: &[u8]) -> Result<CsvImport> {
+    import_csv(file_data, &CsvImportConfig::gibli())
+}
+
+/// Parse a CSV with the column layout in `config_json`, a [`CsvImportConfig`]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn parse_csv_file_with_config(file_data: &[u8], config_json: &str) -> Result<CsvImport> {
+    let config: CsvImportConfig = serde_json::from_str(config_json)
+        .map_err(|e| Error::InvalidInput(format!("Invalid CSV import config: {}", e)))?;
+    import_csv(file_data, &config)
+}
+
+/// Parse a CSV with a typed column layout
+pub fn import_csv(file_data: &[u8], config: &CsvImportConfig) -> Result<CsvImport> {
+    if file_data.len() > MAX_TRACK_FILE_SIZE {
+        return Err(Error::Validation(format!(
+            "File too large: {} bytes (maximum {} bytes)",
+            file_data.len(),
+            MAX_TRACK_FILE_SIZE
+        )));
+    }
+    let delimiter = utils::csv_delimiter(config.delimiter)?;
+
+    let mut table = read_table(file_data, delimiter, config)?;
+    let resampled = match config.resample_interval {
+        Some(interval) if !(interval > 0.0 && interval.is_finite()) => {
+            return Err(Error::InvalidInput(format!("Resample interval must be positive, got {}", interval)));
+        }
+        Some(interval) if is_irregular(&table.times, interval) => {
+            table = table.resample(interval)?;
+            true
+        }
+        _ => false,
+    };
+
+    let mut records = table.records();
+    if table.channel(Channel::Distance).is_none() {
+        fill_distance(&mut records);
+    }
+    fill_speed(&mut records);
+
+    let laps = table.lap_ranges().into_iter().map(|range| lap_from_samples(&records[range])).collect();
+    let messages = FitMessages {
+        records,
+        laps,
+        file_id: Some(FitFileId { file_type: Some("activity".to_string()), ..FitFileId::default() }),
+        ..FitMessages::default()
+    };
+
+    let extra = |channel| table.channel(channel).cloned().unwrap_or_default();
+    Ok(CsvImport {
+        parsed: ParsedFitFile::from_messages(messages, file_data.len(), FitDiagnostics::imported(file_data.len())),
+        wind_angle: extra(Channel::WindAngle),
+        humidity: extra(Channel::Humidity),
+        pressure: extra(Channel::Pressure),
+        cda_reference: extra(Channel::CdaReference),
+        lap_number: extra(Channel::Lap),
+        temperature: extra(Channel::Temperature),
+        skipped_rows: table.skipped_rows,
+        resampled,
+    })
+}
+
+/// Converted rows, one vector per mapped channel present in the file
+struct Table {
+    times: Vec<f64>,
+    channels: Vec<Option<Vec<f64>>>,
+    skipped_rows: usize,
+}
+
+fn read_table(file_data: &[u8], delimiter: u8, config: &CsvImportConfig) -> Result<Table> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(file_data);
+
+    let headers = reader.headers()
+        .map_err(|e| Error::CsvParse(format!("Could not read the header row: {}", e)))?
+        .clone();
+    let find = |column: &CsvColumn| headers.iter().position(|header| header == column.name.trim());
+
+    let missing: Vec<&str> = std::iter::once(Some(&config.time))
+        .chain(CHANNELS.iter().map(|&channel| config.column(channel)))
+        .flatten()
+        .filter(|column| column.required && find(column).is_none())
+        .map(|column| column.name.as_str())
+        .collect();
+    if !missing.is_empty() {
+        let list: Vec<String> = missing.iter().map(|name| format!("  - {}", name)).collect();
+        return Err(Error::CsvParse(format!("Missing required columns:\n{}", list.join("\n"))));
+    }
+
+    let time_index = find(&config.time)
+        .ok_or_else(|| Error::CsvParse(format!("Missing time column '{}'", config.time.name)))?;
+    let columns: Vec<Option<(usize, &CsvColumn)>> = CHANNELS.iter()
+        .map(|&channel| config.column(channel).and_then(|column| Some((find(column)?, column))))
+        .collect();
+
+    let mut table = Table {
+        times: Vec::new(),
+        channels: columns.iter().map(|column| column.map(|_| Vec::new())).collect(),
+        skipped_rows: 0,
+    };
+
+    let row_time = |row: &csv::StringRecord| row.get(time_index).and_then(|cell| parse_row_time(cell, config));
+    let mut rows = reader.records().peekable();
+    while let Some(row) = rows.next() {
+        let row = row.map_err(|e| Error::CsvParse(e.to_string()))?;
+        // Rows must move forward in time; repeated or unreadable times are dropped, and so is
+        // a lone jump ahead (a glitched timer) that the next row falls back from
+        let last = table.times.last().copied();
+        let next = rows.peek().and_then(|next| next.as_ref().ok()).and_then(row_time);
+        let time = row_time(&row).filter(|&t| {
+            last.is_none_or(|last| t > last) && !next.is_some_and(|next| next < t && last.is_none_or(|last| next > last))
+        });
+        let Some(time) = time else {
+            table.skipped_rows += 1;
+            continue;
+        };
+
+        table.times.push(time);
+        for (values, column) in table.channels.iter_mut().zip(&columns) {
+            if let (Some(values), Some((index, column))) = (values, column) {
+                let value = row.get(*index).and_then(parse_number).map_or(f64::NAN, |v| column.convert(v));
+                values.push(value);
+            }
+        }
+    }
+
+    if table.times.is_empty() {
+        return Err(Error::CsvParse("No data rows with a valid time".to_string()));
+    }
+    if table.skipped_rows > 0 {
+        utils::warn(&format!("Skipped {} CSV rows without a usable time", table.skipped_rows));
+    }
+    Ok(table)
+}
+
+fn parse_number(cell: &str) -> Option<f64> {
+    cell.parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+fn parse_row_time(cell: &str, config: &CsvImportConfig) -> Option<f64> {
+    match &config.time_format {
+        CsvTimeFormat::Number => parse_number(cell).map(|value| config.time.convert(value)),
+        CsvTimeFormat::Iso8601 => parse_time(cell),
+        CsvTimeFormat::Custom(format) => {
+            let utc = DateTime::parse_from_str(cell, format).ok().map(|time| time.to_utc())
+                .or_else(|| NaiveDateTime::parse_from_str(cell, format).ok().map(|time| time.and_utc()))?;
+            Some(utc.timestamp_millis() as f64 / 1000.0)
+        }
+    }
+}
+
+/// Spacing varies by more than a tenth of the target interval
+fn is_irregular(times: &[f64], interval: f64) -> bool {
+    if times.len() < 2 {
+        return false;
+    }
+    let steps: Vec<f64> = times.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let mean = steps.iter().sum::<f64>() / steps.len() as f64;
+    let variance = steps.iter().map(|step| (step - mean).powi(2)).sum::<f64>() / steps.len() as f64;
+    variance.sqrt() > 0.1 * interval
+}
+
+impl Table {
+    fn channel(&self, channel: Channel) -> Option<&Vec<f64>> {
+        let index = CHANNELS.iter().position(|&c| c == channel)?;
+        self.channels[index].as_ref()
+    }
+
+    /// Interpolate onto a regular grid covering the rows; lap numbers are held, not blended
+    fn resample(&self, interval: f64) -> Result<Table> {
+        let start = (self.times[0] / interval).floor() * interval;
+        let end = (self.times[self.times.len() - 1] / interval).ceil() * interval;
+        let steps = ((end - start) / interval).round();
+        if steps >= MAX_RESAMPLED_ROWS as f64 {
+            return Err(Error::CsvParse(format!(
+                "Resampling {} s of data every {} s would give more than {} rows",
+                end - start,
+                interval,
+                MAX_RESAMPLED_ROWS
+            )));
+        }
+        let count = steps as usize + 1;
+        let grid: Vec<f64> = (0..count).map(|i| start + i as f64 * interval).collect();
+
+        let channels = CHANNELS.iter().zip(&self.channels).map(|(&channel, values)| {
+            values.as_ref().map(|values| match channel {
+                Channel::Lap => grid.iter().map(|&t| {
+                    let index = self.times.partition_point(|&time| time <= t).saturating_sub(1);
+                    values[index]
+                }).collect(),
+                _ => utils::interpolate(&self.times, values, &grid),
+            })
+        }).collect();
+
+        Ok(Table { times: grid, channels, skipped_rows: self.skipped_rows })
+    }
+
+    fn records(&self) -> Vec<FitRecord> {
+        let value = |channel, i: usize| {
+            self.channel(channel).map(|values| values[i]).filter(|v| v.is_finite())
+        };
+
+        (0..self.times.len()).map(|i| {
+            let air_speed = value(Channel::AirSpeed, i);
+            FitRecord {
+                timestamp: self.times[i],
+                distance: value(Channel::Distance, i),
+                position_lat: value(Channel::Latitude, i),
+                position_long: value(Channel::Longitude, i),
+                altitude: value(Channel::Altitude, i),
+                speed: value(Channel::Speed, i),
+                power: value(Channel::Power, i),
+                heart_rate: value(Channel::HeartRate, i),
+                cadence: value(Channel::Cadence, i),
+                grade: None,
+                temperature: value(Channel::Temperature, i),
+                gps_accuracy: None,
+                calories: None,
+                air_speed,
+                // Head-on component of the measured apparent wind
+                wind_speed: value(Channel::WindAngle, i)
+                    .zip(air_speed)
+                    .map(|(angle, air_speed)| angle.to_radians().cos() * air_speed),
+                battery_soc: None,
+            }
+        }).collect()
+    }
+
+    /// Record ranges of the laps: runs of the same lap number, blank cells staying in the
+    /// current lap; the whole file when there is no lap column
+    fn lap_ranges(&self) -> Vec<std::ops::Range<usize>> {
+        let Some(laps) = self.channel(Channel::Lap) else {
+            return std::iter::once(0..self.times.len()).collect();
+        };
+
+        let mut ranges: Vec<std::ops::Range<usize>> = Vec::new();
+        let mut current = f64::NAN;
+        for (i, &lap) in laps.iter().enumerate() {
+            match ranges.last_mut() {
+                Some(range) if lap.is_nan() || lap == current => range.end = i + 1,
+                _ => {
+                    current = lap;
+                    ranges.push(i..i + 1);
+                }
+            }
+        }
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIBLI: &str = "\
+Timer (ms),ANT+ Speed (cm/s),Power (w),Wind Magnitude (km/h),Wind Angle (deg),Latitude,Longitude,GPS Altitude (mm),Temperature,Humidity (%RH),Barometric Pressure (Pa),CdA,Lap Number
+0,1000,200,36,0,470000000,70000000,500000,20,50,101325,0.25,1
+1000,1000,200,36,0,470000900,70000000,500500,20,50,101325,0.27,1
+1500,1100,220,39.6,60,470001400,70000000,501000,20,50,101300,nan,1
+3000,1200,240,43.2,60,470002900,70000000,501500,21,51,101300,0.30,2
+4000,1200,240,43.2,0,470003900,70000000,502000,21,51,101300,,2
+";
+
+    #[test]
+    fn test_gibli_units_resampling_and_laps() {
+        let import = parse_csv_file(GIBLI.as_bytes()).unwrap();
+        let data = import.fit_data();
+
+        // The 500 ms row makes the timer irregular: back on a 1 s grid
+        assert!(import.resampled());
+        assert_eq!(data.timestamps(), vec![0.0, 1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(data.velocity()[..2], [10.0, 10.0]);
+        assert!((data.velocity()[2] - 11.333).abs() < 1e-3);
+        assert_eq!(data.air_speed()[0], 10.0);
+        assert_eq!(data.position_lat()[0], 47.0);
+        assert_eq!(data.altitude()[4], 502.0);
+        assert!((data.distance()[4] - 43.4).abs() < 0.1);
+        // cos(60°) of 11 m/s, interpolated between the 1.5 s and 3 s rows
+        assert!((data.wind_speed()[2] - 0.5 * 11.333).abs() < 1e-3);
+
+        assert_eq!(import.pressure()[0], 1013.25);
+        assert!(import.has_environmental_data());
+        assert!((import.average_cda_reference().unwrap() - (0.25 + 0.27 + 0.30 + 0.30) / 4.0).abs() < 1e-9);
+
+        let laps = import.laps();
+        assert_eq!(import.lap_number(), vec![1.0, 1.0, 1.0, 2.0, 2.0]);
+        assert_eq!(laps.len(), 2);
+        assert_eq!((laps[1].start_time(), laps[1].end_time()), (3.0, 4.0));
+    }
+
+    #[test]
+    fn test_custom_layout_with_iso_times() {
+        let csv = "time;kph;watts\n\
+                   2024-06-01T10:00:00Z;36;200\n\
+                   2024-06-01T10:00:01Z;36;210\n\
+                   2024-06-01T10:00:01Z;36;999\n\
+                   not a time;36;999\n\
+                   2024-06-01T10:00:02Z;;220\n";
+        let config = r#"{"delimiter": ";", "time": {"name": "time"}, "time_format": "iso8601",
+                         "speed": {"name": "kph", "scale": 0.2777777777777778}, "power": {"name": "watts"},
+                         "humidity": {"name": "rh"}}"#;
+        let import = parse_csv_file_with_config(csv.as_bytes(), config).unwrap();
+        let data = import.fit_data();
+
+        assert_eq!(import.skipped_rows(), 2);
+        assert!(!import.resampled());
+        assert_eq!(data.timestamps(), vec![1_717_236_000.0, 1_717_236_001.0, 1_717_236_002.0]);
+        assert_eq!(data.power(), vec![200.0, 210.0, 220.0]);
+        assert!((data.velocity()[0] - 10.0).abs() < 1e-9);
+        // Blank speed and no distance or positions: nothing to derive it from
+        assert_eq!(data.velocity()[2], 0.0);
+        assert!(import.humidity().is_empty() && !import.has_environmental_data());
+        assert_eq!(import.laps().len(), 1);
+    }
+
+    #[test]
+    fn test_timer_glitch_and_resample_bound() {
+        let csv = "t,speed\n0,8\n1,8\n2,8\n1000000000000,8\n4,8\n5,8\n";
+        let mut config = CsvImportConfig {
+            speed: Some(CsvColumn::new("speed", 1.0)),
+            ..CsvImportConfig::new(CsvColumn::new("t", 1.0))
+        };
+        let import = import_csv(csv.as_bytes(), &config).unwrap();
+        // Only the glitched row goes; the rows after it are kept
+        assert_eq!(import.skipped_rows(), 1);
+        assert_eq!(import.fit_data().timestamps(), vec![0.0, 1.0, 2.0, 4.0, 5.0]);
+
+        config.resample_interval = Some(1e-9);
+        assert!(matches!(import_csv(csv.as_bytes(), &config), Err(Error::CsvParse(_))));
+        // A glitch on the last row has nothing to fall back to; resampling refuses the span
+        config.resample_interval = Some(1.0);
+        let csv = "t,speed\n0,8\n1,8\n2.5,8\n1000000000000,8\n";
+        assert!(matches!(import_csv(csv.as_bytes(), &config), Err(Error::CsvParse(_))));
+    }
+
+    #[test]
+    fn test_missing_required_columns() {
+        let error = parse_csv_file(b"Timer (ms),Power (w)\n0,200\n").unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("  - ANT+ Speed (cm/s)") && message.contains("  - GPS Altitude (mm)"));
+        assert!(!message.contains("Power (w)") && !message.contains("CdA"));
+
+        let config = serde_json::to_string(&CsvImportConfig::new(CsvColumn::new("t", 1.0))).unwrap();
+        assert!(matches!(parse_csv_file_with_config(b"t\nx\n", &config), Err(Error::CsvParse(_))));
+    }
+}
@@ -1,10 +1,15 @@
 use std::io::Cursor;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use tiff::decoder::{Decoder, DecodingResult, Limits};
 use tiff::tags::Tag;
 use proj4rs::Proj;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::utils;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
 pub struct DEMProcessor {
     width: u32,
     height: u32,
@@ -55,54 +60,235 @@ impl GeoTransform {
     }
 }
 
-#[wasm_bindgen]
+/// Resource caps checked against a DEM's TIFF tags before any raster memory is allocated
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all, set_all))]
+pub struct DemLimits {
+    /// Largest raster accepted, in pixels (width × height)
+    pub max_pixels: usize,
+    /// Largest TIFF file accepted, in bytes
+    pub max_file_bytes: usize,
+    /// Peak decoding memory: the decoder's output buffer plus the f32 elevation grid
+    pub max_decoded_bytes: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DemLimits {
+    /// Defaults fit a 1 GB f32 grid, which a browser tab can usually still hold
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> DemLimits {
+        DemLimits {
+            max_pixels: 250_000_000,
+            max_file_bytes: 1_000_000_000,
+            max_decoded_bytes: 1_000_000_000,
+        }
+    }
+
+    /// The first limit `estimate` exceeds, as a user-facing message
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn violation(&self, estimate: &DemMemoryEstimate) -> Option<String> {
+        if estimate.file_bytes > self.max_file_bytes {
+            Some(format!(
+                "DEM file is {} but the limit is {}",
+                format_bytes(estimate.file_bytes),
+                format_bytes(self.max_file_bytes)
+            ))
+        } else if estimate.pixels > self.max_pixels {
+            Some(format!(
+                "DEM is {}x{} ({} pixels) but the limit is {} pixels",
+                estimate.width, estimate.height, estimate.pixels, self.max_pixels
+            ))
+        } else if estimate.peak_bytes > self.max_decoded_bytes {
+            Some(format!(
+                "Decoding this {}x{} DEM needs {} of memory but the limit is {}",
+                estimate.width,
+                estimate.height,
+                format_bytes(estimate.peak_bytes),
+                format_bytes(self.max_decoded_bytes)
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for DemLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DemLimits {
+    fn check(&self, estimate: &DemMemoryEstimate) -> Result<()> {
+        match self.violation(estimate) {
+            Some(message) => Err(Error::Dem(format!(
+                "DEM Too Large\n\n{}.\n\n\
+                Crop or downsample the DEM to the area of your ride, for example:\n\
+                gdal_translate -projwin <ulx> <uly> <lrx> <lry> input.tif output.tif",
+                message
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn tiff_limits(&self) -> Limits {
+        let mut limits = Limits::default();
+        limits.decoding_buffer_size = self.max_decoded_bytes;
+        limits.ifd_value_size = self.max_file_bytes;
+        limits.intermediate_buffer_size = self.max_decoded_bytes;
+        limits
+    }
+}
+
+/// Memory a DEM needs, worked out from its TIFF tags without decoding the raster
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
+pub struct DemMemoryEstimate {
+    width: u32,
+    height: u32,
+    pixels: usize,
+    samples_per_pixel: u32,
+    bits_per_sample: u32,
+    /// TIFF Compression tag (1 = none, 5 = LZW, 8 = Deflate)
+    compression: u32,
+    file_bytes: usize,
+    /// Output buffer the TIFF decoder allocates for the raster
+    decoded_bytes: usize,
+    /// f32 elevation grid kept by DEMProcessor
+    grid_bytes: usize,
+    /// Both at once while converting; f32 rasters are kept as decoded
+    peak_bytes: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DemMemoryEstimate {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn width(&self) -> u32 { self.width }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn height(&self) -> u32 { self.height }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn pixels(&self) -> usize { self.pixels }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn samples_per_pixel(&self) -> u32 { self.samples_per_pixel }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn bits_per_sample(&self) -> u32 { self.bits_per_sample }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn compression(&self) -> u32 { self.compression }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn file_bytes(&self) -> usize { self.file_bytes }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn decoded_bytes(&self) -> usize { self.decoded_bytes }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn grid_bytes(&self) -> usize { self.grid_bytes }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn peak_bytes(&self) -> usize { self.peak_bytes }
+}
+
+impl DemMemoryEstimate {
+    fn from_decoder(decoder: &mut Decoder<Cursor<&[u8]>>, file_bytes: usize) -> Result<DemMemoryEstimate> {
+        let (width, height) = decoder.dimensions()
+            .map_err(|e| Error::Dem(format!("Failed to get image dimensions: {}", e)))?;
+
+        // Tag defaults from the TIFF 6.0 spec when absent
+        let compression = decoder.find_tag_unsigned::<u32>(Tag::Compression).ok().flatten().unwrap_or(1);
+        let samples_per_pixel = decoder.find_tag_unsigned::<u32>(Tag::SamplesPerPixel).ok().flatten().unwrap_or(1);
+        let bits_per_sample = decoder
+            .find_tag_unsigned_vec::<u32>(Tag::BitsPerSample)
+            .ok()
+            .flatten()
+            .and_then(|bits| bits.first().copied())
+            .unwrap_or(1);
+        let sample_format = decoder.find_tag_unsigned::<u32>(Tag::SampleFormat).ok().flatten().unwrap_or(1);
+
+        let pixels = width as u64 * height as u64;
+        let samples = pixels.saturating_mul(samples_per_pixel as u64);
+        let decoded_bytes = samples.saturating_mul(bits_per_sample.div_ceil(8) as u64);
+        let grid_bytes = pixels.saturating_mul(4);
+        let kept_as_decoded = samples_per_pixel == 1 && bits_per_sample == 32 && sample_format == 3;
+        let peak_bytes = if kept_as_decoded { decoded_bytes } else { decoded_bytes.saturating_add(grid_bytes) };
+
+        // usize is 32 bits in wasm; saturating keeps oversized rasters over any limit
+        let to_usize = |value: u64| usize::try_from(value).unwrap_or(usize::MAX);
+        Ok(DemMemoryEstimate {
+            width,
+            height,
+            pixels: to_usize(pixels),
+            samples_per_pixel,
+            bits_per_sample,
+            compression,
+            file_bytes,
+            decoded_bytes: to_usize(decoded_bytes),
+            grid_bytes: to_usize(grid_bytes),
+            peak_bytes: to_usize(peak_bytes),
+        })
+    }
+
+    fn check_uncompressed_size(&self) -> Result<()> {
+        // The decoder allocates the whole raster up front, so make sure uncompressed pixel
+        // data can actually be in the file before trusting the declared dimensions
+        if self.compression == 1 && self.decoded_bytes > self.file_bytes {
+            return Err(Error::Dem(format!(
+                "TIFF declares {}x{} uncompressed pixels ({} bytes) but the file is only {} bytes",
+                self.width, self.height, self.decoded_bytes, self.file_bytes
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1_000_000_000 {
+        format!("{:.1} GB", bytes as f64 / 1e9)
+    } else {
+        format!("{:.0} MB", bytes as f64 / 1e6)
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl DEMProcessor {
     /// Create a new DEMProcessor from GeoTIFF file bytes
-    #[wasm_bindgen(constructor)]
-    pub fn new(file_data: &[u8], filename: Option<String>) -> Result<DEMProcessor, JsValue> {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(file_data: &[u8], filename: Option<String>) -> Result<DEMProcessor> {
         Self::new_with_world_file(file_data, filename, None, None)
     }
 
     /// Create a new DEMProcessor from TIFF file bytes with optional world file and projection file
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn new_with_world_file(
         file_data: &[u8],
         filename: Option<String>,
         world_file_data: Option<String>,
         proj_file_data: Option<String>
-    ) -> Result<DEMProcessor, JsValue> {
-        // Create custom limits for large DEM files (328MB file)
-        let mut limits = Limits::default();
-        limits.decoding_buffer_size = 2_000_000_000; // 2GB
-        limits.ifd_value_size = 500_000_000; // 500MB for large arrays
-        limits.intermediate_buffer_size = 500_000_000; // 500MB
+    ) -> Result<DEMProcessor> {
+        Self::new_with_limits(file_data, filename, world_file_data, proj_file_data, &DemLimits::default())
+    }
 
-        let cursor = Cursor::new(file_data);
-        let mut decoder = Decoder::new(cursor)
-            .map_err(|e| {
-                let error_msg = format!("{}", e);
-                JsValue::from_str(&format!(
-                    "Failed to Read DEM File\n\n\
-                    The file does not appear to be a valid TIFF/GeoTIFF format.\n\n\
-                    Supported formats:\n\
-                    - GeoTIFF (.tif, .tiff)\n\
-                    - Uncompressed or LZW/Deflate compression\n\
-                    - With optional .tfw (world file) and .prj (projection file)\n\n\
-                    If you have a different format:\n\
-                    - HFA (.img) files: Convert with GDAL\n\
-                    - ASCII Grid (.asc): Convert with GDAL\n\
-                    - HGT (SRTM): Convert with GDAL\n\n\
-                    Conversion command:\n\
-                    gdal_translate -of GTiff input.img output.tif\n\n\
-                    Technical error: {}",
-                    error_msg
-                ))
-            })?
-            .with_limits(limits);
+    /// Like `new_with_world_file`, but rejects DEMs over `limits` before the raster is decoded
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn new_with_limits(
+        file_data: &[u8],
+        filename: Option<String>,
+        world_file_data: Option<String>,
+        proj_file_data: Option<String>,
+        limits: &DemLimits
+    ) -> Result<DEMProcessor> {
+        let mut decoder = Self::open_tiff(file_data, limits)?;
 
-        // Get image dimensions
-        let (width, height) = decoder.dimensions()
-            .map_err(|e| JsValue::from_str(&format!("Failed to get image dimensions: {}", e)))?;
+        let estimate = DemMemoryEstimate::from_decoder(&mut decoder, file_data.len())?;
+        limits.check(&estimate)?;
+        estimate.check_uncompressed_size()?;
+        let (width, height) = (estimate.width, estimate.height);
 
         // Read the image data
         let image_data = decoder.read_image()
@@ -124,7 +310,7 @@ impl DEMProcessor {
                         } else { "" }
                     } else { "" };
 
-                    JsValue::from_str(&format!(
+                    Error::Dem(format!(
                         "Unsupported TIFF Compression Format{}\n\n\
                         This DEM file uses a compression method not supported by the web browser.\n\n\
                         SOLUTION OPTIONS:\n\n\
@@ -144,7 +330,7 @@ impl DEMProcessor {
                         error_msg
                     ))
                 } else {
-                    JsValue::from_str(&format!("Failed to read image: {}", error_msg))
+                    Error::Dem(format!("Failed to read image: {}", error_msg))
                 }
             })?;
 
@@ -162,10 +348,10 @@ impl DEMProcessor {
             tiff::decoder::DecodingResult::F32(_) => "F32 (32-bit float)",
             tiff::decoder::DecodingResult::F64(_) => "F64 (64-bit float)",
         };
-        web_sys::console::log_1(&format!("TIFF data type: {}", data_type_name).into());
+        utils::log(&format!("TIFF data type: {}", data_type_name));
 
         if is_u8 {
-            web_sys::console::warn_1(&"Warning: U8 DEM detected (8-bit, 0-255 range). This is a low-quality format with limited elevation range.".into());
+            utils::warn("Warning: U8 DEM detected (8-bit, 0-255 range). This is a low-quality format with limited elevation range.");
         }
 
         // Convert to f32 array
@@ -188,10 +374,8 @@ impl DEMProcessor {
 
         // Validate: if world file is provided without .prj file, warn user
         if world_file_data.is_some() && proj_file_data.is_none() {
-            web_sys::console::warn_1(&
-                "World file loaded without projection file (.prj). Coordinate system is ambiguous. \
-                Assuming coordinates are in the projection detected from world file values, or WGS84 if geographic."
-            .into());
+            utils::warn("World file loaded without projection file (.prj). Coordinate system is ambiguous. \
+                Assuming coordinates are in the projection detected from world file values, or WGS84 if geographic.");
         }
 
         // Validate bounds - check if coordinates look suspicious (e.g., [0,0,1,1])
@@ -201,7 +385,7 @@ impl DEMProcessor {
         // If bounds are suspiciously small (like [0,0,1,1]), the GeoTIFF tags are likely missing
         if transform.origin_x.abs() < 10.0 && transform.origin_y.abs() < 10.0 &&
            max_x.abs() < 10.0 && max_y.abs() < 10.0 {
-            web_sys::console::warn_1(&format!(
+            utils::warn(&format!(
                 "Warning: DEM bounds look incorrect [{:.1}, {:.1}, {:.1}, {:.1}]\n\
                 This usually means the GeoTIFF file is missing geospatial tags.\n\n\
                 To fix this:\n\
@@ -210,7 +394,7 @@ impl DEMProcessor {
                 3. Or use gdalinfo to check if the file has embedded georeferencing:\n\
                    gdalinfo your_file.tif",
                 transform.origin_x, transform.origin_y, max_x, max_y
-            ).into());
+            ));
         }
 
         // Initialize coordinate transformers based on detected projection
@@ -232,10 +416,10 @@ impl DEMProcessor {
     }
 
     /// Perform batch elevation lookup for multiple lat/lon coordinates
-    #[wasm_bindgen]
-    pub fn batch_lookup(&mut self, lats: Vec<f64>, lons: Vec<f64>) -> Result<Vec<f64>, JsValue> {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn batch_lookup(&mut self, lats: Vec<f64>, lons: Vec<f64>) -> Result<Vec<f64>> {
         if lats.len() != lons.len() {
-            return Err(JsValue::from_str("lats and lons must have the same length"));
+            return Err(Error::InvalidInput("lats and lons must have the same length".to_string()));
         }
 
         let mut altitudes = Vec::with_capacity(lats.len());
@@ -265,7 +449,9 @@ impl DEMProcessor {
             let (col, row) = self.transform.geo_to_pixel(x, y);
 
             // Check if coordinates are within bounds
-            if col < 0.0 || row < 0.0 || col >= self.width as f64 || row >= self.height as f64 {
+            // (a degenerate transform or non-finite input yields NaN, which compares false)
+            if col.is_nan() || row.is_nan() ||
+               col < 0.0 || row < 0.0 || col >= self.width as f64 || row >= self.height as f64 {
                 altitudes.push(f64::NAN);
                 continue;
             }
@@ -285,7 +471,7 @@ impl DEMProcessor {
     }
 
     /// Get the elevation error rate (percentage of failed lookups)
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn get_bounds(&self) -> Vec<f64> {
         let (min_x, max_y) = self.transform.pixel_to_geo(0.0, 0.0);
         let (max_x, min_y) = self.transform.pixel_to_geo(self.width as f64, self.height as f64);
@@ -293,7 +479,7 @@ impl DEMProcessor {
     }
 
     /// Get metadata about the DEM
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn get_metadata(&self) -> String {
         format!(
             "{{\"width\": {}, \"height\": {}, \"nodata\": {}}}",
@@ -301,8 +487,44 @@ impl DEMProcessor {
         )
     }
 
+    /// Read a DEM's dimensions and sample format from its TIFF tags and estimate the memory
+    /// `new_with_limits` would need, without decoding the raster; check it with
+    /// `DemLimits::violation`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn estimate_memory(file_data: &[u8], limits: &DemLimits) -> Result<DemMemoryEstimate> {
+        let mut decoder = Self::open_tiff(file_data, limits)?;
+        DemMemoryEstimate::from_decoder(&mut decoder, file_data.len())
+    }
+
     // Helper methods (not exposed to JS)
 
+    fn open_tiff<'a>(file_data: &'a [u8], limits: &DemLimits) -> Result<Decoder<Cursor<&'a [u8]>>> {
+        let cursor = Cursor::new(file_data);
+        let decoder = Decoder::new(cursor)
+            .map_err(|e| {
+                let error_msg = format!("{}", e);
+                Error::Dem(format!(
+                    "Failed to Read DEM File\n\n\
+                    The file does not appear to be a valid TIFF/GeoTIFF format.\n\n\
+                    Supported formats:\n\
+                    - GeoTIFF (.tif, .tiff)\n\
+                    - Uncompressed or LZW/Deflate compression\n\
+                    - With optional .tfw (world file) and .prj (projection file)\n\n\
+                    If you have a different format:\n\
+                    - HFA (.img) files: Convert with GDAL\n\
+                    - ASCII Grid (.asc): Convert with GDAL\n\
+                    - HGT (SRTM): Convert with GDAL\n\n\
+                    Conversion command:\n\
+                    gdal_translate -of GTiff input.img output.tif\n\n\
+                    Technical error: {}",
+                    error_msg
+                ))
+            })?
+            .with_limits(limits.tiff_limits());
+
+        Ok(decoder)
+    }
+
     fn get_pixel_value(&self, col: usize, row: usize) -> f32 {
         let idx = row * self.width as usize + col;
         if idx < self.data.len() {
@@ -335,19 +557,31 @@ impl DEMProcessor {
         }
     }
 
-    fn convert_to_f32(data: DecodingResult, _size: usize) -> Result<Vec<f32>, JsValue> {
-        match data {
-            DecodingResult::U8(values) => Ok(values.iter().map(|&v| v as f32).collect()),
-            DecodingResult::U16(values) => Ok(values.iter().map(|&v| v as f32).collect()),
-            DecodingResult::U32(values) => Ok(values.iter().map(|&v| v as f32).collect()),
-            DecodingResult::U64(values) => Ok(values.iter().map(|&v| v as f32).collect()),
-            DecodingResult::I8(values) => Ok(values.iter().map(|&v| v as f32).collect()),
-            DecodingResult::I16(values) => Ok(values.iter().map(|&v| v as f32).collect()),
-            DecodingResult::I32(values) => Ok(values.iter().map(|&v| v as f32).collect()),
-            DecodingResult::I64(values) => Ok(values.iter().map(|&v| v as f32).collect()),
-            DecodingResult::F32(values) => Ok(values),
-            DecodingResult::F64(values) => Ok(values.iter().map(|&v| v as f32).collect()),
+    fn convert_to_f32(data: DecodingResult, size: usize) -> Result<Vec<f32>> {
+        let values: Vec<f32> = match data {
+            DecodingResult::U8(values) => values.iter().map(|&v| v as f32).collect(),
+            DecodingResult::U16(values) => values.iter().map(|&v| v as f32).collect(),
+            DecodingResult::U32(values) => values.iter().map(|&v| v as f32).collect(),
+            DecodingResult::U64(values) => values.iter().map(|&v| v as f32).collect(),
+            DecodingResult::I8(values) => values.iter().map(|&v| v as f32).collect(),
+            DecodingResult::I16(values) => values.iter().map(|&v| v as f32).collect(),
+            DecodingResult::I32(values) => values.iter().map(|&v| v as f32).collect(),
+            DecodingResult::I64(values) => values.iter().map(|&v| v as f32).collect(),
+            DecodingResult::F32(values) => values,
+            DecodingResult::F64(values) => values.iter().map(|&v| v as f32).collect(),
+        };
+
+        // Multi-band or sub-byte rasters don't decode to one sample per pixel, and
+        // indexing them as a single-band grid would return the wrong elevations
+        if values.len() != size {
+            return Err(Error::Dem(format!(
+                "DEM must be a single-band raster: decoded {} samples for {} pixels",
+                values.len(),
+                size
+            )));
         }
+
+        Ok(values)
     }
 
     fn parse_geotransform(
@@ -355,23 +589,23 @@ impl DEMProcessor {
         filename: Option<&str>,
         width: u32,
         height: u32
-    ) -> Result<GeoTransform, JsValue> {
+    ) -> Result<GeoTransform> {
         // First, try to read GeoTIFF tags (ModelPixelScaleTag and ModelTiepointTag)
         if let Some(transform) = Self::parse_geotiff_tags(decoder, width, height) {
-            web_sys::console::log_1(&format!(
+            utils::log(&format!(
                 "Parsed GeoTIFF tags: origin=({}, {}), pixel_size=({}, {})",
                 transform.origin_x, transform.origin_y, transform.pixel_width, transform.pixel_height
-            ).into());
+            ));
             return Ok(transform);
         }
 
         // Try to parse SRTM-style filename (e.g., N47E007.tif or n47_e007_1arc_v3.tif)
         if let Some(fname) = filename {
             if let Some(transform) = Self::parse_srtm_filename(fname, width, height) {
-                web_sys::console::log_1(&format!(
+                utils::log(&format!(
                     "Parsed SRTM filename: origin=({}, {}), pixel_size=({}, {})",
                     transform.origin_x, transform.origin_y, transform.pixel_width, transform.pixel_height
-                ).into());
+                ));
                 return Ok(transform);
             }
         }
@@ -380,7 +614,7 @@ impl DEMProcessor {
         let pixel_width = 1.0 / width as f64;
         let pixel_height = -1.0 / height as f64;
 
-        web_sys::console::warn_1(&"Using generic 1-degree grid transform".into());
+        utils::warn("Using generic 1-degree grid transform");
         Ok(GeoTransform {
             origin_x: 0.0,
             origin_y: 1.0,
@@ -406,13 +640,13 @@ impl DEMProcessor {
 
         // Try to read GDAL_METADATA tag (42112) which might contain transformation info
         if let Ok(metadata) = decoder.get_tag_ascii_string(Tag::Unknown(42112)) {
-            web_sys::console::log_1(&format!("Found GDAL_METADATA: {}", metadata).into());
+            utils::log(&format!("Found GDAL_METADATA: {}", metadata));
         }
 
         // Try ModelTransformationTag (34264) first - this is a direct affine matrix
         if let Ok(transform_matrix) = decoder.get_tag_f64_vec(Tag::Unknown(34264)) {
             if transform_matrix.len() == 16 {
-                web_sys::console::log_1(&format!("Found ModelTransformationTag (34264): {:?}", transform_matrix).into());
+                utils::log(&format!("Found ModelTransformationTag (34264): {:?}", transform_matrix));
                 // Transform matrix is in column-major order:
                 // [0,4,8,12] = first column (X transformation)
                 // [1,5,9,13] = second column (Y transformation)
@@ -432,15 +666,15 @@ impl DEMProcessor {
         // Try to read ModelPixelScaleTag (33550) - contains [ScaleX, ScaleY, ScaleZ]
         let pixel_scale = match decoder.get_tag_f64_vec(Tag::Unknown(33550)) {
             Ok(scale) if scale.len() >= 2 => {
-                web_sys::console::log_1(&format!("Found ModelPixelScaleTag: {:?}", scale).into());
+                utils::log(&format!("Found ModelPixelScaleTag: {:?}", scale));
                 scale
             },
             Err(e) => {
-                web_sys::console::log_1(&format!("Failed to read ModelPixelScaleTag (33550): {:?}", e).into());
+                utils::log(&format!("Failed to read ModelPixelScaleTag (33550): {:?}", e));
                 return None;
             },
             Ok(scale) => {
-                web_sys::console::log_1(&format!("ModelPixelScaleTag has insufficient data: len={}", scale.len()).into());
+                utils::log(&format!("ModelPixelScaleTag has insufficient data: len={}", scale.len()));
                 return None;
             }
         };
@@ -449,15 +683,15 @@ impl DEMProcessor {
         // where (I,J,K) is pixel coordinate and (X,Y,Z) is geographic coordinate
         let tiepoints = match decoder.get_tag_f64_vec(Tag::Unknown(33922)) {
             Ok(points) if points.len() >= 6 => {
-                web_sys::console::log_1(&format!("Found ModelTiepointTag: {:?}", points).into());
+                utils::log(&format!("Found ModelTiepointTag: {:?}", points));
                 points
             },
             Err(e) => {
-                web_sys::console::log_1(&format!("Failed to read ModelTiepointTag (33922): {:?}", e).into());
+                utils::log(&format!("Failed to read ModelTiepointTag (33922): {:?}", e));
                 return None;
             },
             Ok(points) => {
-                web_sys::console::log_1(&format!("ModelTiepointTag has insufficient data: len={}", points.len()).into());
+                utils::log(&format!("ModelTiepointTag has insufficient data: len={}", points.len()));
                 return None;
             }
         };
@@ -481,10 +715,10 @@ impl DEMProcessor {
         let origin_x = geo_x - (pixel_i * scale_x);
         let origin_y = geo_y + (pixel_j * scale_y);
 
-        web_sys::console::log_1(&format!(
+        utils::log(&format!(
             "GeoTIFF tags - Tiepoint: pixel({}, {})->geo({}, {}), Scale: ({}, {})",
             pixel_i, pixel_j, geo_x, geo_y, scale_x, scale_y
-        ).into());
+        ));
 
         Some(GeoTransform {
             origin_x,
@@ -496,7 +730,7 @@ impl DEMProcessor {
         })
     }
 
-    fn parse_world_file(world_file_content: &str) -> Result<GeoTransform, JsValue> {
+    fn parse_world_file(world_file_content: &str) -> Result<GeoTransform> {
         // World file format (.tfw, .tiff.aux.xml, etc.):
         // Line 1: pixel width (A)
         // Line 2: rotation Y (D)
@@ -507,7 +741,7 @@ impl DEMProcessor {
 
         let lines: Vec<&str> = world_file_content.lines().collect();
         if lines.len() < 6 {
-            return Err(JsValue::from_str(&format!(
+            return Err(Error::WorldFile(format!(
                 "Invalid World File Format\n\n\
                 World files (.tfw, .tifw, .jgw, etc.) must contain exactly 6 lines.\n\
                 Found {} lines in the provided world file.\n\n\
@@ -524,22 +758,22 @@ impl DEMProcessor {
         }
 
         let pixel_width = lines[0].trim().parse::<f64>()
-            .map_err(|e| JsValue::from_str(&format!("Failed to parse pixel width: {}", e)))?;
+            .map_err(|e| Error::WorldFile(format!("Failed to parse pixel width: {}", e)))?;
         let rotation_y = lines[1].trim().parse::<f64>()
-            .map_err(|e| JsValue::from_str(&format!("Failed to parse rotation Y: {}", e)))?;
+            .map_err(|e| Error::WorldFile(format!("Failed to parse rotation Y: {}", e)))?;
         let rotation_x = lines[2].trim().parse::<f64>()
-            .map_err(|e| JsValue::from_str(&format!("Failed to parse rotation X: {}", e)))?;
+            .map_err(|e| Error::WorldFile(format!("Failed to parse rotation X: {}", e)))?;
         let pixel_height = lines[3].trim().parse::<f64>()
-            .map_err(|e| JsValue::from_str(&format!("Failed to parse pixel height: {}", e)))?;
+            .map_err(|e| Error::WorldFile(format!("Failed to parse pixel height: {}", e)))?;
         let origin_x = lines[4].trim().parse::<f64>()
-            .map_err(|e| JsValue::from_str(&format!("Failed to parse origin X: {}", e)))?;
+            .map_err(|e| Error::WorldFile(format!("Failed to parse origin X: {}", e)))?;
         let origin_y = lines[5].trim().parse::<f64>()
-            .map_err(|e| JsValue::from_str(&format!("Failed to parse origin Y: {}", e)))?;
+            .map_err(|e| Error::WorldFile(format!("Failed to parse origin Y: {}", e)))?;
 
-        web_sys::console::log_1(&format!(
+        utils::log(&format!(
             "Parsed world file: origin=({}, {}), pixel_size=({}, {}), rotation=({}, {})",
             origin_x, origin_y, pixel_width, pixel_height, rotation_x, rotation_y
-        ).into());
+        ));
 
         Ok(GeoTransform {
             origin_x,
@@ -559,7 +793,7 @@ impl DEMProcessor {
         let mut lat_start = None;
         let mut lon_start = None;
 
-        for (i, ch) in upper.chars().enumerate() {
+        for (i, ch) in upper.char_indices() {
             if ch == 'N' || ch == 'S' {
                 lat_start = Some(i);
             } else if ch == 'E' || ch == 'W' {
@@ -587,10 +821,10 @@ impl DEMProcessor {
                 let tile_size_m = 50000.0; // 50km per tile
                 let pixel_size = tile_size_m / width as f64;
 
-                web_sys::console::log_1(&format!(
+                utils::log(&format!(
                     "Projected coordinates: northing={}, easting={}, pixel={}m",
                     northing, easting, pixel_size
-                ).into());
+                ));
 
                 return Some(GeoTransform {
                     origin_x: easting,
@@ -643,7 +877,7 @@ impl DEMProcessor {
         Some(-9999.0)
     }
 
-    fn setup_projection(transform: &GeoTransform) -> Result<(Option<Proj>, Option<Proj>), JsValue> {
+    fn setup_projection(transform: &GeoTransform) -> Result<(Option<Proj>, Option<Proj>)> {
         // Detect if DEM uses projected coordinates vs geographic (WGS84)
         if transform.origin_x.abs() > 1000.0 || transform.origin_y.abs() > 1000.0 {
             // Projected coordinates detected
@@ -657,13 +891,13 @@ impl DEMProcessor {
 
             if x > 2_000_000.0 && x < 8_000_000.0 && y > 1_000_000.0 && y < 6_000_000.0 {
                 // Likely European projection (ETRS89LAEA)
-                web_sys::console::log_1(&"Detected projected CRS, setting up WGS84→ETRS89LAEA transformation".into());
+                utils::log("Detected projected CRS, setting up WGS84→ETRS89LAEA transformation");
 
                 let wgs84 = Proj::from_proj_string("+proj=longlat +datum=WGS84 +no_defs")
-                    .map_err(|e| JsValue::from_str(&format!("Failed to create WGS84 projection: {:?}", e)))?;
+                    .map_err(|e| Error::Projection(format!("Failed to create WGS84 projection: {:?}", e)))?;
 
                 let etrs89laea = Proj::from_proj_string("+proj=laea +lat_0=52 +lon_0=10 +x_0=4321000 +y_0=3210000 +ellps=GRS80 +units=m +no_defs")
-                    .map_err(|e| JsValue::from_str(&format!("Failed to create ETRS89LAEA projection: {:?}", e)))?;
+                    .map_err(|e| Error::Projection(format!("Failed to create ETRS89LAEA projection: {:?}", e)))?;
 
                 Ok((Some(wgs84), Some(etrs89laea)))
             } else if x > 100_000.0 && x < 900_000.0 && y > 0.0 && y < 10_000_000.0 {
@@ -672,31 +906,31 @@ impl DEMProcessor {
                 // UTM zones 10-19 cover most of USA
                 let zone = Self::estimate_utm_zone_from_coords(x, y);
 
-                web_sys::console::log_1(&format!(
+                utils::log(&format!(
                     "Detected projected CRS (likely UTM Zone {}N), setting up WGS84→UTM transformation",
                     zone
-                ).into());
+                ));
 
                 let wgs84 = Proj::from_proj_string("+proj=longlat +datum=WGS84 +no_defs")
-                    .map_err(|e| JsValue::from_str(&format!("Failed to create WGS84 projection: {:?}", e)))?;
+                    .map_err(|e| Error::Projection(format!("Failed to create WGS84 projection: {:?}", e)))?;
 
                 // UTM projection with estimated zone
                 let utm_proj_string = format!("+proj=utm +zone={} +datum=WGS84 +units=m +no_defs", zone);
                 let utm = Proj::from_proj_string(&utm_proj_string)
-                    .map_err(|e| JsValue::from_str(&format!("Failed to create UTM projection: {:?}", e)))?;
+                    .map_err(|e| Error::Projection(format!("Failed to create UTM projection: {:?}", e)))?;
 
                 Ok((Some(wgs84), Some(utm)))
             } else {
                 // Unknown projected CRS - warn user
-                web_sys::console::warn_1(&format!(
+                utils::warn(&format!(
                     "Unknown projected CRS detected (X={}, Y={}). Elevation lookups may fail.",
                     x, y
-                ).into());
+                ));
                 Ok((None, None))
             }
         } else {
             // Geographic coordinates (WGS84)
-            web_sys::console::log_1(&"Detected geographic CRS (WGS84), no transformation needed".into());
+            utils::log("Detected geographic CRS (WGS84), no transformation needed");
             Ok((None, None))
         }
     }
@@ -737,11 +971,12 @@ impl DEMProcessor {
     fn setup_projection_from_prj(
         _transform: &GeoTransform,
         prj_content: &str
-    ) -> Result<(Option<Proj>, Option<Proj>), JsValue> {
+    ) -> Result<(Option<Proj>, Option<Proj>)> {
         // Parse .prj file (WKT format) to extract projection information
         // Example: PROJCS["GCS North American 1983 UTM Zone 16N (Calculated)", ...]
 
-        web_sys::console::log_1(&format!("Parsing .prj file: {}", &prj_content[..100.min(prj_content.len())]).into());
+        let preview: String = prj_content.chars().take(100).collect();
+        utils::log(&format!("Parsing .prj file: {}", preview));
 
         // Check if it's NAD27, NAD83 or WGS84 datum
         let datum = if prj_content.contains("NAD83") || prj_content.contains("North_American_Datum_1983") {
@@ -759,29 +994,28 @@ impl DEMProcessor {
                 Self::extract_transverse_mercator_params(prj_content) {
 
                 let utm_zone = Self::extract_utm_zone_from_prj(prj_content);
-                let proj_type = if utm_zone.is_some() {
-                    format!("UTM-like Zone {}", utm_zone.unwrap())
-                } else {
-                    "Transverse Mercator".to_string()
+                let proj_type = match utm_zone {
+                    Some(zone) => format!("UTM-like Zone {}", zone),
+                    None => "Transverse Mercator".to_string(),
                 };
 
-                web_sys::console::log_1(&format!(
+                utils::log(&format!(
                     "Detected {} projection ({}): central_meridian={}, false_easting={}, false_northing={}, scale_factor={}, lat_0={}",
                     proj_type, datum, central_meridian, false_easting, false_northing, scale_factor, latitude_of_origin
-                ).into());
+                ));
 
                 let wgs84 = Proj::from_proj_string("+proj=longlat +datum=WGS84 +no_defs")
-                    .map_err(|e| JsValue::from_str(&format!("Failed to create WGS84 projection: {:?}", e)))?;
+                    .map_err(|e| Error::Projection(format!("Failed to create WGS84 projection: {:?}", e)))?;
 
                 // Build Transverse Mercator projection string with actual parameters from .prj
                 // NAD83 and WGS84 are nearly identical for most purposes (differ by <2m in CONUS)
                 // proj4rs may not support NAD83/NAD27 directly, so we use WGS84 as approximation
                 // For high-precision work, proper datum transformation would be needed
                 let proj_datum = if datum == "NAD83" || datum == "NAD27" {
-                    web_sys::console::log_1(&format!(
+                    utils::log(&format!(
                         "Note: Using WGS84 as approximation for {} (difference <2m in CONUS)",
                         datum
-                    ).into());
+                    ));
                     "WGS84"
                 } else {
                     datum
@@ -793,12 +1027,12 @@ impl DEMProcessor {
                 );
 
                 let tm_proj = Proj::from_proj_string(&tm_proj_string)
-                    .map_err(|e| JsValue::from_str(&format!("Failed to create TM projection: {:?}", e)))?;
+                    .map_err(|e| Error::Projection(format!("Failed to create TM projection: {:?}", e)))?;
 
-                web_sys::console::log_1(&format!(
+                utils::log(&format!(
                     "Created projections - WGS84: '+proj=longlat +datum=WGS84', TM: '{}'",
                     tm_proj_string
-                ).into());
+                ));
 
                 return Ok((Some(wgs84), Some(tm_proj)));
             }
@@ -808,20 +1042,20 @@ impl DEMProcessor {
         let utm_zone = Self::extract_utm_zone_from_prj(prj_content);
 
         if let Some(zone) = utm_zone {
-            web_sys::console::log_1(&format!(
+            utils::log(&format!(
                 "Detected standard UTM Zone {} ({}) from .prj file, setting up WGS84→UTM transformation",
                 zone, datum
-            ).into());
+            ));
 
             let wgs84 = Proj::from_proj_string("+proj=longlat +datum=WGS84 +no_defs")
-                .map_err(|e| JsValue::from_str(&format!("Failed to create WGS84 projection: {:?}", e)))?;
+                .map_err(|e| Error::Projection(format!("Failed to create WGS84 projection: {:?}", e)))?;
 
             // Standard UTM projection - use WGS84 approximation for NAD83/NAD27
             let proj_datum = if datum == "NAD83" || datum == "NAD27" {
-                web_sys::console::log_1(&format!(
+                utils::log(&format!(
                     "Note: Using WGS84 as approximation for {} (difference <2m in CONUS)",
                     datum
-                ).into());
+                ));
                 "WGS84"
             } else {
                 datum
@@ -829,17 +1063,17 @@ impl DEMProcessor {
 
             let utm_proj_string = format!("+proj=utm +zone={} +datum={} +units=m +no_defs", zone, proj_datum);
             let utm = Proj::from_proj_string(&utm_proj_string)
-                .map_err(|e| JsValue::from_str(&format!("Failed to create UTM projection: {:?}", e)))?;
+                .map_err(|e| Error::Projection(format!("Failed to create UTM projection: {:?}", e)))?;
 
-            web_sys::console::log_1(&format!(
+            utils::log(&format!(
                 "Created projections - WGS84: '+proj=longlat +datum=WGS84', Projected: '{}'",
                 utm_proj_string
-            ).into());
+            ));
 
             Ok((Some(wgs84), Some(utm)))
         } else {
             // Couldn't parse projection, fallback to geographic
-            web_sys::console::warn_1(&"Could not parse projection from .prj file, assuming geographic coordinates".into());
+            utils::warn("Could not parse projection from .prj file, assuming geographic coordinates");
             Ok((None, None))
         }
     }
@@ -864,11 +1098,11 @@ impl DEMProcessor {
                     .collect();
 
                 if let Ok(zone) = zone_str.parse::<u8>() {
-                    if zone >= 1 && zone <= 60 {
-                        web_sys::console::log_1(&format!(
+                    if (1..=60).contains(&zone) {
+                        utils::log(&format!(
                             "Extracted UTM zone {} from .prj file",
                             zone
-                        ).into());
+                        ));
                         return Some(zone);
                     }
                 }
@@ -888,11 +1122,11 @@ impl DEMProcessor {
                     // Formula: zone = floor((lon + 180) / 6) + 1
                     let zone = ((central_meridian + 180.0) / 6.0).floor() as i32 + 1;
 
-                    if zone >= 1 && zone <= 60 {
-                        web_sys::console::log_1(&format!(
+                    if (1..=60).contains(&zone) {
+                        utils::log(&format!(
                             "Detected UTM zone {} from central_meridian {} (false_easting={}, scale_factor={})",
                             zone, central_meridian, false_easting, scale_factor
-                        ).into());
+                        ));
                         return Some(zone as u8);
                     }
                 }
@@ -908,9 +1142,10 @@ impl DEMProcessor {
 
         // Helper function to extract a parameter value (case-insensitive)
         fn extract_parameter(content: &str, param_name: &str) -> Option<f64> {
-            // Convert both to uppercase for case-insensitive matching
-            let upper_content = content.to_uppercase();
-            let upper_param = param_name.to_uppercase();
+            // Convert both to ASCII uppercase for case-insensitive matching; full Unicode
+            // uppercasing can change byte lengths and misalign indices into `content`
+            let upper_content = content.to_ascii_uppercase();
+            let upper_param = param_name.to_ascii_uppercase();
 
             // Look for PARAMETER["param_name",value] - case insensitive
             let search_pattern = format!("PARAMETER[\"{}\"", upper_param);
@@ -976,4 +1211,64 @@ mod tests {
         assert!((col - 10.0).abs() < 1e-6);
         assert!((row - 20.0).abs() < 1e-6);
     }
+
+    #[test]
+    fn test_non_ascii_names_and_prj_do_not_panic() {
+        // Found by the prj fuzz target: byte offsets from a Unicode-uppercased copy
+        // were used to slice the original string
+        let prj = "PROJCS[\"ßß\",PROJECTION[\"Transverse_Mercator\"],PARAMETER[\"Central_Meridian\",-87.0]]";
+        let params = DEMProcessor::extract_transverse_mercator_params(prj).unwrap();
+        assert_eq!(params.0, -87.0);
+        assert!(DEMProcessor::setup_projection_from_prj(&GeoTransform {
+            origin_x: 0.0,
+            origin_y: 0.0,
+            pixel_width: 1.0,
+            pixel_height: -1.0,
+            rotation_x: 0.0,
+            rotation_y: 0.0,
+        }, &"é".repeat(80)).is_ok());
+
+        let transform = DEMProcessor::parse_srtm_filename("süd_n47e007.tif", 4, 4).unwrap();
+        assert_eq!((transform.origin_x, transform.origin_y), (7.0, 48.0));
+    }
+
+    const F32_TIFF: &[u8] = include_bytes!("../fuzz/corpus/dem_tiff/seed-f32-4x4.tif");
+    const U16_TIFF: &[u8] = include_bytes!("../fuzz/corpus/dem_tiff/seed-u16-4x4.tif");
+
+    #[test]
+    fn test_estimate_memory_from_tags() {
+        let limits = DemLimits::default();
+        let f32_estimate = DEMProcessor::estimate_memory(F32_TIFF, &limits).unwrap();
+        assert_eq!((f32_estimate.width(), f32_estimate.height(), f32_estimate.pixels()), (4, 4, 16));
+        assert_eq!((f32_estimate.decoded_bytes(), f32_estimate.peak_bytes()), (64, 64));
+
+        // Integer rasters are converted, so both buffers are alive at once
+        let u16_estimate = DEMProcessor::estimate_memory(U16_TIFF, &limits).unwrap();
+        assert_eq!((u16_estimate.bits_per_sample(), u16_estimate.compression()), (16, 1));
+        assert_eq!((u16_estimate.decoded_bytes(), u16_estimate.grid_bytes(), u16_estimate.peak_bytes()), (32, 64, 96));
+        assert!(limits.violation(&u16_estimate).is_none());
+    }
+
+    #[test]
+    fn test_limits_reject_before_decoding() {
+        let world_file = Some("0.25\n0.0\n0.0\n-0.25\n7.0\n48.0\n".to_string());
+        let mut dem = DEMProcessor::new_with_world_file(U16_TIFF, None, world_file.clone(), None).unwrap();
+        assert_eq!(dem.batch_lookup(vec![47.9], vec![7.1]).unwrap(), vec![100.0]);
+
+        let limits = [
+            DemLimits { max_pixels: 15, ..DemLimits::default() },
+            DemLimits { max_file_bytes: 100, ..DemLimits::default() },
+            DemLimits { max_decoded_bytes: 95, ..DemLimits::default() },
+        ];
+        for limit in limits {
+            let error = DEMProcessor::new_with_limits(U16_TIFF, None, world_file.clone(), None, &limit);
+            assert!(matches!(error, Err(Error::Dem(message)) if message.starts_with("DEM Too Large")));
+        }
+    }
+
+    #[test]
+    fn test_rejects_multi_band_raster() {
+        let rgb = DecodingResult::U8(vec![0; 4 * 4 * 3]);
+        assert!(matches!(DEMProcessor::convert_to_f32(rgb, 16), Err(Error::Dem(_))));
+    }
 }
@@ -0,0 +1,48 @@
+use thiserror::Error;
+
+/// Errors returned by the analyzer. Messages are written for end users,
+/// so the wasm adapter passes them through to JS unchanged.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// FIT bytes rejected by the security checks before parsing
+    #[error("Validation error: {0}")]
+    Validation(String),
+
+    /// FIT data the decoder could not read
+    #[error("Failed to parse FIT data: {0}")]
+    FitParse(String),
+
+    /// GPX or TCX file that could not be read
+    #[error("Failed to parse GPX/TCX data: {0}")]
+    TrackParse(String),
+
+    /// CSV sensor log that could not be read
+    #[error("Failed to parse CSV data: {0}")]
+    CsvParse(String),
+
+    /// DEM raster that could not be decoded
+    #[error("{0}")]
+    Dem(String),
+
+    /// Malformed .tfw/.jgw world file
+    #[error("{0}")]
+    WorldFile(String),
+
+    /// Coordinate system that could not be set up
+    #[error("{0}")]
+    Projection(String),
+
+    /// Argument out of range or inconsistent with the other arguments
+    #[error("{0}")]
+    InvalidInput(String),
+
+    /// Not enough usable data for the requested analysis
+    #[error("{0}")]
+    InsufficientData(String),
+
+    /// .veproj archive that could not be read or written
+    #[error("Project file error: {0}")]
+    Project(String),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,754 @@
+//! Per-sample CSV and JSON export of a VE run: the ride channels next to the
+//! computed ones, under a header with the parameters and metrics that produced them.
+//! The trimmed track also goes out as GPX, and as KML coloured by VE residual for
+//! reviewing problem spots in Google Earth or QGIS.
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use quick_xml::escape::escape;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{json, Value};
+use crate::error::{Error, Result};
+use crate::fit_parser::{FitData, LapData};
+use crate::utils;
+use crate::virtual_elevation::{VEParameters, VEResult};
+
+/// Unit system for exported values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(eq, eq_int))]
+pub enum ExportUnits {
+    /// m, m/s, kg, kg/m³
+    #[default]
+    Si,
+    /// km and km/h for distance and speed, otherwise SI
+    Metric,
+    /// mi, mph, ft, lb and lb/ft³
+    Imperial,
+}
+
+/// What goes into an export besides the samples
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all, set_all))]
+pub struct ExportOptions {
+    pub units: ExportUnits,
+    /// CSV field separator; ignored by the JSON export
+    pub delimiter: char,
+    /// Only the samples from trim_start to trim_end
+    pub trim_only: bool,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ExportOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> ExportOptions {
+        ExportOptions { units: ExportUnits::Si, delimiter: ',', trim_only: false }
+    }
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Elevation written for each track point
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(eq, eq_int))]
+pub enum TrackElevation {
+    /// The ride's own altitude channel
+    #[default]
+    Actual,
+    /// Elevation from `set_dem_elevation`
+    Dem,
+    /// Virtual elevation, shifted onto the actual altitude at trim_start
+    Virtual,
+}
+
+/// Physical quantity of an exported value, which fixes its unit and precision
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quantity {
+    Time,
+    Distance,
+    Speed,
+    Power,
+    Elevation,
+    Percent,
+    Acceleration,
+    Density,
+    Mass,
+    Angle,
+    Ratio,
+}
+
+const FEET_PER_METER: f64 = 1.0 / 0.3048;
+const MILES_PER_METER: f64 = 1.0 / 1609.344;
+const POUNDS_PER_KG: f64 = 1.0 / 0.453_592_37;
+const LB_FT3_PER_KG_M3: f64 = 0.062_427_96;
+
+impl Quantity {
+    /// Unit label, factor from SI, and decimals written
+    fn unit(self, units: ExportUnits) -> (&'static str, f64, i32) {
+        use ExportUnits::*;
+        match (self, units) {
+            (Quantity::Time, _) => ("s", 1.0, 3),
+            (Quantity::Distance, Si) => ("m", 1.0, 2),
+            (Quantity::Distance, Metric) => ("km", 0.001, 5),
+            (Quantity::Distance, Imperial) => ("mi", MILES_PER_METER, 5),
+            (Quantity::Speed, Si) => ("m/s", 1.0, 3),
+            (Quantity::Speed, Metric) => ("km/h", 3.6, 2),
+            (Quantity::Speed, Imperial) => ("mph", 3600.0 * MILES_PER_METER, 2),
+            (Quantity::Power, _) => ("W", 1.0, 1),
+            (Quantity::Elevation, Si | Metric) => ("m", 1.0, 3),
+            (Quantity::Elevation, Imperial) => ("ft", FEET_PER_METER, 2),
+            (Quantity::Percent, _) => ("%", 100.0, 3),
+            (Quantity::Acceleration, Si | Metric) => ("m/s²", 1.0, 4),
+            (Quantity::Acceleration, Imperial) => ("ft/s²", FEET_PER_METER, 4),
+            (Quantity::Density, Si | Metric) => ("kg/m³", 1.0, 5),
+            (Quantity::Density, Imperial) => ("lb/ft³", LB_FT3_PER_KG_M3, 6),
+            (Quantity::Mass, Si | Metric) => ("kg", 1.0, 2),
+            (Quantity::Mass, Imperial) => ("lb", POUNDS_PER_KG, 2),
+            (Quantity::Angle, _) => ("°", 1.0, 1),
+            (Quantity::Ratio, _) => ("", 1.0, 6),
+        }
+    }
+
+    /// Converted and rounded; None for NaN and infinities
+    fn convert(self, value: f64, units: ExportUnits) -> Option<f64> {
+        let (_, factor, decimals) = self.unit(units);
+        let scale = 10f64.powi(decimals);
+        value.is_finite().then(|| (value * factor * scale).round() / scale)
+    }
+
+    fn label(self, name: &str, units: ExportUnits) -> String {
+        match self.unit(units).0 {
+            "" => name.to_string(),
+            unit => format!("{} ({})", name, unit),
+        }
+    }
+}
+
+const COLUMNS: [(&str, Quantity); 11] = [
+    ("time", Quantity::Time),
+    ("distance", Quantity::Distance),
+    ("speed", Quantity::Speed),
+    ("power", Quantity::Power),
+    ("altitude", Quantity::Elevation),
+    ("virtual_elevation", Quantity::Elevation),
+    ("slope", Quantity::Percent),
+    ("acceleration", Quantity::Acceleration),
+    ("effective_wind", Quantity::Speed),
+    ("apparent_velocity", Quantity::Speed),
+    ("rho", Quantity::Density),
+];
+
+/// A VE run ready to export: its ride data, parameters, chosen CdA/Crr and trim region.
+/// Virtual elevation is written shifted onto the actual altitude at trim_start, as plotted.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
+pub struct VeExport {
+    fit_data: FitData,
+    result: VEResult,
+    params: VEParameters,
+    cda: f64,
+    crr: f64,
+    trim_start: usize,
+    trim_end: usize,
+    rho_array: Option<Vec<f64>>,
+    file_name: Option<String>,
+    laps: Vec<LapData>,
+    dem_elevation: Option<Vec<f64>>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl VeExport {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(
+        fit_data: FitData,
+        result: VEResult,
+        params: VEParameters,
+        cda: f64,
+        crr: f64,
+        trim_start: usize,
+        trim_end: usize,
+    ) -> VeExport {
+        VeExport {
+            fit_data,
+            result,
+            params,
+            cda,
+            crr,
+            trim_start,
+            trim_end,
+            rho_array: None,
+            file_name: None,
+            laps: Vec::new(),
+            dem_elevation: None,
+        }
+    }
+
+    /// Per-sample air density the run used; without it every row gets `params.rho`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_rho_array(&mut self, rho_array: Vec<f64>) {
+        self.rho_array = Some(rho_array);
+    }
+
+    /// Source file name for the header
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_file_name(&mut self, file_name: String) {
+        self.file_name = Some(file_name);
+    }
+
+    /// Laps whose starts are marked in the KML export
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_laps(&mut self, laps: Vec<LapData>) {
+        self.laps = laps;
+    }
+
+    /// Per-record DEM elevation (m) for `TrackElevation::Dem`, e.g. from `batch_lookup`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_dem_elevation(&mut self, dem_elevation: Vec<f64>) {
+        self.dem_elevation = Some(dem_elevation);
+    }
+
+    /// GPX 1.1 track of the trim region; records without a position fix are left out
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn write_gpx(&self, elevation: TrackElevation) -> Result<String> {
+        let track = self.track(elevation)?;
+        let name = escape(self.track_name()).into_owned();
+
+        let mut gpx = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        gpx.push_str(&format!(
+            "<gpx version=\"1.1\" creator=\"virtual-elevation-analyzer {}\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+            env!("CARGO_PKG_VERSION")
+        ));
+        gpx.push_str(&format!("  <metadata>\n    <name>{}</name>\n", name));
+        if let Some(time) = track.first().and_then(|point| point.time.as_ref()) {
+            gpx.push_str(&format!("    <time>{}</time>\n", time));
+        }
+        gpx.push_str("  </metadata>\n");
+        gpx.push_str(&format!("  <trk>\n    <name>{}</name>\n    <trkseg>\n", name));
+        for point in &track {
+            gpx.push_str(&format!("      <trkpt lat=\"{:.7}\" lon=\"{:.7}\">", point.lat, point.lon));
+            if let Some(elevation) = point.elevation {
+                gpx.push_str(&format!("<ele>{:.2}</ele>", elevation));
+            }
+            if let Some(time) = &point.time {
+                gpx.push_str(&format!("<time>{}</time>", time));
+            }
+            gpx.push_str("</trkpt>\n");
+        }
+        gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+        Ok(gpx)
+    }
+
+    /// KML of the trim region: the track split into runs coloured by VE residual
+    /// (VE minus actual altitude), and placemarks at trim_start, trim_end and lap starts.
+    /// Z values carry the chosen elevation; the line itself is clamped to the ground.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn write_kml(&self, elevation: TrackElevation) -> Result<String> {
+        let track = self.track(elevation)?;
+        let name = escape(self.track_name()).into_owned();
+
+        let mut kml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n");
+        kml.push_str(&format!("  <name>{}</name>\n", name));
+        for band in RESIDUAL_BANDS.iter().chain([&NO_RESIDUAL]) {
+            kml.push_str(&format!(
+                "  <Style id=\"{}\"><LineStyle><color>{}</color><width>4</width></LineStyle></Style>\n",
+                band.id, band.color
+            ));
+        }
+        kml.push_str("  <Style id=\"marker\"><IconStyle><scale>1.1</scale></IconStyle></Style>\n");
+
+        kml.push_str("  <Folder>\n    <name>Track by VE residual</name>\n");
+        let mut start = 0;
+        while start < track.len() {
+            let band = residual_band(track[start].residual);
+            let mut end = start;
+            while end + 1 < track.len() && residual_band(track[end + 1].residual).id == band.id {
+                end += 1;
+            }
+            // Each run ends on the next run's first point so the line stays connected
+            let last = (end + 1).min(track.len() - 1);
+            let coordinates: Vec<String> = track[start..=last].iter().map(KmlPoint::coordinates).collect();
+            kml.push_str(&format!(
+                "    <Placemark>\n      <name>{}</name>\n      <description>Records {} to {}</description>\n      \
+<styleUrl>#{}</styleUrl>\n      <LineString><tessellate>1</tessellate><altitudeMode>clampToGround</altitudeMode>\
+<coordinates>{}</coordinates></LineString>\n    </Placemark>\n",
+                band.label, track[start].index, track[end].index, band.id, coordinates.join(" ")
+            ));
+            start = end + 1;
+        }
+        kml.push_str("  </Folder>\n");
+
+        kml.push_str("  <Folder>\n    <name>Markers</name>\n");
+        for (label, index) in self.markers(&track) {
+            let Some(point) = track.iter().find(|point| point.index >= index) else { continue };
+            let residual = point.residual.map_or("n/a".to_string(), |r| format!("{:+.2} m", r));
+            kml.push_str(&format!(
+                "    <Placemark>\n      <name>{}</name>\n      <description>Record {}, {:.0} m, VE residual {}</description>\n      \
+<styleUrl>#marker</styleUrl>\n      <Point><coordinates>{}</coordinates></Point>\n    </Placemark>\n",
+                escape(label.as_str()), point.index, point.distance, residual, point.coordinates()
+            ));
+        }
+        kml.push_str("  </Folder>\n</Document>\n</kml>\n");
+        Ok(kml)
+    }
+
+    /// `# key (unit): value` header lines, then one delimited row per sample; blank cells are missing values
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn write_csv(&self, options: &ExportOptions) -> Result<String> {
+        // '#' starts the header lines
+        if options.delimiter == '#' {
+            return Err(Error::InvalidInput("Export delimiter must not be '#'".to_string()));
+        }
+        let delimiter = utils::csv_delimiter(options.delimiter)?;
+        let rows = self.rows(options)?;
+
+        let mut out = String::new();
+        for (name, quantity, value) in self.header(options.units) {
+            let label = quantity.map_or(name.to_string(), |quantity| quantity.label(name, options.units));
+            let value = match value {
+                Value::String(text) => text,
+                Value::Null => String::new(),
+                other => other.to_string(),
+            };
+            out.push_str(&format!("# {}: {}\n", label, value));
+        }
+
+        let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(Vec::new());
+        let csv_error = |e: csv::Error| Error::InvalidInput(format!("Could not write CSV: {}", e));
+        writer.write_record(COLUMNS.iter().map(|(name, quantity)| quantity.label(name, options.units)))
+            .map_err(csv_error)?;
+        for row in rows {
+            writer.write_record(row.iter().map(|value| value.map_or(String::new(), |v| v.to_string())))
+                .map_err(csv_error)?;
+        }
+        let body = writer.into_inner().map_err(|e| Error::InvalidInput(format!("Could not write CSV: {}", e)))?;
+        out.push_str(&String::from_utf8_lossy(&body));
+        Ok(out)
+    }
+
+    /// `{"header": {...}, "units": {...}, "columns": [...], "rows": [[...], ...]}`; missing values are null
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn write_json(&self, options: &ExportOptions) -> Result<String> {
+        let rows = self.rows(options)?;
+        let header = self.header(options.units).into_iter().map(|(name, _, value)| (name, value)).collect();
+        let quantities = [
+            ("distance", Quantity::Distance),
+            ("speed", Quantity::Speed),
+            ("elevation", Quantity::Elevation),
+            ("acceleration", Quantity::Acceleration),
+            ("density", Quantity::Density),
+            ("mass", Quantity::Mass),
+        ];
+        let units: Vec<(&str, Value)> = quantities.iter()
+            .map(|(name, quantity)| (*name, json!(quantity.unit(options.units).0)))
+            .collect();
+        let columns: Vec<Value> = COLUMNS.iter()
+            .map(|(name, quantity)| json!({ "name": name, "unit": quantity.unit(options.units).0 }))
+            .collect();
+
+        let export = JsonExport { header: OrderedObject(header), units: OrderedObject(units), columns, rows };
+        serde_json::to_string(&export)
+            .map_err(|e| Error::InvalidInput(format!("Could not serialize export: {}", e)))
+    }
+}
+
+impl VeExport {
+    /// Header entries in order, numbers with a quantity converted to `units`
+    fn header(&self, units: ExportUnits) -> Vec<(&'static str, Option<Quantity>, Value)> {
+        let result = &self.result;
+        let start_time = self.fit_data.timestamps_slice().first().and_then(|&t| utils::rfc3339(t));
+        let entries = vec![
+            ("crate_version", None, json!(env!("CARGO_PKG_VERSION"))),
+            ("file_name", None, json!(self.file_name)),
+            ("start_time", None, json!(start_time)),
+            ("cda", Some(Quantity::Ratio), json!(self.cda)),
+            ("crr", Some(Quantity::Ratio), json!(self.crr)),
+            ("system_mass", Some(Quantity::Mass), json!(self.params.system_mass)),
+            ("rho", Some(Quantity::Density), json!(self.params.rho)),
+            ("eta", Some(Quantity::Ratio), json!(self.params.eta)),
+            ("wind_speed", Some(Quantity::Speed), json!(self.params.wind_speed)),
+            ("wind_direction", Some(Quantity::Angle), json!(self.params.wind_direction)),
+            ("velodrome", None, json!(self.params.velodrome)),
+            ("trim_start", None, json!(self.trim_start)),
+            ("trim_end", None, json!(self.trim_end)),
+            ("r2", Some(Quantity::Ratio), json!(result.r2())),
+            ("rmse", Some(Quantity::Elevation), json!(result.rmse())),
+            ("ve_elevation_diff", Some(Quantity::Elevation), json!(result.ve_elevation_diff())),
+            ("actual_elevation_diff", Some(Quantity::Elevation), json!(result.actual_elevation_diff())),
+            ("virtual_distance_air", Some(Quantity::Distance), json!(result.virtual_distance_air())),
+            ("virtual_distance_ground", Some(Quantity::Distance), json!(result.virtual_distance_ground())),
+            ("vd_difference_percent", Some(Quantity::Ratio), json!(result.vd_difference_percent())),
+        ];
+        entries.into_iter().map(|(name, quantity, value)| {
+            let value = match (quantity, value.as_f64()) {
+                (Some(quantity), Some(v)) => json!(quantity.convert(v, units)),
+                _ => value,
+            };
+            (name, quantity, value)
+        }).collect()
+    }
+
+    /// Record count, trim region clamped to it, and VE shifted the way the metrics
+    /// shift it: onto the actual altitude at trim_start (unshifted without altitude)
+    fn calibrated(&self) -> Result<Calibrated> {
+        let count = self.fit_data.record_count();
+        let virtual_elevation = self.result.virtual_elevation();
+        if virtual_elevation.len() != count {
+            return Err(Error::InvalidInput(format!(
+                "VE result has {} samples but the ride has {} records",
+                virtual_elevation.len(),
+                count
+            )));
+        }
+        if count == 0 {
+            return Err(Error::InsufficientData("No records to export".to_string()));
+        }
+
+        let altitude = self.fit_data.altitude_slice();
+        let trim_end = self.trim_end.min(count - 1);
+        let trim_start = self.trim_start.min(trim_end);
+        let has_altitude = !self.params.velodrome && altitude.iter().any(|&a| a.is_finite() && a != 0.0);
+        let offset = match altitude.get(trim_start) {
+            Some(&a) if has_altitude && a.is_finite() => a - virtual_elevation[trim_start],
+            _ => 0.0,
+        };
+        Ok(Calibrated {
+            count,
+            trim_start,
+            trim_end,
+            virtual_elevation: virtual_elevation.iter().map(|ve| ve + offset).collect(),
+            has_altitude,
+        })
+    }
+
+    /// Converted sample rows in `COLUMNS` order
+    fn rows(&self, options: &ExportOptions) -> Result<Vec<Vec<Option<f64>>>> {
+        let Calibrated { count, trim_start, trim_end, virtual_elevation, .. } = self.calibrated()?;
+        let data = &self.fit_data;
+        let columns = [
+            data.timestamps_slice().iter().map(|t| t - data.timestamps_slice()[0]).collect(),
+            data.distance_slice().to_vec(),
+            data.velocity_slice().to_vec(),
+            data.power_slice().to_vec(),
+            data.altitude_slice().to_vec(),
+            virtual_elevation,
+            self.result.virtual_slope(),
+            self.result.acceleration(),
+            self.result.effective_wind(),
+            self.result.apparent_velocity(),
+            (0..count).map(|i| {
+                self.rho_array.as_ref().and_then(|rho| rho.get(i).copied()).unwrap_or(self.params.rho)
+            }).collect::<Vec<f64>>(),
+        ];
+
+        let range = if options.trim_only { trim_start..trim_end + 1 } else { 0..count };
+        Ok(range.map(|i| {
+            columns.iter().zip(&COLUMNS).map(|(values, (_, quantity))| {
+                values.get(i).and_then(|&v| quantity.convert(v, options.units))
+            }).collect()
+        }).collect())
+    }
+
+    fn track_name(&self) -> &str {
+        self.file_name.as_deref().unwrap_or("Virtual elevation track")
+    }
+
+    /// Trim-region points with a position fix, elevation from `source`
+    fn track(&self, source: TrackElevation) -> Result<Vec<KmlPoint>> {
+        let Calibrated { count, trim_start, trim_end, virtual_elevation, has_altitude } = self.calibrated()?;
+        let data = &self.fit_data;
+        let elevation: Vec<f64> = match source {
+            TrackElevation::Actual => data.altitude_slice().to_vec(),
+            TrackElevation::Virtual => virtual_elevation.clone(),
+            TrackElevation::Dem => match &self.dem_elevation {
+                Some(dem) if dem.len() == count => dem.clone(),
+                Some(dem) => return Err(Error::InvalidInput(format!(
+                    "DEM elevation has {} values for {} records", dem.len(), count
+                ))),
+                None => return Err(Error::InvalidInput("No DEM elevation set for the track export".to_string())),
+            },
+        };
+        let value = |values: &[f64], i: usize| values.get(i).copied().filter(|v| v.is_finite());
+        let (lat, lon) = (data.position_lat_slice(), data.position_long_slice());
+        let altitude = data.altitude_slice();
+
+        let track: Vec<KmlPoint> = (trim_start..=trim_end).filter_map(|i| {
+            let (lat, lon) = (value(lat, i)?, value(lon, i)?);
+            // Missing positions come through as 0, 0
+            if lat == 0.0 && lon == 0.0 {
+                return None;
+            }
+            let residual = value(altitude, i).filter(|_| has_altitude).map(|actual| virtual_elevation[i] - actual);
+            Some(KmlPoint {
+                index: i,
+                lat,
+                lon,
+                elevation: value(&elevation, i),
+                time: value(data.timestamps_slice(), i).and_then(utils::rfc3339),
+                distance: value(data.distance_slice(), i).unwrap_or(0.0),
+                residual,
+            })
+        }).collect();
+
+        if track.is_empty() {
+            return Err(Error::InsufficientData("No GPS positions in the trim region".to_string()));
+        }
+        Ok(track)
+    }
+
+    /// Labels and record indices of the trim ends and the lap starts inside the track
+    fn markers(&self, track: &[KmlPoint]) -> Vec<(String, usize)> {
+        let (first, last) = (track[0].index, track[track.len() - 1].index);
+        let timestamps = self.fit_data.timestamps_slice();
+        let mut markers = vec![("Trim start".to_string(), first)];
+        for (number, lap) in self.laps.iter().enumerate() {
+            let index = timestamps.partition_point(|&t| t < lap.start_time());
+            if index > first && index <= last {
+                markers.push((format!("Lap {}", number + 1), index));
+            }
+        }
+        markers.push(("Trim end".to_string(), last));
+        markers
+    }
+}
+
+struct KmlPoint {
+    index: usize,
+    lat: f64,
+    lon: f64,
+    elevation: Option<f64>,
+    time: Option<String>,
+    distance: f64,
+    /// VE minus actual altitude, m
+    residual: Option<f64>,
+}
+
+impl KmlPoint {
+    fn coordinates(&self) -> String {
+        match self.elevation {
+            Some(elevation) => format!("{:.7},{:.7},{:.2}", self.lon, self.lat, elevation),
+            None => format!("{:.7},{:.7}", self.lon, self.lat),
+        }
+    }
+}
+
+/// Residual range drawn in one KML colour (aabbggrr)
+struct ResidualBand {
+    id: &'static str,
+    label: &'static str,
+    /// Residuals below this fall in the band
+    upper: f64,
+    color: &'static str,
+}
+
+/// Blue where VE runs below the actual altitude, green within half a metre, red above
+const RESIDUAL_BANDS: [ResidualBand; 5] = [
+    ResidualBand { id: "residual_below_2", label: "VE more than 2 m below", upper: -2.0, color: "ffff0000" },
+    ResidualBand { id: "residual_below", label: "VE 0.5-2 m below", upper: -0.5, color: "ffffaa00" },
+    ResidualBand { id: "residual_ok", label: "VE within 0.5 m", upper: 0.5, color: "ff00cc00" },
+    ResidualBand { id: "residual_above", label: "VE 0.5-2 m above", upper: 2.0, color: "ff00aaff" },
+    ResidualBand { id: "residual_above_2", label: "VE more than 2 m above", upper: f64::INFINITY, color: "ff0000ff" },
+];
+
+const NO_RESIDUAL: ResidualBand = ResidualBand {
+    id: "residual_none",
+    label: "No actual altitude",
+    upper: f64::NAN,
+    color: "ff888888",
+};
+
+fn residual_band(residual: Option<f64>) -> &'static ResidualBand {
+    match residual {
+        Some(residual) => RESIDUAL_BANDS.iter().find(|band| residual < band.upper).unwrap_or(&RESIDUAL_BANDS[4]),
+        None => &NO_RESIDUAL,
+    }
+}
+
+struct Calibrated {
+    count: usize,
+    trim_start: usize,
+    trim_end: usize,
+    virtual_elevation: Vec<f64>,
+    has_altitude: bool,
+}
+
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    header: OrderedObject<'a>,
+    units: OrderedObject<'a>,
+    columns: Vec<Value>,
+    rows: Vec<Vec<Option<f64>>>,
+}
+
+/// JSON object keeping its keys in insertion order (serde_json's `Map` sorts them)
+struct OrderedObject<'a>(Vec<(&'a str, Value)>);
+
+impl Serialize for OrderedObject<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(key, value)| (key, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::RideBuilder;
+    use crate::virtual_elevation::VirtualElevationCalculator;
+
+    /// 60 s climbing at 1 m per 10 s
+    fn ride() -> RideBuilder {
+        RideBuilder::new(1_700_000_000.0, 60)
+            .speed(|_| 8.0)
+            .power(|_| 220.0)
+            .altitude(|k| 100.0 + k as f64 * 0.1)
+    }
+
+    /// VE run over `fit_data` at CdA 0.3, Crr 0.004, trimmed to records 10..=50
+    fn export_for(fit_data: FitData) -> VeExport {
+        let params = VEParameters { system_mass: 80.0, ..VEParameters::new() };
+        let calculator = VirtualElevationCalculator::new(fit_data.clone().into(), params.clone());
+        let result = calculator.calculate_virtual_elevation(0.3, 0.004, 10, 50);
+        VeExport::new(fit_data, result, params, 0.3, 0.004, 10, 50)
+    }
+
+    fn export() -> VeExport {
+        export_for(ride().build())
+    }
+
+    #[test]
+    fn test_csv_header_units_and_trim() {
+        let mut export = export();
+        export.set_file_name("ride.fit".to_string());
+        let options = ExportOptions { units: ExportUnits::Metric, delimiter: ';', trim_only: true };
+        let csv = export.write_csv(&options).unwrap();
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[1], "# file_name: ride.fit");
+        assert_eq!(lines[2], "# start_time: 2023-11-14T22:13:20+00:00");
+        assert!(lines.contains(&"# cda: 0.3"));
+        assert!(lines.contains(&"# trim_start: 10"));
+        let header = lines.iter().position(|line| !line.starts_with('#')).unwrap();
+        assert_eq!(lines[header], "time (s);distance (km);speed (km/h);power (W);altitude (m);virtual_elevation (m);\
+slope (%);acceleration (m/s²);effective_wind (km/h);apparent_velocity (km/h);rho (kg/m³)");
+
+        // trim_start..=trim_end, with VE meeting the actual altitude at trim_start
+        let rows: Vec<Vec<&str>> = lines[header + 1..].iter().map(|line| line.split(';').collect()).collect();
+        assert_eq!(rows.len(), 41);
+        assert_eq!(rows[0][0], "10");
+        assert_eq!(rows[0][2], "28.8");
+        assert_eq!(rows[0][4], rows[0][5]);
+        assert_eq!(rows[0][10], "1.225");
+    }
+
+    #[test]
+    fn test_json_imperial_and_rho_array() {
+        let mut export = export();
+        let mut rho = vec![1.2; 60];
+        rho[30] = f64::NAN;
+        export.set_rho_array(rho);
+        let options = ExportOptions { units: ExportUnits::Imperial, ..ExportOptions::new() };
+        let json: Value = serde_json::from_str(&export.write_json(&options).unwrap()).unwrap();
+
+        assert_eq!(json["header"]["system_mass"], 176.37);
+        assert_eq!(json["header"]["file_name"], Value::Null);
+        assert_eq!(json["units"]["elevation"], "ft");
+        assert_eq!(json["columns"][4], json!({ "name": "altitude", "unit": "ft" }));
+
+        let rows = json["rows"].as_array().unwrap();
+        assert_eq!(rows.len(), 60);
+        assert_eq!(rows[0][4], 328.08);
+        assert_eq!(rows[0][10], 0.074914);
+        assert_eq!(rows[30][10], Value::Null);
+    }
+
+    /// The same ride heading north, without a fix at record 20, and with VE pushed
+    /// 3 m below the altitude from record 40
+    fn track_export() -> VeExport {
+        let ride = ride().position(|k| if k == 20 { (0.0, 0.0) } else { (45.0 + k as f64 * 0.00007, 7.5) });
+        let mut export = export_for(ride.build());
+        // Moving the altitude instead of VE keeps the residual without rebuilding the result
+        let ve = export.result.virtual_elevation();
+        let offset = export.fit_data.altitude_slice()[10] - ve[10];
+        let altitude = (0..60).map(|i| ve[i] + offset + if i >= 40 { 3.0 } else { 0.0 }).collect();
+        export.fit_data.set_altitude(altitude);
+        export
+    }
+
+    #[test]
+    fn test_gpx_elevation_sources() {
+        let mut export = track_export();
+        export.set_file_name("ride & co.fit".to_string());
+        let gpx = export.write_gpx(TrackElevation::Actual).unwrap();
+        assert!(gpx.contains("<name>ride &amp; co.fit</name>"));
+        // trim_start..=trim_end without the record lacking a fix
+        assert_eq!(gpx.matches("<trkpt ").count(), 40);
+        assert!(gpx.contains("<trkpt lat=\"45.0007000\" lon=\"7.5000000\">"));
+        assert!(gpx.contains("<time>2023-11-14T22:13:30+00:00</time>"));
+
+        let actual = export.fit_data.altitude_slice()[45];
+        let virtual_gpx = export.write_gpx(TrackElevation::Virtual).unwrap();
+        assert!(gpx.contains(&format!("<ele>{:.2}</ele>", actual)));
+        assert!(virtual_gpx.contains(&format!("<ele>{:.2}</ele>", actual - 3.0)));
+
+        assert!(export.write_gpx(TrackElevation::Dem).is_err());
+        export.set_dem_elevation(vec![250.0; 60]);
+        let dem_gpx = export.write_gpx(TrackElevation::Dem).unwrap();
+        assert_eq!(dem_gpx.matches("<ele>250.00</ele>").count(), 40);
+    }
+
+    #[test]
+    fn test_kml_markers_and_residual_colors() {
+        let mut export = track_export();
+        let lap = |start: f64| -> LapData {
+            serde_json::from_value(json!({
+                "start_time": start, "end_time": start + 30.0, "total_elapsed_time": 30.0,
+                "total_distance": 240.0, "avg_power": 220.0, "avg_speed": 8.0, "max_speed": 8.0,
+                "start_position_lat": 45.0, "start_position_long": 7.5,
+            })).unwrap()
+        };
+        // The first lap starts before the trim region and gets no marker
+        export.set_laps(vec![lap(1_700_000_000.0), lap(1_700_000_030.0)]);
+        let kml = export.write_kml(TrackElevation::Actual).unwrap();
+
+        assert!(kml.contains("<name>Trim start</name>\n      <description>Record 10,"));
+        assert!(kml.contains("<name>Lap 2</name>\n      <description>Record 30,"));
+        assert!(kml.contains("<name>Trim end</name>\n      <description>Record 50,"));
+        assert!(!kml.contains("<name>Lap 1</name>"));
+
+        // Within half a metre up to record 39, 3 m below after; the runs share a point
+        assert!(kml.contains("<description>Records 10 to 39</description>\n      <styleUrl>#residual_ok</styleUrl>"));
+        assert!(kml.contains("<description>Records 40 to 50</description>\n      <styleUrl>#residual_below_2</styleUrl>"));
+        assert!(kml.contains("7.5000000,45.0028000"));
+        assert!(kml.contains("VE residual -3.00 m"));
+    }
+
+    #[test]
+    fn test_rejects_unreadable_delimiters() {
+        let export = export();
+        for delimiter in ['#', '"', '\n', '\r', 'é'] {
+            let options = ExportOptions { delimiter, ..ExportOptions::new() };
+            assert!(matches!(export.write_csv(&options), Err(Error::InvalidInput(_))), "{:?}", delimiter);
+        }
+        let options = ExportOptions { delimiter: '\t', ..ExportOptions::new() };
+        assert!(export.write_csv(&options).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_mismatched_result() {
+        let mut export = export();
+        export.fit_data = export.fit_data.select(0..30);
+        let mismatch = |error: Error| {
+            matches!(&error, Error::InvalidInput(message) if message.contains("60 samples but the ride has 30 records"))
+        };
+        assert!(mismatch(export.write_json(&ExportOptions::new()).unwrap_err()));
+        assert!(mismatch(export.write_csv(&ExportOptions::new()).unwrap_err()));
+    }
+}
@@ -0,0 +1,379 @@
+//! Rewrites a FIT file with corrected altitude, e.g. after a DEM lookup or a VE-based
+//! correction, so other tools can read the result. Every message is copied byte for
+//! byte except record messages, whose altitude fields are replaced and which can gain
+//! virtual elevation and CdA developer fields. Both CRCs are recomputed.
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use std::collections::HashMap;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use crate::error::{Error, Result};
+use crate::fit_messages::{Message, Messages};
+use crate::fit_parser::FitData;
+use crate::security::SecurityValidator;
+use crate::utils::fit_crc;
+
+/// FIT timestamps count from 1989-12-31T00:00:00Z
+const FIT_EPOCH: i64 = 631_065_600;
+
+const FIELD_DESCRIPTION: u16 = 206;
+const DEVELOPER_DATA_ID: u16 = 207;
+const RECORD: u16 = 20;
+const ALTITUDE_FIELD: u8 = 2;
+const ENHANCED_ALTITUDE_FIELD: u8 = 78;
+
+const BASE_UINT8: u8 = 0x02;
+const BASE_STRING: u8 = 0x07;
+const BASE_BYTE: u8 = 0x0D;
+const BASE_UINT16: u8 = 0x84;
+const BASE_UINT32: u8 = 0x86;
+const BASE_FLOAT32: u8 = 0x88;
+
+/// Identifies the developer fields this crate writes
+const APPLICATION_ID: [u8; 16] = *b"ve-analyzer-1.0\0";
+
+/// Replacement channels for a FIT file's record messages, matched to records by timestamp.
+/// Records with no matching timestamp keep their altitude and get invalid developer values.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
+pub struct FitRewrite {
+    timestamps: Vec<f64>,
+    altitude: Vec<f64>,
+    virtual_elevation: Option<Vec<f64>>,
+    cda: Option<Vec<f64>>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl FitRewrite {
+    /// Altitude to write comes from `fit_data`, so set it there first (`set_altitude`)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(fit_data: &FitData) -> FitRewrite {
+        FitRewrite {
+            timestamps: fit_data.timestamps(),
+            altitude: fit_data.altitude(),
+            virtual_elevation: None,
+            cda: None,
+        }
+    }
+
+    /// Add a `virtual_elevation` (m) developer field to every record
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_virtual_elevation(&mut self, virtual_elevation: Vec<f64>) {
+        self.virtual_elevation = Some(virtual_elevation);
+    }
+
+    /// Add a `cda` (m²) developer field to every record
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_cda(&mut self, cda: Vec<f64>) {
+        self.cda = Some(cda);
+    }
+
+    /// Encode `source` (the FIT file `fit_data` was parsed from) with the replacements.
+    /// Only the first file of a chained FIT file is rewritten; anything after it is copied.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn write(&self, source: &[u8]) -> Result<Vec<u8>> {
+        for (name, values) in [("virtual_elevation", &self.virtual_elevation), ("cda", &self.cda)] {
+            if let Some(values) = values.as_ref().filter(|values| values.len() != self.timestamps.len()) {
+                return Err(Error::InvalidInput(format!(
+                    "{} has {} values for {} records", name, values.len(), self.timestamps.len()
+                )));
+            }
+        }
+        // Header CRC and truncation don't matter here, the structure does
+        SecurityValidator::new().inspect_fit_data(source).check(true)?;
+        let header_size = source[0] as usize;
+        let data_size = LittleEndian::read_u32(&source[4..8]) as usize;
+        let data_end = header_size + data_size;
+        if data_size == 0 || data_end > source.len() {
+            return Err(Error::Validation("FIT file is truncated; recover it before rewriting".to_string()));
+        }
+        let data = &source[header_size..data_end];
+
+        let developer_fields = self.developer_fields();
+        let developer_index = match developer_fields.is_empty() {
+            true => None,
+            false => Some(free_developer_index(data)?),
+        };
+        let body = self.encode(data, &developer_fields, developer_index)?;
+
+        let mut file = source[..header_size].to_vec();
+        // Developer fields need protocol 2.0
+        if developer_index.is_some() && file[1] >> 4 < 2 {
+            file[1] = 0x20;
+        }
+        let body_size = u32::try_from(body.len())
+            .map_err(|_| Error::InvalidInput("Rewritten FIT data exceeds 4 GB".to_string()))?;
+        LittleEndian::write_u32(&mut file[4..8], body_size);
+        if header_size >= 14 {
+            let crc = fit_crc(0, &file[..header_size - 2]);
+            LittleEndian::write_u16(&mut file[header_size - 2..header_size], crc);
+        }
+        // With its own header CRC the header is left out of the data CRC
+        let crc_start = if header_size >= 14 { header_size } else { 0 };
+        file.extend_from_slice(&body);
+        let crc = fit_crc(0, &file[crc_start..]);
+        file.extend_from_slice(&crc.to_le_bytes());
+        if let Some(rest) = source.get(data_end + 2..) {
+            file.extend_from_slice(rest);
+        }
+        Ok(file)
+    }
+}
+
+/// One developer field this rewrite adds to records
+struct DeveloperField {
+    name: &'static str,
+    units: &'static str,
+    values: Vec<f64>,
+}
+
+impl FitRewrite {
+    fn developer_fields(&self) -> Vec<DeveloperField> {
+        let mut fields = Vec::new();
+        if let Some(values) = &self.virtual_elevation {
+            fields.push(DeveloperField { name: "virtual_elevation", units: "m", values: values.clone() });
+        }
+        if let Some(values) = &self.cda {
+            fields.push(DeveloperField { name: "cda", units: "m2", values: values.clone() });
+        }
+        fields
+    }
+
+    fn encode(&self, data: &[u8], developer_fields: &[DeveloperField], developer_index: Option<u8>) -> Result<Vec<u8>> {
+        // Records are matched on whole seconds, as the parser reads them
+        let mut by_time: HashMap<i64, usize> = HashMap::new();
+        for (i, &t) in self.timestamps.iter().enumerate() {
+            by_time.entry(t.round() as i64).or_insert(i);
+        }
+
+        let mut out = Vec::with_capacity(data.len() + data.len() / 4);
+        // Local types whose record definition had no altitude, so enhanced_altitude was appended
+        let mut added_altitude = [false; 16];
+        let mut described = developer_index.is_none();
+
+        let mut messages = Messages::new(data);
+        while let Some(message) = messages.next() {
+            match message? {
+                Message::Definition { header, global, raw, prefix, regular, developer } => {
+                    let local = header & 0x0F;
+                    if global != RECORD {
+                        added_altitude[local as usize] = false;
+                        out.extend_from_slice(raw);
+                        continue;
+                    }
+
+                    // Our descriptions go right before the first record definition, on its
+                    // local type, which that definition then takes back
+                    if let (false, Some(index)) = (described, developer_index) {
+                        describe_fields(&mut out, local, index, developer_fields);
+                        described = true;
+                    }
+                    let has_altitude = regular.chunks(3).any(|field| field[0] == ALTITUDE_FIELD || field[0] == ENHANCED_ALTITUDE_FIELD);
+                    added_altitude[local as usize] = !has_altitude;
+
+                    let regular_count = regular.len() / 3 + usize::from(!has_altitude);
+                    let developer_count = developer.len() / 3 + developer_fields.len();
+                    if regular_count + developer_count > 255 {
+                        return Err(Error::InvalidInput("Record definition has no room for more fields".to_string()));
+                    }
+                    let with_developer = developer_count > 0;
+                    out.push(if with_developer { header | 0x20 } else { header });
+                    out.extend_from_slice(prefix);
+                    out.push(regular_count as u8);
+                    out.extend_from_slice(regular);
+                    if !has_altitude {
+                        out.extend_from_slice(&[ENHANCED_ALTITUDE_FIELD, 4, BASE_UINT32]);
+                    }
+                    if with_developer {
+                        out.push(developer_count as u8);
+                        out.extend_from_slice(developer);
+                        for number in 0..developer_fields.len() {
+                            out.extend_from_slice(&[number as u8, 4, developer_index.unwrap_or(0)]);
+                        }
+                    }
+                }
+                Message::Data { header, local, bytes, timestamp } => {
+                    let layout = messages.layout(local);
+                    out.push(header);
+                    if layout.global != RECORD {
+                        out.extend_from_slice(bytes);
+                        continue;
+                    }
+
+                    let index = timestamp.and_then(|t| by_time.get(&(i64::from(t) + FIT_EPOCH)).copied());
+                    // Altitude is stored as (m + 500) * 5
+                    let raw = index.and_then(|i| self.altitude.get(i).copied())
+                        .filter(|a| a.is_finite())
+                        .map(|altitude| ((altitude + 500.0) * 5.0).round().max(0.0));
+                    let mut regular = bytes[..layout.regular_size].to_vec();
+                    if let (Some(raw), Some((offset, 2))) = (raw, layout.field(ALTITUDE_FIELD)) {
+                        write_u16(&mut regular[offset..offset + 2], raw.min(f64::from(u16::MAX - 1)) as u16, layout.big_endian);
+                    }
+                    if let (Some(raw), Some((offset, 4))) = (raw, layout.field(ENHANCED_ALTITUDE_FIELD)) {
+                        write_u32(&mut regular[offset..offset + 4], raw.min(f64::from(u32::MAX - 1)) as u32, layout.big_endian);
+                    }
+                    out.extend_from_slice(&regular);
+                    if added_altitude[local as usize] {
+                        let value = raw.map_or(u32::MAX, |raw| raw.min(f64::from(u32::MAX - 1)) as u32);
+                        push_u32(&mut out, value, layout.big_endian);
+                    }
+                    out.extend_from_slice(&bytes[layout.regular_size..]);
+                    for field in developer_fields {
+                        let value = index.and_then(|i| field.values.get(i).copied()).filter(|v| v.is_finite());
+                        push_u32(&mut out, value.map_or(u32::MAX, |v| (v as f32).to_bits()), layout.big_endian);
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn write_u16(bytes: &mut [u8], value: u16, big_endian: bool) {
+    if big_endian { BigEndian::write_u16(bytes, value) } else { LittleEndian::write_u16(bytes, value) }
+}
+
+fn write_u32(bytes: &mut [u8], value: u32, big_endian: bool) {
+    if big_endian { BigEndian::write_u32(bytes, value) } else { LittleEndian::write_u32(bytes, value) }
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32, big_endian: bool) {
+    let mut bytes = [0; 4];
+    write_u32(&mut bytes, value, big_endian);
+    out.extend_from_slice(&bytes);
+}
+
+/// Lowest developer data index no DeveloperDataId in the file already claims
+fn free_developer_index(data: &[u8]) -> Result<u8> {
+    let mut used = [false; 256];
+    let mut messages = Messages::new(data);
+    while let Some(message) = messages.next() {
+        if let Message::Data { local, bytes, .. } = message? {
+            let layout = messages.layout(local);
+            // developer_data_index is field 3
+            if let (DEVELOPER_DATA_ID, Some((offset, 1))) = (layout.global, layout.field(3)) {
+                used[bytes[offset] as usize] = true;
+            }
+        }
+    }
+    // 0xFF is the invalid value
+    used[..255].iter().position(|&used| !used).map(|index| index as u8)
+        .ok_or_else(|| Error::InvalidInput("FIT file has no free developer data index".to_string()))
+}
+
+/// DeveloperDataId and one FieldDescription per field, on local type `local`
+fn describe_fields(out: &mut Vec<u8>, local: u8, developer_index: u8, fields: &[DeveloperField]) {
+    let definition = |out: &mut Vec<u8>, global: u16, field_defs: &[(u8, u8, u8)]| {
+        out.extend_from_slice(&[0x40 | local, 0, 0]);
+        out.extend_from_slice(&global.to_le_bytes());
+        out.push(field_defs.len() as u8);
+        for &(number, size, base_type) in field_defs {
+            out.extend_from_slice(&[number, size, base_type]);
+        }
+    };
+
+    definition(out, DEVELOPER_DATA_ID, &[(1, 16, BASE_BYTE), (3, 1, BASE_UINT8)]);
+    out.push(local);
+    out.extend_from_slice(&APPLICATION_ID);
+    out.push(developer_index);
+
+    for (number, field) in fields.iter().enumerate() {
+        let name = null_terminated(field.name);
+        let units = null_terminated(field.units);
+        definition(out, FIELD_DESCRIPTION, &[
+            (0, 1, BASE_UINT8),
+            (1, 1, BASE_UINT8),
+            (2, 1, BASE_UINT8),
+            (3, name.len() as u8, BASE_STRING),
+            (8, units.len() as u8, BASE_STRING),
+            (14, 2, BASE_UINT16),
+        ]);
+        out.push(local);
+        out.extend_from_slice(&[developer_index, number as u8, BASE_FLOAT32]);
+        out.extend_from_slice(&name);
+        out.extend_from_slice(&units);
+        out.extend_from_slice(&RECORD.to_le_bytes());
+    }
+}
+
+fn null_terminated(text: &str) -> Vec<u8> {
+    let mut bytes = text.as_bytes().to_vec();
+    bytes.push(0);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fit_parser::{parse_fit_file, parse_fit_file_with_mode, FitParseMode};
+    use crate::test_support::{base_type, mesg_num, FitWriter};
+
+    const START: u32 = 1_000_000_000;
+
+    /// Three records with altitude, then one from a definition without it
+    fn ride() -> Vec<u8> {
+        let mut writer = FitWriter::new();
+        writer.message(mesg_num::FILE_ID, &[(0, base_type::ENUM, &[4])]);
+        for k in 0..3u32 {
+            let altitude = ((100.0 + 500.0) * 5.0) as u16 + k as u16;
+            writer.message(mesg_num::RECORD, &[
+                (253, base_type::UINT32, &(START + k).to_le_bytes()),
+                (2, base_type::UINT16, &altitude.to_le_bytes()),
+                (7, base_type::UINT16, &200u16.to_le_bytes()),
+            ]);
+        }
+        writer.message(mesg_num::RECORD, &[
+            (253, base_type::UINT32, &(START + 3).to_le_bytes()),
+            (7, base_type::UINT16, &210u16.to_le_bytes()),
+        ]);
+        writer.finish(14, None)
+    }
+
+    #[test]
+    fn test_rewrites_altitude_and_crcs() {
+        let source = ride();
+        let mut fit_data = parse_fit_file(&source).unwrap().fit_data();
+        assert_eq!(fit_data.record_count(), 4);
+        fit_data.set_altitude(vec![250.0, 251.2, f64::NAN, 253.0]);
+
+        let rewritten = FitRewrite::new(&fit_data).write(&source).unwrap();
+        // Strict mode checks both CRCs
+        let parsed = parse_fit_file_with_mode(&rewritten, FitParseMode::Strict).unwrap();
+        let altitude = parsed.fit_data().altitude();
+        assert_eq!(altitude[0], 250.0);
+        assert!((altitude[1] - 251.2).abs() < 1e-9);
+        // NaN keeps the original value; the last record gained enhanced_altitude
+        assert!((altitude[2] - 100.4).abs() < 1e-9);
+        assert_eq!(altitude[3], 253.0);
+        assert_eq!(parsed.fit_data().power()[3], 210.0);
+    }
+
+    #[test]
+    fn test_adds_described_developer_fields() {
+        let source = ride();
+        let fit_data = parse_fit_file(&source).unwrap().fit_data();
+        let mut rewrite = FitRewrite::new(&fit_data);
+        rewrite.set_virtual_elevation(vec![0.0, 0.5, 1.0, 1.5]);
+        rewrite.set_cda(vec![0.25; 4]);
+        let rewritten = rewrite.write(&source).unwrap();
+        assert!(parse_fit_file_with_mode(&rewritten, FitParseMode::Strict).is_ok());
+
+        let records: Vec<_> = fitparser::from_bytes(&rewritten).unwrap().into_iter()
+            .filter(|message| message.kind() == fitparser::profile::MesgNum::Record)
+            .collect();
+        assert_eq!(records.len(), 4);
+        let value = |record: &fitparser::FitDataRecord, name: &str| {
+            record.fields().iter().find(|field| field.name() == name).map(|field| field.value().clone())
+        };
+        assert_eq!(value(&records[1], "virtual_elevation"), Some(fitparser::Value::Float32(0.5)));
+        assert_eq!(value(&records[3], "cda"), Some(fitparser::Value::Float32(0.25)));
+    }
+
+    #[test]
+    fn test_rejects_mismatched_lengths() {
+        let source = ride();
+        let mut rewrite = FitRewrite::new(&parse_fit_file(&source).unwrap().fit_data());
+        rewrite.set_cda(vec![0.25; 3]);
+        assert!(matches!(rewrite.write(&source), Err(Error::InvalidInput(_))));
+    }
+}
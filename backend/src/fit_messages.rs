@@ -0,0 +1,167 @@
+//! Message walker over the data section of a FIT file, shared by the security scan
+//! and the FIT rewrite so both read record headers and definitions the same way.
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use crate::error::Error;
+
+pub(crate) const TIMESTAMP_FIELD: u8 = 253;
+
+/// Field layout of one local message type, from its definition
+#[derive(Clone, Default)]
+pub(crate) struct Layout {
+    pub big_endian: bool,
+    pub global: u16,
+    /// (number, offset, size) of each regular field
+    pub fields: Vec<(u8, usize, usize)>,
+    pub regular_size: usize,
+    pub developer_size: usize,
+}
+
+impl Layout {
+    pub fn field(&self, number: u8) -> Option<(usize, usize)> {
+        self.fields.iter().find(|field| field.0 == number).map(|&(_, offset, size)| (offset, size))
+    }
+
+    pub fn read_u32(&self, bytes: &[u8]) -> u32 {
+        if self.big_endian { BigEndian::read_u32(bytes) } else { LittleEndian::read_u32(bytes) }
+    }
+}
+
+pub(crate) enum Message<'a> {
+    Definition {
+        header: u8,
+        global: u16,
+        /// The whole definition message
+        raw: &'a [u8],
+        /// Reserved byte, architecture and global message number
+        prefix: &'a [u8],
+        /// (number, size, base type) triples
+        regular: &'a [u8],
+        developer: &'a [u8],
+    },
+    Data {
+        header: u8,
+        /// Local type; `Messages::layout` has its field layout
+        local: u8,
+        bytes: &'a [u8],
+        /// From the timestamp field or a compressed timestamp header, FIT epoch
+        timestamp: Option<u32>,
+    },
+}
+
+/// Why the walk stopped early
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MessageError {
+    /// The message runs past the end of the data
+    Truncated,
+    /// A data message uses a local type with no definition yet
+    UndefinedLocalType(u8),
+}
+
+impl From<MessageError> for Error {
+    fn from(error: MessageError) -> Error {
+        match error {
+            MessageError::Truncated => Error::Validation("FIT message runs past the end of the data".to_string()),
+            MessageError::UndefinedLocalType(local) => {
+                Error::Validation(format!("FIT data message uses undefined local type {}", local))
+            }
+        }
+    }
+}
+
+/// Walks the messages of a FIT data section (the bytes between header and CRC)
+pub(crate) struct Messages<'a> {
+    data: &'a [u8],
+    pos: usize,
+    layouts: [Option<Layout>; 16],
+    last_timestamp: Option<u32>,
+}
+
+impl<'a> Messages<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Messages { data, pos: 0, layouts: Default::default(), last_timestamp: None }
+    }
+
+    /// Offset of the next message in the data section
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Layout of the latest definition of `local`. Data messages look theirs up here
+    /// rather than carry a copy, so walking a file doesn't allocate per message.
+    pub fn layout(&self, local: u8) -> &Layout {
+        self.layouts[local as usize].as_ref().expect("data message yielded for an undefined local type")
+    }
+
+    fn read(&mut self) -> Result<Message<'a>, MessageError> {
+        let data = self.data;
+        let header = data[self.pos];
+        let pos = self.pos + 1;
+
+        // Normal header with bit 6 set: definition message
+        if header & 0x80 == 0 && header & 0x40 != 0 {
+            let prefix = data.get(pos..pos + 5).ok_or(MessageError::Truncated)?;
+            let big_endian = prefix[1] == 1;
+            let global = if big_endian { BigEndian::read_u16(&prefix[2..4]) } else { LittleEndian::read_u16(&prefix[2..4]) };
+            let regular_end = pos + 5 + 3 * prefix[4] as usize;
+            let regular = data.get(pos + 5..regular_end).ok_or(MessageError::Truncated)?;
+            let (developer, end) = if header & 0x20 != 0 {
+                let count = *data.get(regular_end).ok_or(MessageError::Truncated)? as usize;
+                let end = regular_end + 1 + 3 * count;
+                (data.get(regular_end + 1..end).ok_or(MessageError::Truncated)?, end)
+            } else {
+                (&[][..], regular_end)
+            };
+
+            let mut layout = Layout { big_endian, global, ..Layout::default() };
+            for field in regular.chunks(3) {
+                layout.fields.push((field[0], layout.regular_size, field[1] as usize));
+                layout.regular_size += field[1] as usize;
+            }
+            layout.developer_size = developer.chunks(3).map(|field| field[1] as usize).sum();
+            self.layouts[(header & 0x0F) as usize] = Some(layout);
+            self.pos = end;
+            return Ok(Message::Definition { header, global, raw: &data[pos - 1..end], prefix: &prefix[..4], regular, developer });
+        }
+
+        // Normal data message, or compressed timestamp header (local type in bits 5-6)
+        let (local, time_offset) = if header & 0x80 != 0 {
+            ((header >> 5) & 0x03, Some(u32::from(header & 0x1F)))
+        } else {
+            (header & 0x0F, None)
+        };
+        let layout = self.layouts[local as usize].as_ref().ok_or(MessageError::UndefinedLocalType(local))?;
+        let end = pos + layout.regular_size + layout.developer_size;
+        let bytes = data.get(pos..end).ok_or(MessageError::Truncated)?;
+
+        let timestamp = match (time_offset, layout.field(TIMESTAMP_FIELD)) {
+            (_, Some((offset, 4))) => Some(layout.read_u32(&bytes[offset..offset + 4])).filter(|&t| t != u32::MAX),
+            // The offset is the low five bits of the time, rolling over from the last full timestamp
+            (Some(time_offset), _) => self.last_timestamp.map(|last| {
+                let time = (last & !0x1F) | time_offset;
+                if time_offset >= last & 0x1F { time } else { time.wrapping_add(0x20) }
+            }),
+            _ => None,
+        };
+        self.pos = end;
+        if timestamp.is_some() {
+            self.last_timestamp = timestamp;
+        }
+        Ok(Message::Data { header, local, bytes, timestamp })
+    }
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = Result<Message<'a>, MessageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let message = self.read();
+        // Nothing after a broken message can be framed
+        if message.is_err() {
+            self.pos = self.data.len();
+        }
+        Some(message)
+    }
+}
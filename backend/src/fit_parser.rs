@@ -1,10 +1,14 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use byteorder::{ByteOrder, LittleEndian};
-use crate::fitparser_wrapper::FitParserWrapper;
+use crate::error::{Error, Result};
+use crate::fitparser_wrapper::{FitDeviceInfo, FitFileId, FitMessages, FitParserWrapper, FitSession, FitUserProfile};
+use crate::virtual_elevation::VEData;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
 pub struct FitData {
     timestamps: Vec<f64>,
     power: Vec<f64>,
@@ -21,87 +25,190 @@ pub struct FitData {
     temperature: Vec<f64>,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl FitData {
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn timestamps(&self) -> Vec<f64> {
         self.timestamps.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn power(&self) -> Vec<f64> {
         self.power.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn velocity(&self) -> Vec<f64> {
         self.velocity.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn position_lat(&self) -> Vec<f64> {
         self.position_lat.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn position_long(&self) -> Vec<f64> {
         self.position_long.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn altitude(&self) -> Vec<f64> {
         self.altitude.clone()
     }
 
     /// Set altitude values (for DEM correction)
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn set_altitude(&mut self, altitude: Vec<f64>) {
         self.altitude = altitude;
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn distance(&self) -> Vec<f64> {
         self.distance.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn air_speed(&self) -> Vec<f64> {
         self.air_speed.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn wind_speed(&self) -> Vec<f64> {
         self.wind_speed.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn battery_soc(&self) -> Vec<f64> {
         self.battery_soc.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn heart_rate(&self) -> Vec<f64> {
         self.heart_rate.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn cadence(&self) -> Vec<f64> {
         self.cadence.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn temperature(&self) -> Vec<f64> {
         self.temperature.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn record_count(&self) -> usize {
         self.timestamps.len()
     }
 }
 
+// Borrowed access to the record channels; the getters above copy for JS.
+// The wasm adapter builds zero-copy Float64Array views on top of these.
+impl FitData {
+    /// Copy of the records in `range`; channels the source doesn't have stay empty
+    pub(crate) fn select(&self, range: std::ops::Range<usize>) -> FitData {
+        let pick = |values: &Vec<f64>| values.get(range.clone()).map(<[f64]>::to_vec).unwrap_or_default();
+        FitData {
+            timestamps: pick(&self.timestamps),
+            power: pick(&self.power),
+            velocity: pick(&self.velocity),
+            position_lat: pick(&self.position_lat),
+            position_long: pick(&self.position_long),
+            altitude: pick(&self.altitude),
+            distance: pick(&self.distance),
+            air_speed: pick(&self.air_speed),
+            wind_speed: pick(&self.wind_speed),
+            battery_soc: pick(&self.battery_soc),
+            heart_rate: pick(&self.heart_rate),
+            cadence: pick(&self.cadence),
+            temperature: pick(&self.temperature),
+        }
+    }
+
+    /// Replace the air channels with merged sensor-log values
+    pub(crate) fn set_air_data(&mut self, air_speed: Vec<f64>, wind_speed: Vec<f64>) {
+        self.air_speed = air_speed;
+        self.wind_speed = wind_speed;
+    }
+
+    pub(crate) fn set_temperature(&mut self, temperature: Vec<f64>) {
+        self.temperature = temperature;
+    }
+
+    pub fn timestamps_slice(&self) -> &[f64] {
+        &self.timestamps
+    }
+
+    pub fn power_slice(&self) -> &[f64] {
+        &self.power
+    }
+
+    pub fn velocity_slice(&self) -> &[f64] {
+        &self.velocity
+    }
+
+    pub fn position_lat_slice(&self) -> &[f64] {
+        &self.position_lat
+    }
+
+    pub fn position_long_slice(&self) -> &[f64] {
+        &self.position_long
+    }
+
+    pub fn altitude_slice(&self) -> &[f64] {
+        &self.altitude
+    }
+
+    pub fn distance_slice(&self) -> &[f64] {
+        &self.distance
+    }
+
+    pub fn air_speed_slice(&self) -> &[f64] {
+        &self.air_speed
+    }
+
+    pub fn wind_speed_slice(&self) -> &[f64] {
+        &self.wind_speed
+    }
+
+    pub fn battery_soc_slice(&self) -> &[f64] {
+        &self.battery_soc
+    }
+
+    pub fn heart_rate_slice(&self) -> &[f64] {
+        &self.heart_rate
+    }
+
+    pub fn cadence_slice(&self) -> &[f64] {
+        &self.cadence
+    }
+
+    pub fn temperature_slice(&self) -> &[f64] {
+        &self.temperature
+    }
+}
+
+impl From<FitData> for VEData {
+    fn from(fit_data: FitData) -> VEData {
+        VEData::new(
+            fit_data.timestamps,
+            fit_data.power,
+            fit_data.velocity,
+            fit_data.position_lat,
+            fit_data.position_long,
+            fit_data.altitude,
+            fit_data.distance,
+            fit_data.air_speed,
+            fit_data.wind_speed,
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
 pub struct LapData {
     start_time: f64,
     end_time: f64,
@@ -114,82 +221,913 @@ pub struct LapData {
     start_position_long: f64,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl LapData {
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn start_time(&self) -> f64 {
         self.start_time
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn end_time(&self) -> f64 {
         self.end_time
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn total_elapsed_time(&self) -> f64 {
         self.total_elapsed_time
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn total_distance(&self) -> f64 {
         self.total_distance
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn avg_power(&self) -> f64 {
         self.avg_power
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn avg_speed(&self) -> f64 {
         self.avg_speed
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn max_speed(&self) -> f64 {
         self.max_speed
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn start_position_lat(&self) -> f64 {
         self.start_position_lat
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn start_position_long(&self) -> f64 {
         self.start_position_long
     }
 }
 
+/// Identity of the recording device and file, from the FIT FileId message
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
+pub struct FileId {
+    file_type: Option<String>,
+    manufacturer: Option<String>,
+    product: Option<String>,
+    serial_number: Option<u32>,
+    time_created: Option<f64>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl FileId {
+    /// FIT file type, e.g. "activity"
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn file_type(&self) -> Option<String> {
+        self.file_type.clone()
+    }
+
+    /// Manufacturer name (e.g. "garmin"), or its numeric id if unknown to the profile
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn manufacturer(&self) -> Option<String> {
+        self.manufacturer.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn product(&self) -> Option<String> {
+        self.product.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn serial_number(&self) -> Option<u32> {
+        self.serial_number
+    }
+
+    /// Unix timestamp (seconds)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn time_created(&self) -> Option<f64> {
+        self.time_created
+    }
+}
+
+impl From<FitFileId> for FileId {
+    fn from(file_id: FitFileId) -> Self {
+        FileId {
+            file_type: file_id.file_type,
+            manufacturer: file_id.manufacturer,
+            product: file_id.product,
+            serial_number: file_id.serial_number,
+            time_created: file_id.time_created,
+        }
+    }
+}
+
+/// A device or sensor from the FIT DeviceInfo messages (latest values per device index)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
+pub struct DeviceInfo {
+    device_index: Option<String>,
+    device_type: Option<String>,
+    manufacturer: Option<String>,
+    product: Option<String>,
+    product_name: Option<String>,
+    serial_number: Option<u32>,
+    software_version: Option<f64>,
+    hardware_version: Option<u32>,
+    battery_voltage: Option<f64>,
+    battery_status: Option<String>,
+    battery_level: Option<f64>,
+    source_type: Option<String>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DeviceInfo {
+    /// "creator" for the recording device, otherwise the sensor's index
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn device_index(&self) -> Option<String> {
+        self.device_index.clone()
+    }
+
+    /// Sensor type, e.g. "bike_power", "heart_rate", "bike_speed_cadence"
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn device_type(&self) -> Option<String> {
+        self.device_type.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn manufacturer(&self) -> Option<String> {
+        self.manufacturer.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn product(&self) -> Option<String> {
+        self.product.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn product_name(&self) -> Option<String> {
+        self.product_name.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn serial_number(&self) -> Option<u32> {
+        self.serial_number
+    }
+
+    /// Firmware version
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn software_version(&self) -> Option<f64> {
+        self.software_version
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn hardware_version(&self) -> Option<u32> {
+        self.hardware_version
+    }
+
+    /// Volts
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn battery_voltage(&self) -> Option<f64> {
+        self.battery_voltage
+    }
+
+    /// e.g. "good", "low"
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn battery_status(&self) -> Option<String> {
+        self.battery_status.clone()
+    }
+
+    /// Percent
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn battery_level(&self) -> Option<f64> {
+        self.battery_level
+    }
+
+    /// "antplus", "bluetooth_low_energy", "local", ...
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn source_type(&self) -> Option<String> {
+        self.source_type.clone()
+    }
+}
+
+impl From<FitDeviceInfo> for DeviceInfo {
+    fn from(device: FitDeviceInfo) -> Self {
+        DeviceInfo {
+            device_index: device.device_index,
+            device_type: device.device_type,
+            manufacturer: device.manufacturer,
+            product: device.product,
+            product_name: device.product_name,
+            serial_number: device.serial_number,
+            software_version: device.software_version,
+            hardware_version: device.hardware_version,
+            battery_voltage: device.battery_voltage,
+            battery_status: device.battery_status,
+            battery_level: device.battery_level,
+            source_type: device.source_type,
+        }
+    }
+}
+
+/// A FIT Session message: one sport/activity segment of the file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
+pub struct SessionData {
+    sport: Option<String>,
+    sub_sport: Option<String>,
+    start_time: f64,
+    end_time: f64,
+    total_elapsed_time: f64,
+    total_timer_time: Option<f64>,
+    total_distance: Option<f64>,
+    total_calories: Option<f64>,
+    total_ascent: Option<f64>,
+    total_descent: Option<f64>,
+    avg_speed: Option<f64>,
+    max_speed: Option<f64>,
+    avg_power: Option<f64>,
+    max_power: Option<f64>,
+    normalized_power: Option<f64>,
+    avg_heart_rate: Option<f64>,
+    max_heart_rate: Option<f64>,
+    avg_cadence: Option<f64>,
+    first_lap_index: Option<u32>,
+    num_laps: Option<u32>,
+    /// Records and laps falling inside the session's time span
+    #[serde(default)]
+    record_start: usize,
+    #[serde(default)]
+    record_count: usize,
+    #[serde(default)]
+    lap_start: usize,
+    #[serde(default)]
+    lap_count: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl SessionData {
+    /// e.g. "cycling"
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn sport(&self) -> Option<String> {
+        self.sport.clone()
+    }
+
+    /// e.g. "road", "track", "indoor_cycling"
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn sub_sport(&self) -> Option<String> {
+        self.sub_sport.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn start_time(&self) -> f64 {
+        self.start_time
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn end_time(&self) -> f64 {
+        self.end_time
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn total_elapsed_time(&self) -> f64 {
+        self.total_elapsed_time
+    }
+
+    /// Seconds excluding pauses
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn total_timer_time(&self) -> Option<f64> {
+        self.total_timer_time
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn total_distance(&self) -> Option<f64> {
+        self.total_distance
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn total_calories(&self) -> Option<f64> {
+        self.total_calories
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn total_ascent(&self) -> Option<f64> {
+        self.total_ascent
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn total_descent(&self) -> Option<f64> {
+        self.total_descent
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn avg_speed(&self) -> Option<f64> {
+        self.avg_speed
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn max_speed(&self) -> Option<f64> {
+        self.max_speed
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn avg_power(&self) -> Option<f64> {
+        self.avg_power
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn max_power(&self) -> Option<f64> {
+        self.max_power
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn normalized_power(&self) -> Option<f64> {
+        self.normalized_power
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn avg_heart_rate(&self) -> Option<f64> {
+        self.avg_heart_rate
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn max_heart_rate(&self) -> Option<f64> {
+        self.max_heart_rate
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn avg_cadence(&self) -> Option<f64> {
+        self.avg_cadence
+    }
+
+    /// Index into the file's laps of this session's first lap
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn first_lap_index(&self) -> Option<u32> {
+        self.first_lap_index
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn num_laps(&self) -> Option<u32> {
+        self.num_laps
+    }
+
+    /// Index in the file's records of the session's first record
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn record_start(&self) -> usize {
+        self.record_start
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn record_count(&self) -> usize {
+        self.record_count
+    }
+
+    /// Index in the file's laps of the session's first lap
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn lap_start(&self) -> usize {
+        self.lap_start
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn lap_count(&self) -> usize {
+        self.lap_count
+    }
+}
+
+impl SessionData {
+    /// Locate the session's records and laps by time. Records are in time order;
+    /// laps belong to the session they start in.
+    fn assign_ranges(&mut self, timestamps: &[f64], laps: &[LapData]) {
+        self.record_start = timestamps.partition_point(|&t| t < self.start_time);
+        self.record_count = timestamps.partition_point(|&t| t <= self.end_time).saturating_sub(self.record_start);
+
+        let in_session = |lap: &LapData| lap.start_time >= self.start_time && lap.start_time < self.end_time;
+        self.lap_start = laps.iter().position(in_session).unwrap_or(laps.len());
+        self.lap_count = laps[self.lap_start..].iter().take_while(|lap| in_session(lap)).count();
+    }
+}
+
+impl From<FitSession> for SessionData {
+    fn from(session: FitSession) -> Self {
+        SessionData {
+            sport: session.sport,
+            sub_sport: session.sub_sport,
+            start_time: session.start_time,
+            end_time: session.end_time,
+            total_elapsed_time: session.total_elapsed_time,
+            total_timer_time: session.total_timer_time,
+            total_distance: session.total_distance,
+            total_calories: session.total_calories,
+            total_ascent: session.total_ascent,
+            total_descent: session.total_descent,
+            avg_speed: session.avg_speed,
+            max_speed: session.max_speed,
+            avg_power: session.avg_power,
+            max_power: session.max_power,
+            normalized_power: session.normalized_power,
+            avg_heart_rate: session.avg_heart_rate,
+            max_heart_rate: session.max_heart_rate,
+            avg_cadence: session.avg_cadence,
+            first_lap_index: session.first_lap_index,
+            num_laps: session.num_laps,
+            record_start: 0,
+            record_count: 0,
+            lap_start: 0,
+            lap_count: 0,
+        }
+    }
+}
+
+/// Rider settings from the FIT UserProfile message
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
+pub struct UserProfile {
+    weight: Option<f64>,
+    height: Option<f64>,
+    age: Option<f64>,
+    gender: Option<String>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl UserProfile {
+    /// Rider weight in kg
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn weight(&self) -> Option<f64> {
+        self.weight
+    }
+
+    /// Metres
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn height(&self) -> Option<f64> {
+        self.height
+    }
+
+    /// Years
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn age(&self) -> Option<f64> {
+        self.age
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn gender(&self) -> Option<String> {
+        self.gender.clone()
+    }
+}
+
+impl From<FitUserProfile> for UserProfile {
+    fn from(profile: FitUserProfile) -> Self {
+        UserProfile {
+            weight: profile.weight,
+            height: profile.height,
+            age: profile.age,
+            gender: profile.gender,
+        }
+    }
+}
+
+/// How [`parse_fit_file_with_mode`] treats CRC mismatches and damaged files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(eq, eq_int))]
+pub enum FitParseMode {
+    /// Ignore the data CRC, fail on a damaged header or data (what `parse_fit_file` does)
+    #[default]
+    Lenient,
+    /// Fail on a header or data CRC mismatch or a truncated file
+    Strict,
+    /// Ignore CRCs and truncation, keep every message before the first damaged one, e.g. after a
+    /// head unit crashed mid-ride
+    Recover,
+}
+
+/// Integrity report for a parsed FIT file: both CRCs as stored and as computed, and how
+/// much of the data section was decoded
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
+pub struct FitDiagnostics {
+    mode: FitParseMode,
+    file_size: usize,
+    header_size: usize,
+    declared_data_size: usize,
+    header_crc: Option<u16>,
+    computed_header_crc: Option<u16>,
+    data_crc: Option<u16>,
+    computed_data_crc: Option<u16>,
+    truncated: bool,
+    decoded_bytes: usize,
+    stop_reason: Option<String>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl FitDiagnostics {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn mode(&self) -> FitParseMode {
+        self.mode
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn file_size(&self) -> usize {
+        self.file_size
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn header_size(&self) -> usize {
+        self.header_size
+    }
+
+    /// Data section size according to the header
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn declared_data_size(&self) -> usize {
+        self.declared_data_size
+    }
+
+    /// Stored header CRC; None for 12-byte headers and headers with a zero CRC
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn header_crc(&self) -> Option<u16> {
+        self.header_crc
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn computed_header_crc(&self) -> Option<u16> {
+        self.computed_header_crc
+    }
+
+    /// Stored data CRC; None if the file ends before it
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn data_crc(&self) -> Option<u16> {
+        self.data_crc
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn computed_data_crc(&self) -> Option<u16> {
+        self.computed_data_crc
+    }
+
+    /// The file ends before the data section and CRC the header announces
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Bytes from the start of the file to the end of the last decoded message
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn decoded_bytes(&self) -> usize {
+        self.decoded_bytes
+    }
+
+    /// Decoder error that ended a recovery-mode parse early
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn stop_reason(&self) -> Option<String> {
+        self.stop_reason.clone()
+    }
+
+    /// None when there is no header CRC to check
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn header_crc_valid(&self) -> Option<bool> {
+        Some(self.header_crc? == self.computed_header_crc?)
+    }
+
+    /// None when the file is truncated before the data CRC
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn data_crc_valid(&self) -> Option<bool> {
+        Some(self.data_crc? == self.computed_data_crc?)
+    }
+
+    /// Messages past the damage were dropped (recovery mode only)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn recovered(&self) -> bool {
+        self.stop_reason.is_some()
+    }
+
+    /// One-line description for logs and the UI
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn summary(&self) -> String {
+        if self.header_size == 0 {
+            return "Imported from GPX, TCX or CSV, no CRCs to check".to_string();
+        }
+        let data_end = self.header_size.saturating_add(self.declared_data_size);
+        match (&self.stop_reason, self.integrity_problem()) {
+            (Some(reason), _) => format!(
+                "Recovered {} of {} bytes; decoding stopped: {}",
+                self.decoded_bytes, data_end, reason
+            ),
+            (None, Some(problem)) => problem,
+            (None, None) => "FIT file intact, CRCs valid".to_string(),
+        }
+    }
+}
+
+impl FitDiagnostics {
+    /// Header facts and CRCs, before any message is decoded
+    fn inspect(file_data: &[u8], mode: FitParseMode) -> FitDiagnostics {
+        use crate::utils::fit_crc;
+
+        let header_size = file_data[0] as usize;
+        let declared_data_size = LittleEndian::read_u32(&file_data[4..8]) as usize;
+
+        // A zero header CRC means "not computed"
+        let header_crc = Some(header_size)
+            .filter(|&size| size >= 14)
+            .map(|size| LittleEndian::read_u16(&file_data[size - 2..size]))
+            .filter(|&crc| crc != 0);
+        let computed_header_crc = header_crc.map(|_| fit_crc(0, &file_data[..header_size - 2]));
+
+        // The data CRC covers the header too unless the header has its own CRC
+        let data_end = header_size.saturating_add(declared_data_size);
+        let crc_start = if header_crc.is_some() { header_size } else { 0 };
+        let data_crc = file_data.get(data_end..data_end.saturating_add(2))
+            .filter(|_| declared_data_size > 0)
+            .map(LittleEndian::read_u16);
+        let computed_data_crc = data_crc.map(|_| fit_crc(0, &file_data[crc_start..data_end]));
+
+        FitDiagnostics {
+            mode,
+            file_size: file_data.len(),
+            header_size,
+            declared_data_size,
+            header_crc,
+            computed_header_crc,
+            data_crc,
+            computed_data_crc,
+            truncated: data_crc.is_none(),
+            decoded_bytes: 0,
+            stop_reason: None,
+        }
+    }
+
+    /// Diagnostics for a GPX, TCX or CSV import: no header or CRCs, the whole file was read
+    pub(crate) fn imported(file_size: usize) -> FitDiagnostics {
+        FitDiagnostics { file_size, decoded_bytes: file_size, ..FitDiagnostics::default() }
+    }
+
+    /// What strict mode rejects: truncation or a CRC mismatch
+    fn integrity_problem(&self) -> Option<String> {
+        if self.truncated {
+            return Some(format!(
+                "FIT file is truncated: header declares {} data bytes plus CRC, file has {} bytes after the header",
+                self.declared_data_size,
+                self.file_size - self.header_size
+            ));
+        }
+        if self.header_crc_valid() == Some(false) {
+            return Some(format!(
+                "FIT header CRC mismatch: stored 0x{:04X}, computed 0x{:04X}",
+                self.header_crc.unwrap_or_default(),
+                self.computed_header_crc.unwrap_or_default()
+            ));
+        }
+        if self.data_crc_valid() == Some(false) {
+            return Some(format!(
+                "FIT data CRC mismatch: stored 0x{:04X}, computed 0x{:04X}",
+                self.data_crc.unwrap_or_default(),
+                self.computed_data_crc.unwrap_or_default()
+            ));
+        }
+        None
+    }
+}
+
+/// Content fingerprint of a FIT file. Only the data section is hashed, so re-saving a
+/// file with a different header (12 vs 14 bytes, recomputed or zeroed header CRC)
+/// keeps the same fingerprint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
+pub struct FitFingerprint {
+    sha256: String,
+    data_size: usize,
+    file_id: Option<FileId>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl FitFingerprint {
+    /// Lowercase hex SHA-256 of the data records (header and trailing CRC excluded)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn sha256(&self) -> String {
+        self.sha256.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn data_size(&self) -> usize {
+        self.data_size
+    }
+
+    /// None if the file has no FileId message
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn file_id(&self) -> Option<FileId> {
+        self.file_id.clone()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
 pub struct ParsedFitFile {
     fit_data: FitData,
     laps: Vec<LapData>,
     parsing_statistics: ParsingStatistics,
+    #[serde(default)]
+    file_id: Option<FileId>,
+    #[serde(default)]
+    devices: Vec<DeviceInfo>,
+    #[serde(default)]
+    sessions: Vec<SessionData>,
+    #[serde(default)]
+    user_profile: Option<UserProfile>,
+    #[serde(default)]
+    diagnostics: FitDiagnostics,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl ParsedFitFile {
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn fit_data(&self) -> FitData {
         self.fit_data.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn laps(&self) -> Vec<LapData> {
         self.laps.clone()
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn parsing_statistics(&self) -> ParsingStatistics {
         self.parsing_statistics.clone()
     }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn file_id(&self) -> Option<FileId> {
+        self.file_id.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn devices(&self) -> Vec<DeviceInfo> {
+        self.devices.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn sessions(&self) -> Vec<SessionData> {
+        self.sessions.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn user_profile(&self) -> Option<UserProfile> {
+        self.user_profile.clone()
+    }
+
+    /// The power meter that recorded the file, if it announced itself in DeviceInfo
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn power_meter(&self) -> Option<DeviceInfo> {
+        self.devices.iter()
+            .find(|device| device.device_type.as_deref() == Some("bike_power"))
+            .cloned()
+    }
+
+    /// CRC checks and, in recovery mode, what could be salvaged
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn diagnostics(&self) -> FitDiagnostics {
+        self.diagnostics.clone()
+    }
+
+    /// Index of the first session of the given sport (e.g. "cycling")
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn find_session(&self, sport: &str) -> Option<usize> {
+        self.sessions.iter().position(|session| session.sport.as_deref() == Some(sport))
+    }
+
+    /// Just the records and laps of one session (multisport and brick files), so the
+    /// analysis of the bike leg can't pick up records from the run or transitions.
+    /// `index` is 0-based; errors number sessions from 1, as the CLI does.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn session(&self, index: usize) -> Result<ParsedFitFile> {
+        let session = self.sessions.get(index).ok_or_else(|| Error::InvalidInput(format!(
+            "Session {} not in file ({} sessions)", index + 1, self.sessions.len()
+        )))?;
+
+        let records = session.record_start..session.record_start + session.record_count;
+        let laps = self.laps[session.lap_start..session.lap_start + session.lap_count].to_vec();
+        let fit_data = self.fit_data.select(records);
+        let parsing_statistics = ParsingStatistics::from_data(&fit_data, laps.len(), self.parsing_statistics.file_size);
+
+        let mut session = session.clone();
+        session.record_start = 0;
+        session.lap_start = 0;
+
+        Ok(ParsedFitFile {
+            fit_data,
+            laps,
+            parsing_statistics,
+            file_id: self.file_id.clone(),
+            devices: self.devices.clone(),
+            sessions: vec![session],
+            user_profile: self.user_profile.clone(),
+            diagnostics: self.diagnostics.clone(),
+        })
+    }
+}
+
+impl ParsedFitFile {
+    /// Split into record data and laps without copying the record arrays
+    pub fn into_parts(self) -> (FitData, Vec<LapData>) {
+        (self.fit_data, self.laps)
+    }
+
+    /// Assemble the public structure from decoded messages; GPX/TCX import builds the
+    /// same messages so every format shares this path
+    pub(crate) fn from_messages(messages: FitMessages, file_size: usize, diagnostics: FitDiagnostics) -> ParsedFitFile {
+        let (fit_records, fit_laps) = (messages.records, messages.laps);
+
+        // Convert FIT records to our data structure
+        let mut timestamps = Vec::new();
+        let mut power = Vec::new();
+        let mut velocity = Vec::new();
+        let mut position_lat = Vec::new();
+        let mut position_long = Vec::new();
+        let mut altitude = Vec::new();
+        let mut distance = Vec::new();
+        let mut air_speed = Vec::new();
+        let mut wind_speed = Vec::new();
+        let mut battery_soc = Vec::new();
+        let mut heart_rate = Vec::new();
+        let mut cadence = Vec::new();
+        let mut temperature = Vec::new();
+
+        for record in &fit_records {
+            timestamps.push(record.timestamp);
+            power.push(record.power.unwrap_or(0.0));
+            velocity.push(record.speed.unwrap_or(0.0));
+            position_lat.push(record.position_lat.unwrap_or(0.0));
+            position_long.push(record.position_long.unwrap_or(0.0));
+            altitude.push(record.altitude.unwrap_or(0.0));
+            distance.push(record.distance.unwrap_or(0.0));
+            air_speed.push(record.air_speed.unwrap_or(0.0));
+            wind_speed.push(record.wind_speed.unwrap_or(0.0));
+            battery_soc.push(record.battery_soc.unwrap_or(0.0));
+            heart_rate.push(record.heart_rate.unwrap_or(0.0));
+            cadence.push(record.cadence.unwrap_or(0.0));
+            temperature.push(record.temperature.unwrap_or(0.0));
+        }
+
+        let fit_data = FitData {
+            timestamps,
+            power,
+            velocity,
+            position_lat,
+            position_long,
+            altitude,
+            distance,
+            air_speed,
+            wind_speed,
+            battery_soc,
+            heart_rate,
+            cadence,
+            temperature,
+        };
+
+        // Convert FIT laps to our data structure
+        let mut laps = Vec::new();
+        for fit_lap in &fit_laps {
+            laps.push(LapData {
+                start_time: fit_lap.start_time,
+                end_time: fit_lap.end_time,
+                total_elapsed_time: fit_lap.total_elapsed_time,
+                total_distance: fit_lap.total_distance,
+                avg_power: fit_lap.avg_power,
+                avg_speed: fit_lap.avg_speed,
+                max_speed: fit_lap.max_speed,
+                start_position_lat: fit_lap.start_position_lat.unwrap_or(0.0),
+                start_position_long: fit_lap.start_position_long.unwrap_or(0.0),
+            });
+        }
+
+        let parsing_statistics = ParsingStatistics::from_data(&fit_data, laps.len(), file_size);
+
+        let mut sessions: Vec<SessionData> = messages.sessions.into_iter().map(SessionData::from).collect();
+        for session in &mut sessions {
+            session.assign_ranges(&fit_data.timestamps, &laps);
+        }
+
+        ParsedFitFile {
+            fit_data,
+            laps,
+            parsing_statistics,
+            file_id: messages.file_id.map(FileId::from),
+            devices: messages.devices.into_iter().map(DeviceInfo::from).collect(),
+            sessions,
+            user_profile: messages.user_profile.map(UserProfile::from),
+            diagnostics,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
 pub struct ParsingStatistics {
     file_size: usize,
     record_count: usize,
@@ -204,75 +1142,124 @@ pub struct ParsingStatistics {
     max_speed_ms: f64,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl ParsingStatistics {
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn file_size(&self) -> usize {
         self.file_size
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn record_count(&self) -> usize {
         self.record_count
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn lap_count(&self) -> usize {
         self.lap_count
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn has_power_data(&self) -> bool {
         self.has_power_data
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn has_gps_data(&self) -> bool {
         self.has_gps_data
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn duration_seconds(&self) -> f64 {
         self.duration_seconds
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn total_distance_m(&self) -> f64 {
         self.total_distance_m
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn avg_power(&self) -> f64 {
         self.avg_power
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn max_power(&self) -> f64 {
         self.max_power
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn avg_speed_ms(&self) -> f64 {
         self.avg_speed_ms
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn max_speed_ms(&self) -> f64 {
         self.max_speed_ms
     }
 }
 
+impl ParsingStatistics {
+    /// Summary of the records in `fit_data`
+    fn from_data(fit_data: &FitData, lap_count: usize, file_size: usize) -> ParsingStatistics {
+        let power = &fit_data.power;
+        let velocity = &fit_data.velocity;
+        let distance = &fit_data.distance;
+
+        // Calculate statistics from real data
+        let record_count = fit_data.timestamps.len();
+        let has_power_data = power.iter().any(|&p| p > 0.0);
+        let has_gps_data = fit_data.position_lat.iter().any(|&lat| lat != 0.0);
+
+        let duration = if record_count > 0 {
+            fit_data.timestamps.last().unwrap() - fit_data.timestamps.first().unwrap()
+        } else {
+            0.0
+        };
+
+        let total_distance = distance.last().unwrap_or(&0.0) - distance.first().unwrap_or(&0.0);
+
+        ParsingStatistics {
+            file_size,
+            record_count,
+            lap_count,
+            has_power_data,
+            has_gps_data,
+            duration_seconds: duration,
+            total_distance_m: total_distance,
+            avg_power: if has_power_data {
+                let valid_power: Vec<f64> = power.iter().filter(|&&p| p > 0.0).cloned().collect();
+                if valid_power.is_empty() { 0.0 } else { valid_power.iter().sum::<f64>() / valid_power.len() as f64 }
+            } else { 0.0 },
+            max_power: power.iter().fold(0.0, |a, &b| a.max(b)),
+            avg_speed_ms: if !velocity.is_empty() {
+                velocity.iter().sum::<f64>() / velocity.len() as f64
+            } else { 0.0 },
+            max_speed_ms: velocity.iter().fold(0.0, |a, &b| a.max(b)),
+        }
+    }
+}
+
 // Simple FIT file parser - basic implementation
 // Note: This is a simplified parser focused on the first implementation
 // A full FIT parser would require handling all message types and field definitions
-#[wasm_bindgen]
-pub fn parse_fit_file(file_data: &[u8]) -> Result<ParsedFitFile, JsValue> {
-    // Validate file header
-    crate::security::SecurityValidator::new().validate_fit_data(file_data)
-        .map_err(|e| JsValue::from_str(&format!("Validation error: {:?}", e)))?;
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn parse_fit_file(file_data: &[u8]) -> Result<ParsedFitFile> {
+    parse_fit_file_with_mode(file_data, FitParseMode::Lenient)
+}
+
+/// Parse with explicit handling of CRC mismatches and damaged files; see [`FitParseMode`].
+/// The outcome is reported in [`ParsedFitFile::diagnostics`].
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn parse_fit_file_with_mode(file_data: &[u8], mode: FitParseMode) -> Result<ParsedFitFile> {
+    // Validate file header and message structure; recovery tolerates truncation and a bad header CRC
+    crate::security::SecurityValidator::new()
+        .inspect_fit_data(file_data)
+        .check(mode == FitParseMode::Recover)?;
 
     if file_data.len() < 12 {
-        return Err(JsValue::from_str("File too small to be a valid FIT file"));
+        return Err(Error::Validation("File too small to be a valid FIT file".to_string()));
     }
 
     // Parse FIT header (variables prefixed with _ as they're read but not currently used)
@@ -283,117 +1270,270 @@ pub fn parse_fit_file(file_data: &[u8]) -> Result<ParsedFitFile, JsValue> {
 
     // Check for FIT file signature ".FIT"
     if &file_data[8..12] != b".FIT" {
-        return Err(JsValue::from_str("Invalid FIT file signature"));
+        return Err(Error::Validation("Invalid FIT file signature".to_string()));
     }
 
     // Parse the actual FIT data using the fitparser crate
-    let parser = FitParserWrapper::new(file_data.to_vec())
-        .map_err(|e| JsValue::from_str(&format!("Failed to create FIT parser: {}", e)))?;
-
-    let (fit_records, fit_laps) = parser.parse()
-        .map_err(|e| JsValue::from_str(&format!("Failed to parse FIT data: {}", e)))?;
-
-    // Convert FIT records to our data structure
-    let mut timestamps = Vec::new();
-    let mut power = Vec::new();
-    let mut velocity = Vec::new();
-    let mut position_lat = Vec::new();
-    let mut position_long = Vec::new();
-    let mut altitude = Vec::new();
-    let mut distance = Vec::new();
-    let mut air_speed = Vec::new();
-    let mut wind_speed = Vec::new();
-    let mut battery_soc = Vec::new();
-    let mut heart_rate = Vec::new();
-    let mut cadence = Vec::new();
-    let mut temperature = Vec::new();
-
-    for record in &fit_records {
-        timestamps.push(record.timestamp);
-        power.push(record.power.unwrap_or(0.0));
-        velocity.push(record.speed.unwrap_or(0.0));
-        position_lat.push(record.position_lat.unwrap_or(0.0));
-        position_long.push(record.position_long.unwrap_or(0.0));
-        altitude.push(record.altitude.unwrap_or(0.0));
-        distance.push(record.distance.unwrap_or(0.0));
-        air_speed.push(record.air_speed.unwrap_or(0.0));
-        wind_speed.push(record.wind_speed.unwrap_or(0.0));
-        battery_soc.push(record.battery_soc.unwrap_or(0.0));
-        heart_rate.push(record.heart_rate.unwrap_or(0.0));
-        cadence.push(record.cadence.unwrap_or(0.0));
-        temperature.push(record.temperature.unwrap_or(0.0));
-    }
-
-    let fit_data = FitData {
-        timestamps,
-        power: power.clone(),
-        velocity: velocity.clone(),
-        position_lat,
-        position_long,
-        altitude,
-        distance: distance.clone(),
-        air_speed,
-        wind_speed,
-        battery_soc,
-        heart_rate: heart_rate.clone(),
-        cadence: cadence.clone(),
-        temperature,
-    };
-
-    // Convert FIT laps to our data structure
-    let mut laps = Vec::new();
-    for fit_lap in &fit_laps {
-        laps.push(LapData {
-            start_time: fit_lap.start_time,
-            end_time: fit_lap.end_time,
-            total_elapsed_time: fit_lap.total_elapsed_time,
-            total_distance: fit_lap.total_distance,
-            avg_power: fit_lap.avg_power,
-            avg_speed: fit_lap.avg_speed,
-            max_speed: fit_lap.max_speed,
-            start_position_lat: fit_lap.start_position_lat.unwrap_or(0.0),
-            start_position_long: fit_lap.start_position_long.unwrap_or(0.0),
-        });
-    }
-
-    // Calculate statistics from real data
-    let record_count = fit_records.len();
-    let lap_count = fit_laps.len();
-    let has_power_data = power.iter().any(|&p| p > 0.0);
-    let has_gps_data = fit_data.position_lat.iter().any(|&lat| lat != 0.0);
-
-    let duration = if record_count > 0 {
-        fit_data.timestamps.last().unwrap() - fit_data.timestamps.first().unwrap()
-    } else {
-        0.0
-    };
-
-    let total_distance = distance.last().unwrap_or(&0.0) - distance.first().unwrap_or(&0.0);
-
-    let parsing_statistics = ParsingStatistics {
-        file_size: file_data.len(),
-        record_count,
-        lap_count,
-        has_power_data,
-        has_gps_data,
-        duration_seconds: duration,
-        total_distance_m: total_distance,
-        avg_power: if has_power_data {
-            let valid_power: Vec<f64> = power.iter().filter(|&&p| p > 0.0).cloned().collect();
-            if valid_power.is_empty() { 0.0 } else { valid_power.iter().sum::<f64>() / valid_power.len() as f64 }
-        } else { 0.0 },
-        max_power: power.iter().fold(0.0, |a, &b| a.max(b)),
-        avg_speed_ms: if !velocity.is_empty() {
-            velocity.iter().sum::<f64>() / velocity.len() as f64
-        } else { 0.0 },
-        max_speed_ms: velocity.iter().fold(0.0, |a, &b| a.max(b)),
-    };
-
-    Ok(ParsedFitFile {
-        fit_data,
-        laps,
-        parsing_statistics,
+    let parser = FitParserWrapper::new(file_data.to_vec())?;
+
+    let mut diagnostics = FitDiagnostics::inspect(file_data, mode);
+    if mode == FitParseMode::Strict {
+        if let Some(problem) = diagnostics.integrity_problem() {
+            return Err(Error::FitParse(problem));
+        }
+    }
+
+    let messages = parser.parse_with(mode == FitParseMode::Recover)?;
+    diagnostics.decoded_bytes = messages.decoded_bytes;
+    diagnostics.stop_reason = messages.decode_error.clone();
+    if let Some(reason) = &diagnostics.stop_reason {
+        if messages.records.is_empty() {
+            return Err(Error::FitParse(format!("No records could be recovered: {}", reason)));
+        }
+        crate::utils::warn(&diagnostics.summary());
+    }
+    Ok(ParsedFitFile::from_messages(messages, file_data.len(), diagnostics))
+}
+
+/// Stable fingerprint of a FIT file's data section together with its FileId
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn fingerprint_fit_file(file_data: &[u8]) -> Result<FitFingerprint> {
+    // The header CRC doesn't matter here; a data section shorter than declared is caught below
+    crate::security::SecurityValidator::new().inspect_fit_data(file_data).check(true)?;
+
+    let header_size = file_data[0] as usize;
+    let data_size = LittleEndian::read_u32(&file_data[4..8]) as usize;
+    let data = header_size.checked_add(data_size)
+        .and_then(|end| file_data.get(header_size..end))
+        .ok_or_else(|| Error::Validation(format!(
+            "FIT data size {} exceeds file length {}", data_size, file_data.len()
+        )))?;
+
+    let file_id = FitParserWrapper::new(file_data.to_vec())?.parse_file_id()?;
+
+    Ok(FitFingerprint {
+        sha256: crate::utils::sha256_hex(data),
+        data_size,
+        file_id: file_id.map(FileId::from),
     })
 }
 
-// Real FIT parsing now implemented - no more estimation needed
\ No newline at end of file
+// Real FIT parsing now implemented - no more estimation needed
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{base_type, mesg_num, FitWriter};
+
+    /// FIT timestamps count from 1989-12-31T00:00:00Z
+    const FIT_EPOCH: f64 = 631_065_600.0;
+
+    fn activity(power: u16) -> FitWriter {
+        let mut writer = FitWriter::new();
+        writer
+            .message(mesg_num::FILE_ID, &[
+                (0, base_type::ENUM, &[4]),
+                (1, base_type::UINT16, &1u16.to_le_bytes()),
+                (2, base_type::UINT16, &2697u16.to_le_bytes()),
+                (3, base_type::UINT32Z, &3_945_849_289u32.to_le_bytes()),
+                (4, base_type::UINT32, &1_000_000_000u32.to_le_bytes()),
+            ])
+            .message(mesg_num::RECORD, &[
+                (253, base_type::UINT32, &1_000_000_001u32.to_le_bytes()),
+                (7, base_type::UINT16, &power.to_le_bytes()),
+            ]);
+        writer
+    }
+
+    #[test]
+    fn test_rejects_non_fit_data() {
+        assert!(matches!(parse_fit_file(b"not a fit file"), Err(Error::Validation(_))));
+        assert!(matches!(parse_fit_file(&[14]), Err(Error::Validation(_))));
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_header() {
+        let writer = activity(250);
+        let fingerprint = fingerprint_fit_file(&writer.finish(14, Some(0x1234))).unwrap();
+
+        for file in [writer.finish(14, None), writer.finish(14, Some(0)), writer.finish(12, None)] {
+            assert_eq!(fingerprint_fit_file(&file).unwrap().sha256(), fingerprint.sha256());
+        }
+        assert_ne!(fingerprint_fit_file(&activity(251).finish(14, None)).unwrap().sha256(), fingerprint.sha256());
+
+        let file_id = fingerprint.file_id().unwrap();
+        assert_eq!(file_id.file_type().as_deref(), Some("activity"));
+        assert_eq!(file_id.manufacturer().as_deref(), Some("garmin"));
+        assert_eq!(file_id.serial_number(), Some(3_945_849_289));
+        assert_eq!(file_id.time_created(), Some(FIT_EPOCH + 1_000_000_000.0));
+    }
+
+    #[test]
+    fn test_fingerprint_rejects_truncated_data() {
+        let mut file = activity(250).finish(14, None);
+        file.truncate(30);
+        assert!(matches!(fingerprint_fit_file(&file), Err(Error::Validation(_))));
+    }
+
+    #[test]
+    fn test_parses_devices_sessions_and_user_profile() {
+        let power_meter = |timestamp: u32, battery_status: u8| -> Vec<(u8, u8, Vec<u8>)> {
+            vec![
+                (253, base_type::UINT32, timestamp.to_le_bytes().to_vec()),
+                (0, base_type::UINT8, vec![1]),
+                (25, base_type::ENUM, vec![1]), // antplus
+                (1, base_type::UINT8, vec![11]), // bike_power
+                (2, base_type::UINT16, 1u16.to_le_bytes().to_vec()),
+                (3, base_type::UINT32Z, 12_345u32.to_le_bytes().to_vec()),
+                (5, base_type::UINT16, 432u16.to_le_bytes().to_vec()),
+                (11, base_type::UINT8, vec![battery_status]),
+            ]
+        };
+
+        // Start and end-of-ride DeviceInfo for the same sensor collapse into one entry
+        let mut writer = activity(250);
+        for fields in [power_meter(1_000_000_000, 2), power_meter(1_000_003_600, 4)] {
+            let refs: Vec<(u8, u8, &[u8])> = fields.iter().map(|(n, t, v)| (*n, *t, v.as_slice())).collect();
+            writer.message(mesg_num::DEVICE_INFO, &refs);
+        }
+        writer
+            .message(mesg_num::USER_PROFILE, &[(4, base_type::UINT16, &712u16.to_le_bytes())])
+            .message(mesg_num::SESSION, &[
+                (253, base_type::UINT32, &1_000_003_600u32.to_le_bytes()),
+                (2, base_type::UINT32, &1_000_000_000u32.to_le_bytes()),
+                (5, base_type::ENUM, &[2]),
+                (7, base_type::UINT32, &3_600_000u32.to_le_bytes()),
+                (20, base_type::UINT16, &245u16.to_le_bytes()),
+                (26, base_type::UINT16, &3u16.to_le_bytes()),
+            ]);
+
+        let parsed = parse_fit_file(&writer.finish(14, None)).unwrap();
+        assert_eq!(parsed.file_id().unwrap().manufacturer().as_deref(), Some("garmin"));
+
+        assert_eq!(parsed.devices().len(), 1);
+        let power_meter = parsed.power_meter().unwrap();
+        assert_eq!(power_meter.serial_number(), Some(12_345));
+        assert_eq!(power_meter.software_version(), Some(4.32));
+        assert_eq!(power_meter.battery_status().as_deref(), Some("low"));
+
+        let session = &parsed.sessions()[0];
+        assert_eq!(session.sport().as_deref(), Some("cycling"));
+        assert_eq!(session.start_time(), FIT_EPOCH + 1_000_000_000.0);
+        assert_eq!(session.end_time(), session.start_time() + 3600.0);
+        assert_eq!(session.avg_power(), Some(245.0));
+        assert_eq!(session.num_laps(), Some(3));
+
+        assert_eq!(parsed.user_profile().unwrap().weight(), Some(71.2));
+    }
+
+    #[test]
+    fn test_multisport_sessions_split_records_and_laps() {
+        let mut writer = FitWriter::new();
+        writer.message(mesg_num::FILE_ID, &[(0, base_type::ENUM, &[4])]);
+        // Bike 0..=9 s at 200 W, transition, run 20..=29 s without power
+        for (sport, start, power) in [(2u8, 0u32, 200u16), (1, 20, 0)] {
+            for t in start..start + 10 {
+                writer.message(mesg_num::RECORD, &[
+                    (253, base_type::UINT32, &(1_000_000_000 + t).to_le_bytes()),
+                    (7, base_type::UINT16, &power.to_le_bytes()),
+                ]);
+            }
+            let times: [(u8, u8, &[u8]); 3] = [
+                (253, base_type::UINT32, &(1_000_000_009 + start).to_le_bytes()),
+                (2, base_type::UINT32, &(1_000_000_000 + start).to_le_bytes()),
+                (7, base_type::UINT32, &9000u32.to_le_bytes()),
+            ];
+            writer.message(mesg_num::LAP, &times);
+            writer.message(mesg_num::SESSION, &[times[0], times[1], times[2], (5, base_type::ENUM, &[sport])]);
+        }
+
+        let parsed = parse_fit_file(&writer.finish(14, None)).unwrap();
+        assert_eq!(parsed.fit_data().record_count(), 20);
+        let sessions = parsed.sessions();
+        assert_eq!((sessions[1].record_start(), sessions[1].record_count()), (10, 10));
+        assert_eq!((sessions[1].lap_start(), sessions[1].lap_count()), (1, 1));
+
+        let bike = parsed.session(parsed.find_session("cycling").unwrap()).unwrap();
+        assert_eq!(bike.fit_data().record_count(), 10);
+        assert!(bike.fit_data().power().iter().all(|&p| p == 200.0));
+        assert_eq!(bike.laps().len(), 1);
+        assert_eq!(bike.parsing_statistics().duration_seconds(), 9.0);
+
+        let run = parsed.session(parsed.find_session("running").unwrap()).unwrap();
+        assert_eq!(run.fit_data().timestamps()[0], FIT_EPOCH + 1_000_000_020.0);
+        assert!(!run.parsing_statistics().has_power_data());
+        let error = parsed.session(2).unwrap_err();
+        assert!(matches!(error, Error::InvalidInput(_)));
+        assert!(error.to_string().contains("Session 3 not in file (2 sessions)"));
+    }
+
+    fn ride(records: u32) -> Vec<u8> {
+        let mut writer = activity(250);
+        for t in 0..records {
+            writer.message(mesg_num::RECORD, &[
+                (253, base_type::UINT32, &(1_000_000_002 + t).to_le_bytes()),
+                (7, base_type::UINT16, &200u16.to_le_bytes()),
+            ]);
+        }
+        writer.finish(14, None)
+    }
+
+    #[test]
+    fn test_strict_mode_reports_crc_mismatch() {
+        let file = ride(5);
+        let parsed = parse_fit_file_with_mode(&file, FitParseMode::Strict).unwrap();
+        assert_eq!(parsed.diagnostics().header_crc_valid(), Some(true));
+        assert_eq!(parsed.diagnostics().data_crc_valid(), Some(true));
+
+        let mut bad_header = file.clone();
+        bad_header[12] ^= 0xFF;
+        let err = parse_fit_file_with_mode(&bad_header, FitParseMode::Strict).unwrap_err();
+        assert!(err.to_string().contains("header CRC mismatch"), "{}", err);
+
+        // Flip a power value: still decodes, but the data CRC no longer matches
+        let mut bad_data = file.clone();
+        let power = bad_data.len() - 4;
+        bad_data[power] ^= 0x01;
+        let err = parse_fit_file_with_mode(&bad_data, FitParseMode::Strict).unwrap_err();
+        assert!(err.to_string().contains("data CRC mismatch"), "{}", err);
+
+        let lenient = parse_fit_file(&bad_data).unwrap();
+        assert_eq!(lenient.diagnostics().data_crc_valid(), Some(false));
+        assert_eq!(lenient.fit_data().record_count(), 6);
+    }
+
+    #[test]
+    fn test_recover_mode_salvages_truncated_file() {
+        let file = ride(5);
+        // Cut into the last record message
+        let truncated = &file[..file.len() - 5];
+        assert!(parse_fit_file(truncated).is_err());
+        assert!(matches!(parse_fit_file_with_mode(truncated, FitParseMode::Strict), Err(Error::Validation(_))));
+
+        let recovered = parse_fit_file_with_mode(truncated, FitParseMode::Recover).unwrap();
+        let diagnostics = recovered.diagnostics();
+        assert_eq!(recovered.fit_data().record_count(), 5);
+        assert!(diagnostics.truncated() && diagnostics.recovered());
+        assert!(diagnostics.decoded_bytes() < truncated.len());
+        assert!(diagnostics.summary().starts_with("Recovered"));
+
+        // Head unit died before writing the data size
+        let mut unsized_file = truncated.to_vec();
+        unsized_file[4..8].copy_from_slice(&[0; 4]);
+        let recovered = parse_fit_file_with_mode(&unsized_file, FitParseMode::Recover).unwrap();
+        assert_eq!(recovered.fit_data().record_count(), 5);
+    }
+
+    #[test]
+    fn test_truncated_chained_header_is_an_error() {
+        // Found by the fit_parse fuzz target: fitparser underflowed on this
+        let mut file = ride(5);
+        let records = parse_fit_file(&file).unwrap().fit_data().record_count();
+        file.extend_from_slice(&[14, 0x20, 0x54]);
+        assert!(matches!(parse_fit_file(&file), Err(Error::FitParse(_))));
+
+        let recovered = parse_fit_file_with_mode(&file, FitParseMode::Recover).unwrap();
+        assert_eq!(recovered.fit_data().record_count(), records);
+        assert!(recovered.diagnostics().stop_reason().unwrap().contains("Invalid FIT header"));
+    }
+}
@@ -1,7 +1,8 @@
-use fitparser::{self, Value, de::DecodeOption};
+use byteorder::{ByteOrder, LittleEndian};
+use fitparser::{self, Value, de::{DecodeOption, FitObject, FitStreamProcessor}};
 use serde::{Deserialize, Serialize};
-use std::io::Cursor;
-use std::collections::HashSet;
+use std::borrow::Cow;
+use crate::error::{Error, Result};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FitRecord {
@@ -42,58 +43,240 @@ pub struct FitLap {
     pub max_cadence: Option<f64>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FitFileId {
+    pub file_type: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<u32>,
+    pub time_created: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FitDeviceInfo {
+    pub device_index: Option<String>,
+    pub device_type: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub product_name: Option<String>,
+    pub serial_number: Option<u32>,
+    pub software_version: Option<f64>,
+    pub hardware_version: Option<u32>,
+    pub battery_voltage: Option<f64>,
+    pub battery_status: Option<String>,
+    pub battery_level: Option<f64>,
+    pub source_type: Option<String>,
+}
+
+impl FitDeviceInfo {
+    /// Overlay a later message for the same device; fields it leaves out keep their values
+    fn merge(&mut self, later: FitDeviceInfo) {
+        macro_rules! overlay {
+            ($($field:ident),*) => { $( if later.$field.is_some() { self.$field = later.$field; } )* };
+        }
+        overlay!(device_type, manufacturer, product, product_name, serial_number, software_version,
+            hardware_version, battery_voltage, battery_status, battery_level, source_type);
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FitSession {
+    pub sport: Option<String>,
+    pub sub_sport: Option<String>,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub total_elapsed_time: f64,
+    pub total_timer_time: Option<f64>,
+    pub total_distance: Option<f64>,
+    pub total_calories: Option<f64>,
+    pub total_ascent: Option<f64>,
+    pub total_descent: Option<f64>,
+    pub avg_speed: Option<f64>,
+    pub max_speed: Option<f64>,
+    pub avg_power: Option<f64>,
+    pub max_power: Option<f64>,
+    pub normalized_power: Option<f64>,
+    pub avg_heart_rate: Option<f64>,
+    pub max_heart_rate: Option<f64>,
+    pub avg_cadence: Option<f64>,
+    pub first_lap_index: Option<u32>,
+    pub num_laps: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FitUserProfile {
+    pub weight: Option<f64>,
+    pub height: Option<f64>,
+    pub age: Option<f64>,
+    pub gender: Option<String>,
+}
+
+/// Everything the analysis uses from a FIT file, in file order
+#[derive(Debug, Clone, Default)]
+pub struct FitMessages {
+    pub records: Vec<FitRecord>,
+    pub laps: Vec<FitLap>,
+    pub file_id: Option<FitFileId>,
+    /// One entry per device index, later DeviceInfo messages overlaid on earlier ones
+    pub devices: Vec<FitDeviceInfo>,
+    pub sessions: Vec<FitSession>,
+    pub user_profile: Option<FitUserProfile>,
+    /// Bytes up to the end of the last message decoded
+    pub decoded_bytes: usize,
+    /// Why decoding stopped early (recovery mode only)
+    pub decode_error: Option<String>,
+}
+
 pub struct FitParserWrapper {
     data: Vec<u8>,
 }
 
 impl FitParserWrapper {
-    pub fn new(data: Vec<u8>) -> Result<Self, String> {
+    pub fn new(data: Vec<u8>) -> Result<Self> {
         if data.len() < 12 {
-            return Err("File too small to be a valid FIT file".to_string());
+            return Err(Error::Validation("File too small to be a valid FIT file".to_string()));
         }
 
         // Basic FIT signature check
         if &data[8..12] != b".FIT" {
-            return Err("Invalid FIT file signature".to_string());
+            return Err(Error::Validation("Invalid FIT file signature".to_string()));
         }
 
         Ok(FitParserWrapper { data })
     }
 
-    pub fn parse(&self) -> Result<(Vec<FitRecord>, Vec<FitLap>), String> {
-        // Parse FIT file using the fitparser crate
-        let mut cursor = Cursor::new(&self.data);
-
-        // Use decode options to extract developer fields properly
-        let mut opts = HashSet::new();
-        opts.insert(DecodeOption::SkipHeaderCrcValidation);
-        opts.insert(DecodeOption::SkipDataCrcValidation);
-        // Explicitly preserve all fields including developer fields
-        // DO NOT insert DropUnknownFields or DropUnknownMessages - we need developer fields!
-
-        let fit_data = fitparser::de::from_reader_with_options(&mut cursor, &opts)
-            .map_err(|e| format!("Failed to parse FIT file: {}", e))?;
+    pub fn parse(&self) -> Result<FitMessages> {
+        self.parse_with(false)
+    }
 
-        let mut records = Vec::new();
-        let mut laps = Vec::new();
+    /// With `recover`, a damaged or truncated file yields every message before the
+    /// damage instead of an error
+    pub fn parse_with(&self, recover: bool) -> Result<FitMessages> {
+        let (fit_data, decoded_bytes, decode_error) = self.decode(recover)?;
+        let mut messages = FitMessages {
+            decoded_bytes,
+            decode_error,
+            ..FitMessages::default()
+        };
 
-        for (_i, data_record) in fit_data.iter().enumerate() {
+        for data_record in fit_data.iter() {
             match data_record.kind() {
                 fitparser::profile::MesgNum::Record => {
                     if let Some(record) = self.extract_record(data_record) {
-                        records.push(record);
+                        messages.records.push(record);
                     }
                 }
                 fitparser::profile::MesgNum::Lap => {
                     if let Some(lap) = self.extract_lap(data_record) {
-                        laps.push(lap);
+                        messages.laps.push(lap);
+                    }
+                }
+                fitparser::profile::MesgNum::FileId if messages.file_id.is_none() => {
+                    messages.file_id = Some(self.extract_file_id(data_record));
+                }
+                fitparser::profile::MesgNum::DeviceInfo => {
+                    let device = self.extract_device_info(data_record);
+                    match messages.devices.iter_mut().find(|d| d.device_index == device.device_index) {
+                        Some(existing) => existing.merge(device),
+                        None => messages.devices.push(device),
+                    }
+                }
+                fitparser::profile::MesgNum::Session => {
+                    if let Some(session) = self.extract_session(data_record) {
+                        messages.sessions.push(session);
                     }
                 }
+                fitparser::profile::MesgNum::UserProfile => {
+                    messages.user_profile = Some(self.extract_user_profile(data_record));
+                }
                 _ => {} // Skip other message types
             }
         }
 
-        Ok((records, laps))
+        Ok(messages)
+    }
+
+    /// The first FileId message, if the file has one
+    pub fn parse_file_id(&self) -> Result<Option<FitFileId>> {
+        Ok(self.parse()?.file_id)
+    }
+
+    fn decode(&self, recover: bool) -> Result<(Vec<fitparser::FitDataRecord>, usize, Option<String>)> {
+        // Same loop as fitparser::de::from_bytes_with_options, but keeping track of how far
+        // decoding got so a damaged file can be cut at its last good message
+        let mut processor = FitStreamProcessor::new();
+        processor.add_option(DecodeOption::SkipHeaderCrcValidation);
+        processor.add_option(DecodeOption::SkipDataCrcValidation);
+        // Explicitly preserve all fields including developer fields
+        // DO NOT add DropUnknownFields or DropUnknownMessages - we need developer fields!
+
+        let data: Cow<[u8]> = if recover { Cow::Owned(self.with_recoverable_header()) } else { Cow::Borrowed(&self.data) };
+        let mut buffer = &data[..];
+        let mut records = Vec::new();
+        let mut end_of_messages = 0;
+
+        while !buffer.is_empty() {
+            // fitparser panics on a short header (underflow computing the bytes needed) or a
+            // header size past the end of the input (slicing for its CRC), so check any header
+            // it is about to read: at the start, or chained after the last file's messages
+            let offset = data.len() - buffer.len();
+            if offset == 0 || offset > end_of_messages {
+                let header_size = buffer[0] as usize;
+                if !matches!(header_size, 12 | 14) || buffer.len() < header_size {
+                    let message = format!(
+                        "Invalid FIT header at offset {}: size {} with {} bytes remaining",
+                        offset,
+                        header_size,
+                        buffer.len()
+                    );
+                    if recover {
+                        return Ok((records, offset, Some(message)));
+                    }
+                    return Err(Error::FitParse(message));
+                }
+            }
+
+            let step = processor.deserialize_next(buffer).and_then(|(remaining, object)| {
+                let record = match object {
+                    FitObject::DataMessage(message) => Some(processor.decode_message(message)?),
+                    FitObject::Crc(..) => {
+                        processor.reset();
+                        None
+                    }
+                    FitObject::Header(header) => {
+                        end_of_messages = offset + header.header_size() as usize + header.data_size() as usize;
+                        None
+                    }
+                    FitObject::DefinitionMessage(..) => None,
+                };
+                Ok((remaining, record))
+            });
+
+            match step {
+                Ok((remaining, record)) => {
+                    records.extend(record);
+                    buffer = remaining;
+                }
+                Err(e) if recover => {
+                    return Ok((records, data.len() - buffer.len(), Some(e.to_string())));
+                }
+                Err(e) => return Err(Error::FitParse(e.to_string())),
+            }
+        }
+
+        Ok((records, data.len(), None))
+    }
+
+    /// Crashed head units can leave the header's data size at 0; treat everything after
+    /// the header as data so the messages can still be read
+    fn with_recoverable_header(&self) -> Vec<u8> {
+        let mut data = self.data.clone();
+        let header_size = data[0] as usize;
+        if data.len() > header_size && LittleEndian::read_u32(&data[4..8]) == 0 {
+            let data_size = (data.len() - header_size) as u32;
+            LittleEndian::write_u32(&mut data[4..8], data_size);
+        }
+        data
     }
 
     fn extract_record(&self, message: &fitparser::FitDataRecord) -> Option<FitRecord> {
@@ -177,20 +360,16 @@ impl FitParserWrapper {
                     }
                 }
                 // Also check for the plain field names in case they appear without the prefix
-                "air_speed" => {
-                    // Only use if we haven't found the specific _0_11 field
-                    if air_speed.is_none() {
-                        if let Some(value) = self.extract_f64_value(field.value()) {
-                            air_speed = Some(value / 1000.0);
-                        }
+                // Only use if we haven't found the specific _0_11 field
+                "air_speed" if air_speed.is_none() => {
+                    if let Some(value) = self.extract_f64_value(field.value()) {
+                        air_speed = Some(value / 1000.0);
                     }
                 }
-                "wind_speed" => {
-                    // Only use if we haven't found the specific _0_6 field
-                    if wind_speed.is_none() {
-                        if let Some(value) = self.extract_f64_value(field.value()) {
-                            wind_speed = Some(value / 1000.0);
-                        }
+                // Only use if we haven't found the specific _0_6 field
+                "wind_speed" if wind_speed.is_none() => {
+                    if let Some(value) = self.extract_f64_value(field.value()) {
+                        wind_speed = Some(value / 1000.0);
                     }
                 }
                 _ => {
@@ -350,6 +529,165 @@ impl FitParserWrapper {
         }
     }
 
+    fn extract_file_id(&self, message: &fitparser::FitDataRecord) -> FitFileId {
+        let mut file_id = FitFileId::default();
+
+        for field in message.fields() {
+            match field.name() {
+                "type" => {
+                    file_id.file_type = self.extract_string_value(field.value());
+                }
+                "manufacturer" => {
+                    file_id.manufacturer = self.extract_string_value(field.value());
+                }
+                // Subfields resolve to e.g. garmin_product depending on the manufacturer
+                "product" | "garmin_product" | "favero_product" => {
+                    file_id.product = self.extract_string_value(field.value());
+                }
+                "serial_number" => {
+                    file_id.serial_number = self.extract_u32_value(field.value());
+                }
+                "time_created" => {
+                    file_id.time_created = self.extract_f64_value(field.value());
+                }
+                _ => {}
+            }
+        }
+
+        file_id
+    }
+
+    fn extract_device_info(&self, message: &fitparser::FitDataRecord) -> FitDeviceInfo {
+        let mut device = FitDeviceInfo::default();
+
+        for field in message.fields() {
+            match field.name() {
+                "device_index" => {
+                    device.device_index = self.extract_string_value(field.value());
+                }
+                // device_type resolves to a subfield depending on source_type
+                "device_type" | "antplus_device_type" | "ble_device_type" | "local_device_type" => {
+                    device.device_type = self.extract_string_value(field.value());
+                }
+                "manufacturer" => {
+                    device.manufacturer = self.extract_string_value(field.value());
+                }
+                "product" | "garmin_product" | "favero_product" => {
+                    device.product = self.extract_string_value(field.value());
+                }
+                "product_name" => {
+                    device.product_name = self.extract_string_value(field.value());
+                }
+                "serial_number" => {
+                    device.serial_number = self.extract_u32_value(field.value());
+                }
+                "software_version" => {
+                    device.software_version = self.extract_f64_value(field.value());
+                }
+                "hardware_version" => {
+                    device.hardware_version = self.extract_u32_value(field.value());
+                }
+                "battery_voltage" => {
+                    device.battery_voltage = self.extract_f64_value(field.value());
+                }
+                "battery_status" => {
+                    device.battery_status = self.extract_string_value(field.value());
+                }
+                "battery_level" => {
+                    device.battery_level = self.extract_f64_value(field.value());
+                }
+                "source_type" => {
+                    device.source_type = self.extract_string_value(field.value());
+                }
+                _ => {}
+            }
+        }
+
+        device
+    }
+
+    fn extract_session(&self, message: &fitparser::FitDataRecord) -> Option<FitSession> {
+        let mut session = FitSession::default();
+        let mut start_time = None;
+        let mut timestamp = None;
+        let mut total_elapsed_time = None;
+
+        for field in message.fields() {
+            let value = field.value();
+            match field.name() {
+                "sport" => session.sport = self.extract_string_value(value),
+                "sub_sport" => session.sub_sport = self.extract_string_value(value),
+                "start_time" => start_time = self.extract_f64_value(value),
+                "timestamp" => timestamp = self.extract_f64_value(value),
+                "total_elapsed_time" => total_elapsed_time = self.extract_f64_value(value),
+                "total_timer_time" => session.total_timer_time = self.extract_f64_value(value),
+                "total_distance" => session.total_distance = self.extract_f64_value(value),
+                "total_calories" => session.total_calories = self.extract_f64_value(value),
+                "total_ascent" => session.total_ascent = self.extract_f64_value(value),
+                "total_descent" => session.total_descent = self.extract_f64_value(value),
+                "avg_speed" | "enhanced_avg_speed" => session.avg_speed = self.extract_f64_value(value),
+                "max_speed" | "enhanced_max_speed" => session.max_speed = self.extract_f64_value(value),
+                "avg_power" => session.avg_power = self.extract_f64_value(value),
+                "max_power" => session.max_power = self.extract_f64_value(value),
+                "normalized_power" => session.normalized_power = self.extract_f64_value(value),
+                "avg_heart_rate" => session.avg_heart_rate = self.extract_f64_value(value),
+                "max_heart_rate" => session.max_heart_rate = self.extract_f64_value(value),
+                "avg_cadence" => session.avg_cadence = self.extract_f64_value(value),
+                "first_lap_index" => session.first_lap_index = self.extract_u32_value(value),
+                "num_laps" => session.num_laps = self.extract_u32_value(value),
+                _ => {}
+            }
+        }
+
+        // Same time derivation as laps: prefer start_time + elapsed, else end at the timestamp
+        let elapsed = total_elapsed_time?;
+        let (start, end) = match (start_time, timestamp) {
+            (Some(st), _) => (st, st + elapsed),
+            (None, Some(ts)) => (ts - elapsed, ts),
+            (None, None) => return None,
+        };
+        session.start_time = start.min(end);
+        session.end_time = start.max(end);
+        session.total_elapsed_time = elapsed;
+        Some(session)
+    }
+
+    fn extract_user_profile(&self, message: &fitparser::FitDataRecord) -> FitUserProfile {
+        let mut profile = FitUserProfile::default();
+
+        for field in message.fields() {
+            match field.name() {
+                "weight" => profile.weight = self.extract_f64_value(field.value()),
+                "height" => profile.height = self.extract_f64_value(field.value()),
+                "age" => profile.age = self.extract_f64_value(field.value()),
+                "gender" => profile.gender = self.extract_string_value(field.value()),
+                _ => {}
+            }
+        }
+
+        profile
+    }
+
+    /// Enum fields arrive as their profile name, or as the raw number if the profile doesn't know it
+    fn extract_string_value(&self, value: &Value) -> Option<String> {
+        match value {
+            Value::String(s) if !s.is_empty() => Some(s.clone()),
+            Value::String(_) => None,
+            Value::Enum(v) => Some(v.to_string()),
+            _ => self.extract_u32_value(value).map(|v| v.to_string()),
+        }
+    }
+
+    fn extract_u32_value(&self, value: &Value) -> Option<u32> {
+        match value {
+            Value::UInt8(v) | Value::UInt8z(v) => Some(*v as u32),
+            Value::UInt16(v) | Value::UInt16z(v) => Some(*v as u32),
+            Value::UInt32(v) | Value::UInt32z(v) => Some(*v),
+            Value::SInt64(v) => u32::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
     fn extract_f64_value(&self, value: &Value) -> Option<f64> {
         match value {
             Value::Timestamp(ts) => Some(ts.timestamp() as f64),
@@ -1,23 +1,60 @@
-use wasm_bindgen::prelude::*;
+//! Virtual elevation (Chung method) analysis of cycling ride data.
+//!
+//! The crate is plain Rust; the `wasm` feature (on by default) adds the
+//! wasm-bindgen exports used by the web frontend. Native users can depend on it
+//! with `default-features = false`. The `python` feature builds a PyO3/NumPy
+//! extension module instead (see `pyproject.toml`).
 
+mod acceleration_regression;
 mod air_density;
+mod analysis_record;
+mod analysis_session;
+mod coast_down;
+mod csv_import;
 mod dem_processor;
+mod error;
+mod export;
+mod fit_encoder;
+mod fit_messages;
 mod fit_parser;
 mod fitparser_wrapper;
+mod monte_carlo;
+mod optimizer;
+mod power_calibration;
+mod project;
+#[cfg(feature = "python")]
+mod python;
 mod security;
+mod sensitivity;
+mod sensor_merge;
+#[cfg(test)]
+mod test_support;
+mod track_import;
 mod utils;
 mod virtual_elevation;
+#[cfg(feature = "wasm")]
+mod wasm;
 
+pub use acceleration_regression::*;
 pub use air_density::*;
+pub use analysis_record::*;
+pub use analysis_session::*;
+pub use coast_down::*;
+pub use csv_import::*;
 pub use dem_processor::*;
+pub use error::{Error, Result};
+pub use export::*;
+pub use fit_encoder::*;
 pub use fit_parser::*;
 pub use fitparser_wrapper::*;
+pub use monte_carlo::*;
+pub use power_calibration::*;
+pub use project::*;
 pub use security::*;
+pub use sensitivity::*;
+pub use sensor_merge::*;
+pub use track_import::*;
+pub use utils::{format_distance, format_duration, format_power, format_speed};
 pub use virtual_elevation::*;
-
-// Initialize WASM module
-#[wasm_bindgen(start)]
-pub fn init() {
-    console_error_panic_hook::set_once();
-    web_sys::console::log_1(&"Virtual Elevation Analyzer WASM module initialized".into());
-}
\ No newline at end of file
+#[cfg(feature = "wasm")]
+pub use wasm::*;
@@ -0,0 +1,359 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Normal};
+use crate::error::{Error, Result};
+use crate::sensitivity::UncertainInput;
+use crate::virtual_elevation::{PowerCorrection, VirtualElevationCalculator};
+
+const DEFAULT_ITERATIONS: usize = 500;
+const MAX_ITERATIONS: usize = 10_000; // keeps a browser tab responsive
+
+/// Distribution of one uncertain input, in the input's own units
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputDistribution {
+    Normal { mean: f64, sd: f64 },
+    Uniform { min: f64, max: f64 },
+}
+
+impl InputDistribution {
+    fn validate(&self, name: &str) -> Result<()> {
+        let valid = match *self {
+            InputDistribution::Normal { mean, sd } => mean.is_finite() && sd.is_finite() && sd >= 0.0,
+            InputDistribution::Uniform { min, max } => min.is_finite() && max.is_finite() && min <= max,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidInput(format!("Invalid distribution for {}: {:?}", name, self)))
+        }
+    }
+
+    fn sample(&self, rng: &mut ChaCha8Rng) -> f64 {
+        match *self {
+            InputDistribution::Normal { mean, sd } => match Normal::new(mean, sd) {
+                Ok(normal) => normal.sample(rng),
+                Err(_) => mean,
+            },
+            InputDistribution::Uniform { min, max } => rng.gen_range(min..=max),
+        }
+    }
+}
+
+/// Monte Carlo settings. Inputs left as None keep the calculator's value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonteCarloConfig {
+    pub iterations: usize,
+    /// RNG seed; a random one is drawn (and reported) when omitted
+    pub seed: Option<u32>,
+    pub system_mass: Option<InputDistribution>,
+    pub rho: Option<InputDistribution>,
+    pub eta: Option<InputDistribution>,
+    pub wind_speed: Option<InputDistribution>,
+    pub wind_direction: Option<InputDistribution>,
+    pub power_scale: Option<InputDistribution>,
+    pub air_speed_calibration: Option<InputDistribution>,
+}
+
+impl Default for MonteCarloConfig {
+    fn default() -> Self {
+        MonteCarloConfig {
+            iterations: DEFAULT_ITERATIONS,
+            seed: None,
+            system_mass: None,
+            rho: None,
+            eta: None,
+            wind_speed: None,
+            wind_direction: None,
+            power_scale: None,
+            air_speed_calibration: None,
+        }
+    }
+}
+
+impl MonteCarloConfig {
+    fn distributions(&self) -> Vec<(UncertainInput, InputDistribution)> {
+        [
+            (UncertainInput::SystemMass, self.system_mass),
+            (UncertainInput::Rho, self.rho),
+            (UncertainInput::Eta, self.eta),
+            (UncertainInput::WindSpeed, self.wind_speed),
+            (UncertainInput::WindDirection, self.wind_direction),
+            (UncertainInput::PowerScale, self.power_scale),
+            (UncertainInput::AirSpeedCalibration, self.air_speed_calibration),
+        ]
+        .into_iter()
+        .filter_map(|(input, distribution)| distribution.map(|d| (input, d)))
+        .collect()
+    }
+}
+
+/// Mean, spread and percentiles of a sampled quantity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct DistributionSummary {
+    mean: f64,
+    std_dev: f64,
+    min: f64,
+    max: f64,
+    p2_5: f64,
+    p5: f64,
+    p25: f64,
+    p50: f64,
+    p75: f64,
+    p95: f64,
+    p97_5: f64,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl DistributionSummary {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn mean(&self) -> f64 { self.mean }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn std_dev(&self) -> f64 { self.std_dev }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn min(&self) -> f64 { self.min }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn max(&self) -> f64 { self.max }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn p2_5(&self) -> f64 { self.p2_5 }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn p5(&self) -> f64 { self.p5 }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn p25(&self) -> f64 { self.p25 }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn p50(&self) -> f64 { self.p50 }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn p75(&self) -> f64 { self.p75 }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn p95(&self) -> f64 { self.p95 }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn p97_5(&self) -> f64 { self.p97_5 }
+}
+
+impl DistributionSummary {
+    fn from_samples(samples: &[f64]) -> DistributionSummary {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = if samples.len() > 1 {
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let percentile = |p: f64| -> f64 {
+            // Linear interpolation between closest ranks
+            let rank = p / 100.0 * (sorted.len() - 1) as f64;
+            let lower = rank.floor() as usize;
+            let upper = rank.ceil() as usize;
+            sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+        };
+
+        DistributionSummary {
+            mean,
+            std_dev: variance.sqrt(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            p2_5: percentile(2.5),
+            p5: percentile(5.0),
+            p25: percentile(25.0),
+            p50: percentile(50.0),
+            p75: percentile(75.0),
+            p95: percentile(95.0),
+            p97_5: percentile(97.5),
+        }
+    }
+}
+
+/// CdA/Crr uncertainty from re-fitting with randomly drawn inputs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct MonteCarloResult {
+    seed: u32,
+    iterations: usize,
+    cda: DistributionSummary,
+    crr: DistributionSummary,
+    cda_crr_correlation: f64,
+    cda_samples: Vec<f64>,
+    crr_samples: Vec<f64>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl MonteCarloResult {
+    /// Seed that reproduces these samples when passed back in the config
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn seed(&self) -> u32 { self.seed }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn iterations(&self) -> usize { self.iterations }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda(&self) -> DistributionSummary { self.cda.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr(&self) -> DistributionSummary { self.crr.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda_crr_correlation(&self) -> f64 { self.cda_crr_correlation }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda_samples(&self) -> Vec<f64> { self.cda_samples.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr_samples(&self) -> Vec<f64> { self.crr_samples.clone() }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl VirtualElevationCalculator {
+    /// Propagate input uncertainty to CdA/Crr by Monte Carlo.
+    /// `config_json` is a MonteCarloConfig, e.g.
+    /// `{"iterations": 500, "seed": 42, "system_mass": {"type": "normal", "mean": 80, "sd": 0.5}}`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn monte_carlo(&self, config_json: &str, trim_start: usize, trim_end: usize) -> Result<MonteCarloResult> {
+        let config: MonteCarloConfig = serde_json::from_str(config_json)
+            .map_err(|e| Error::InvalidInput(format!("Invalid Monte Carlo config: {}", e)))?;
+
+        self.run_monte_carlo(&config, &[(trim_start, trim_end)])
+    }
+}
+
+impl VirtualElevationCalculator {
+    /// Monte Carlo over one or more trim regions with a typed config
+    pub fn run_monte_carlo(&self, config: &MonteCarloConfig, segments: &[(usize, usize)]) -> Result<MonteCarloResult> {
+        if !(2..=MAX_ITERATIONS).contains(&config.iterations) {
+            return Err(Error::InvalidInput(format!("Iterations must be between 2 and {}", MAX_ITERATIONS)));
+        }
+
+        let distributions = config.distributions();
+        for (input, distribution) in &distributions {
+            distribution.validate(input.name())?;
+        }
+
+        let seed = config.seed.unwrap_or_else(|| uuid::Uuid::new_v4().as_fields().0);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed as u64);
+
+        let mut cda_samples = Vec::with_capacity(config.iterations);
+        let mut crr_samples = Vec::with_capacity(config.iterations);
+
+        for _ in 0..config.iterations {
+            let mut calculator = self.clone();
+            let mut power = PowerCorrection::NONE;
+            for (input, distribution) in &distributions {
+                let mut value = distribution.sample(&mut rng);
+                if input.is_strictly_positive() {
+                    value = value.max(1e-6);
+                }
+                input.apply(&mut calculator, &mut power, value);
+            }
+
+            let fit = calculator.fit_cda_crr(segments, power)?;
+            cda_samples.push(fit.cda());
+            crr_samples.push(fit.crr());
+        }
+
+        Ok(MonteCarloResult {
+            seed,
+            iterations: config.iterations,
+            cda: DistributionSummary::from_samples(&cda_samples),
+            crr: DistributionSummary::from_samples(&crr_samples),
+            cda_crr_correlation: correlation(&cda_samples, &crr_samples),
+            cda_samples,
+            crr_samples,
+        })
+    }
+}
+
+fn correlation(x: &[f64], y: &[f64]) -> f64 {
+    let n = x.len() as f64;
+    let mean_x = x.iter().sum::<f64>() / n;
+    let mean_y = y.iter().sum::<f64>() / n;
+
+    let mut covariance = 0.0;
+    let mut var_x = 0.0;
+    let mut var_y = 0.0;
+    for (a, b) in x.iter().zip(y) {
+        covariance += (a - mean_x) * (b - mean_y);
+        var_x += (a - mean_x).powi(2);
+        var_y += (b - mean_y).powi(2);
+    }
+
+    if var_x > 0.0 && var_y > 0.0 {
+        covariance / (var_x * var_y).sqrt()
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{rolling_course, CDA, MASS};
+
+    fn config() -> MonteCarloConfig {
+        MonteCarloConfig {
+            iterations: 40,
+            seed: Some(7),
+            system_mass: Some(InputDistribution::Normal { mean: MASS, sd: 1.0 }),
+            power_scale: Some(InputDistribution::Uniform { min: 0.98, max: 1.02 }),
+            ..MonteCarloConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_same_seed_reproduces_samples() {
+        let (calculator, segments) = rolling_course(&[7.0, 11.0], 1.0, 0.0);
+        let first = calculator.run_monte_carlo(&config(), &segments).unwrap();
+        let second = calculator.run_monte_carlo(&config(), &segments).unwrap();
+
+        assert_eq!(first.seed, 7);
+        assert_eq!(first.cda_samples, second.cda_samples);
+        assert_eq!(first.crr_samples, second.crr_samples);
+    }
+
+    #[test]
+    fn test_distribution_centered_on_true_cda() {
+        let (calculator, segments) = rolling_course(&[7.0, 11.0], 1.0, 0.0);
+        let result = calculator.run_monte_carlo(&config(), &segments).unwrap();
+
+        assert!((result.cda.mean - CDA).abs() < 0.005, "mean {}", result.cda.mean);
+        assert!(result.cda.std_dev > 0.0);
+        assert!(result.cda.p2_5 <= result.cda.p50 && result.cda.p50 <= result.cda.p97_5);
+    }
+
+    #[test]
+    fn test_config_from_json() {
+        let json = r#"{"iterations": 10, "rho": {"type": "normal", "mean": 1.2, "sd": 0.01}}"#;
+        let config: MonteCarloConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(config.iterations, 10);
+        assert_eq!(config.rho, Some(InputDistribution::Normal { mean: 1.2, sd: 0.01 }));
+        assert!(config.seed.is_none());
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let samples: Vec<f64> = (0..=100).map(|i| i as f64).collect();
+        let summary = DistributionSummary::from_samples(&samples);
+
+        assert_eq!(summary.p50, 50.0);
+        assert_eq!(summary.p2_5, 2.5);
+        assert_eq!(summary.min, 0.0);
+        assert_eq!(summary.max, 100.0);
+    }
+}
@@ -0,0 +1,171 @@
+use nalgebra::{DMatrix, DVector};
+
+const MAX_ITERATIONS: usize = 200;
+const COST_TOLERANCE: f64 = 1e-12;
+const MAX_DAMPING: f64 = 1e12;
+
+/// Outcome of a bounded nonlinear least-squares fit
+#[derive(Debug, Clone)]
+pub(crate) struct LeastSquaresFit {
+    pub params: Vec<f64>,
+    pub residuals: Vec<f64>,
+    /// Standard errors from the Gauss-Newton covariance estimate.
+    /// VE residuals are autocorrelated (cumulative sums), so treat these as optimistic.
+    pub std_errors: Vec<f64>,
+    pub iterations: usize,
+}
+
+impl LeastSquaresFit {
+    pub fn rss(&self) -> f64 {
+        self.residuals.iter().map(|r| r * r).sum()
+    }
+
+    pub fn rmse(&self) -> f64 {
+        if self.residuals.is_empty() {
+            0.0
+        } else {
+            (self.rss() / self.residuals.len() as f64).sqrt()
+        }
+    }
+}
+
+/// Minimize the sum of squared residuals with Levenberg-Marquardt, keeping every
+/// parameter inside its (min, max) bound. The Jacobian is estimated by finite differences.
+pub(crate) fn levenberg_marquardt<F>(residual_fn: F, initial: &[f64], bounds: &[(f64, f64)]) -> LeastSquaresFit
+where
+    F: Fn(&[f64]) -> Vec<f64>,
+{
+    let n_params = initial.len();
+    let mut params: Vec<f64> = initial.iter().zip(bounds)
+        .map(|(&p, &(lo, hi))| p.clamp(lo, hi))
+        .collect();
+
+    let mut residuals = residual_fn(&params);
+    let mut cost = sum_of_squares(&residuals);
+    let mut jacobian = numeric_jacobian(&residual_fn, &params, &residuals, bounds);
+    let mut damping = 1e-3;
+    let mut iterations = 0;
+
+    while iterations < MAX_ITERATIONS && n_params > 0 {
+        iterations += 1;
+
+        let jt = jacobian.transpose();
+        let jtj = &jt * &jacobian;
+        let gradient = &jt * DVector::from_column_slice(&residuals);
+
+        // Marquardt scaling: damp each direction relative to its own curvature
+        let mut lhs = jtj.clone();
+        for k in 0..n_params {
+            lhs[(k, k)] += damping * jtj[(k, k)].max(1e-12);
+        }
+
+        let step = match lhs.lu().solve(&(-gradient)) {
+            Some(step) => step,
+            None => break,
+        };
+
+        let candidate: Vec<f64> = params.iter().zip(step.iter()).zip(bounds)
+            .map(|((&p, &dp), &(lo, hi))| (p + dp).clamp(lo, hi))
+            .collect();
+        let candidate_residuals = residual_fn(&candidate);
+        let candidate_cost = sum_of_squares(&candidate_residuals);
+
+        if candidate_cost.is_finite() && candidate_cost < cost {
+            let improvement = cost - candidate_cost;
+            params = candidate;
+            residuals = candidate_residuals;
+            cost = candidate_cost;
+            damping = (damping * 0.3).max(1e-12);
+
+            if improvement <= COST_TOLERANCE * cost.max(1e-12) {
+                break;
+            }
+            jacobian = numeric_jacobian(&residual_fn, &params, &residuals, bounds);
+        } else {
+            damping *= 10.0;
+            if damping > MAX_DAMPING {
+                break;
+            }
+        }
+    }
+
+    let std_errors = standard_errors(&jacobian, cost, residuals.len());
+
+    LeastSquaresFit {
+        params,
+        residuals,
+        std_errors,
+        iterations,
+    }
+}
+
+fn sum_of_squares(values: &[f64]) -> f64 {
+    values.iter().map(|v| v * v).sum()
+}
+
+/// Forward differences, stepping backwards when a parameter sits on its upper bound
+fn numeric_jacobian<F>(residual_fn: &F, params: &[f64], residuals: &[f64], bounds: &[(f64, f64)]) -> DMatrix<f64>
+where
+    F: Fn(&[f64]) -> Vec<f64>,
+{
+    let mut jacobian = DMatrix::zeros(residuals.len(), params.len());
+
+    for j in 0..params.len() {
+        let mut h = 1e-6 * params[j].abs().max(1.0);
+        if params[j] + h > bounds[j].1 {
+            h = -h;
+        }
+
+        let mut shifted = params.to_vec();
+        shifted[j] += h;
+        let shifted_residuals = residual_fn(&shifted);
+
+        for (i, (r1, r0)) in shifted_residuals.iter().zip(residuals).enumerate() {
+            jacobian[(i, j)] = (r1 - r0) / h;
+        }
+    }
+
+    jacobian
+}
+
+fn standard_errors(jacobian: &DMatrix<f64>, rss: f64, n_residuals: usize) -> Vec<f64> {
+    let n_params = jacobian.ncols();
+    if n_residuals <= n_params {
+        return vec![f64::NAN; n_params];
+    }
+
+    let sigma2 = rss / (n_residuals - n_params) as f64;
+    match (jacobian.transpose() * jacobian).try_inverse() {
+        Some(inverse) => (0..n_params)
+            .map(|k| (inverse[(k, k)] * sigma2).max(0.0).sqrt())
+            .collect(),
+        None => vec![f64::NAN; n_params],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_fit_recovers_parameters() {
+        let xs: Vec<f64> = (0..50).map(|i| i as f64 * 0.1).collect();
+        let ys: Vec<f64> = xs.iter().map(|x| 2.5 * x - 1.0).collect();
+
+        let fit = levenberg_marquardt(
+            |p| xs.iter().zip(&ys).map(|(x, y)| p[0] * x + p[1] - y).collect(),
+            &[1.0, 0.0],
+            &[(-10.0, 10.0), (-10.0, 10.0)],
+        );
+
+        assert!((fit.params[0] - 2.5).abs() < 1e-6);
+        assert!((fit.params[1] + 1.0).abs() < 1e-6);
+        assert!(fit.rmse() < 1e-6);
+    }
+
+    #[test]
+    fn test_bounds_are_respected() {
+        let fit = levenberg_marquardt(|p| vec![p[0] - 5.0], &[0.0], &[(-1.0, 1.0)]);
+        assert_eq!(fit.params[0], 1.0);
+    }
+}
@@ -0,0 +1,245 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::optimizer::levenberg_marquardt;
+use crate::virtual_elevation::{PowerCorrection, VirtualElevationCalculator};
+
+// Plausible power meter calibration errors; anything outside is a broken meter, not a bias
+const POWER_SCALE_BOUNDS: (f64, f64) = (0.8, 1.2);
+const POWER_OFFSET_BOUNDS: (f64, f64) = (-50.0, 50.0);
+
+/// Result of fitting a power meter offset/scale together with CdA/Crr
+/// over repeated runs of the same course at different speeds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct PowerCalibrationResult {
+    cda: f64,
+    crr: f64,
+    power_offset: f64,
+    power_scale: f64,
+    power_offset_std_error: f64,
+    power_scale_std_error: f64,
+    rmse: f64,
+    baseline_cda: f64,
+    baseline_crr: f64,
+    baseline_rmse: f64,
+    segment_avg_speed: Vec<f64>,
+    apparent_segment_cda: Vec<f64>,
+    corrected_segment_cda: Vec<f64>,
+    apparent_cda_spread: f64,
+    corrected_cda_spread: f64,
+    explained_cda_fraction: f64,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl PowerCalibrationResult {
+    /// CdA fitted together with the power correction
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda(&self) -> f64 { self.cda }
+
+    /// Crr fitted together with the power correction
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr(&self) -> f64 { self.crr }
+
+    /// Watts to add to measured power while pedalling
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn power_offset(&self) -> f64 { self.power_offset }
+
+    /// Multiplier to apply to measured power (1.0 = meter reads true)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn power_scale(&self) -> f64 { self.power_scale }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn power_offset_std_error(&self) -> f64 { self.power_offset_std_error }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn power_scale_std_error(&self) -> f64 { self.power_scale_std_error }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn rmse(&self) -> f64 { self.rmse }
+
+    /// CdA fitted over the same runs assuming the power meter reads true
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn baseline_cda(&self) -> f64 { self.baseline_cda }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn baseline_crr(&self) -> f64 { self.baseline_crr }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn baseline_rmse(&self) -> f64 { self.baseline_rmse }
+
+    /// CdA change attributable to the power calibration error (baseline - corrected)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda_shift(&self) -> f64 { self.baseline_cda - self.cda }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn segment_avg_speed(&self) -> Vec<f64> { self.segment_avg_speed.clone() }
+
+    /// Per-run CdA with measured power (Crr held at the baseline fit)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn apparent_segment_cda(&self) -> Vec<f64> { self.apparent_segment_cda.clone() }
+
+    /// Per-run CdA with corrected power (Crr held at the joint fit)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn corrected_segment_cda(&self) -> Vec<f64> { self.corrected_segment_cda.clone() }
+
+    /// Max - min of the per-run CdA with measured power
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn apparent_cda_spread(&self) -> f64 { self.apparent_cda_spread }
+
+    /// Max - min of the per-run CdA with corrected power
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn corrected_cda_spread(&self) -> f64 { self.corrected_cda_spread }
+
+    /// Share (0-1) of the run-to-run CdA spread removed by the power correction
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn explained_cda_fraction(&self) -> f64 { self.explained_cda_fraction }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl VirtualElevationCalculator {
+    /// Estimate power meter offset and scale together with CdA/Crr.
+    /// Each trim region is one run of a repeated course; runs should cover different speeds
+    /// so the offset (~1/v), Crr (constant) and CdA (~v²) terms can be told apart.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn fit_power_calibration(&self, trim_starts: Vec<usize>, trim_ends: Vec<usize>) -> Result<PowerCalibrationResult> {
+        if trim_starts.len() != trim_ends.len() {
+            return Err(Error::InvalidInput("trim_starts and trim_ends must have the same length".to_string()));
+        }
+
+        let segments: Vec<(usize, usize)> = trim_starts.into_iter().zip(trim_ends).collect();
+        self.estimate_power_calibration(&segments)
+    }
+}
+
+impl VirtualElevationCalculator {
+    /// Power calibration fit over (start, end) trim regions, one per run
+    pub fn estimate_power_calibration(&self, segments: &[(usize, usize)]) -> Result<PowerCalibrationResult> {
+        let segments = self.validate_segments(segments)?;
+        if segments.len() < 2 {
+            return Err(Error::InsufficientData("Power calibration needs at least two runs at different speeds".to_string()));
+        }
+
+        let reference = self.reference_elevation()
+            .ok_or_else(|| Error::InsufficientData("No altitude data available to fit against".to_string()))?;
+        let inputs = self.slope_inputs();
+        let params = self.params();
+        let cda_bounds = (params.cda_min, params.cda_max);
+        let crr_bounds = (params.crr_min, params.crr_max);
+        let cda_start = (params.cda_min + params.cda_max) / 2.0;
+        let crr_start = (params.crr_min + params.crr_max) / 2.0;
+
+        // Baseline: CdA/Crr only, trusting the power meter
+        let baseline = levenberg_marquardt(
+            |p| self.segment_residuals(&inputs, &reference, &segments, p[0], p[1], PowerCorrection::NONE),
+            &[cda_start, crr_start],
+            &[cda_bounds, crr_bounds],
+        );
+        if baseline.residuals.len() < 5 {
+            return Err(Error::InsufficientData("Not enough valid altitude samples in the trim regions".to_string()));
+        }
+
+        // Joint fit: CdA, Crr, power scale and offset
+        let joint = levenberg_marquardt(
+            |p| {
+                let power = PowerCorrection { scale: p[2], offset: p[3] };
+                self.segment_residuals(&inputs, &reference, &segments, p[0], p[1], power)
+            },
+            &[baseline.params[0], baseline.params[1], 1.0, 0.0],
+            &[cda_bounds, crr_bounds, POWER_SCALE_BOUNDS, POWER_OFFSET_BOUNDS],
+        );
+        let correction = PowerCorrection { scale: joint.params[2], offset: joint.params[3] };
+
+        // Per-run CdA with Crr held fixed shows how CdA drifts with speed
+        let segment_cda = |crr: f64, power: PowerCorrection| -> Vec<f64> {
+            segments.iter()
+                .map(|&segment| {
+                    levenberg_marquardt(
+                        |p| self.segment_residuals(&inputs, &reference, &[segment], p[0], crr, power),
+                        &[cda_start],
+                        &[cda_bounds],
+                    ).params[0]
+                })
+                .collect()
+        };
+        let apparent_segment_cda = segment_cda(baseline.params[1], PowerCorrection::NONE);
+        let corrected_segment_cda = segment_cda(joint.params[1], correction);
+
+        let segment_avg_speed = segments.iter()
+            .map(|&(start, end)| {
+                let run = &inputs.velocity[start..=end];
+                run.iter().sum::<f64>() / run.len() as f64
+            })
+            .collect();
+
+        let apparent_cda_spread = spread(&apparent_segment_cda);
+        let corrected_cda_spread = spread(&corrected_segment_cda);
+        let explained_cda_fraction = if apparent_cda_spread > 1e-9 {
+            ((apparent_cda_spread - corrected_cda_spread) / apparent_cda_spread).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        Ok(PowerCalibrationResult {
+            cda: joint.params[0],
+            crr: joint.params[1],
+            power_scale: correction.scale,
+            power_offset: correction.offset,
+            power_scale_std_error: joint.std_errors[2],
+            power_offset_std_error: joint.std_errors[3],
+            rmse: joint.rmse(),
+            baseline_cda: baseline.params[0],
+            baseline_crr: baseline.params[1],
+            baseline_rmse: baseline.rmse(),
+            segment_avg_speed,
+            apparent_segment_cda,
+            corrected_segment_cda,
+            apparent_cda_spread,
+            corrected_cda_spread,
+            explained_cda_fraction,
+        })
+    }
+}
+
+fn spread(values: &[f64]) -> f64 {
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    if values.is_empty() { 0.0 } else { max - min }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{rolling_course, CDA};
+
+    const SPEEDS: [f64; 3] = [6.0, 9.0, 12.0];
+
+    #[test]
+    fn test_recovers_power_offset_and_scale() {
+        let (calculator, segments) = rolling_course(&SPEEDS, 1.05, 12.0);
+        let result = calculator.estimate_power_calibration(&segments).unwrap();
+
+        assert!((result.power_scale - 1.05).abs() < 0.01, "scale {}", result.power_scale);
+        assert!((result.power_offset - 12.0).abs() < 2.0, "offset {}", result.power_offset);
+        assert!((result.cda - CDA).abs() < 0.005, "cda {}", result.cda);
+        assert!(result.rmse < result.baseline_rmse);
+        assert!(result.explained_cda_fraction > 0.8);
+    }
+
+    #[test]
+    fn test_true_meter_needs_no_correction() {
+        let (calculator, segments) = rolling_course(&SPEEDS, 1.0, 0.0);
+        let result = calculator.estimate_power_calibration(&segments).unwrap();
+
+        assert!((result.power_scale - 1.0).abs() < 0.005);
+        assert!(result.power_offset.abs() < 1.0);
+        assert!((result.baseline_cda - CDA).abs() < 0.002);
+        assert!(result.cda_shift().abs() < 0.005);
+    }
+
+    #[test]
+    fn test_requires_multiple_runs() {
+        let (calculator, _) = rolling_course(&SPEEDS, 1.0, 0.0);
+        assert!(calculator.estimate_power_calibration(&[(1, 399)]).is_err());
+    }
+}
@@ -0,0 +1,455 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use std::io::{Cursor, Read, Write};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::analysis_record::AnalysisMetrics;
+use crate::analysis_session::AnalysisSession;
+use crate::error::{Error, Result};
+use crate::fit_parser::parse_fit_file;
+use crate::utils;
+use crate::virtual_elevation::{VEParameters, VEResult};
+
+/// Current .veproj manifest format. Older manifests are upgraded on read by
+/// `MIGRATIONS`; newer ones are rejected rather than misread.
+pub const PROJECT_FORMAT_VERSION: u32 = 1;
+
+/// `MIGRATIONS[i]` rewrites a format `i + 1` manifest into format `i + 2` in place.
+/// Append a step here whenever `PROJECT_FORMAT_VERSION` is bumped.
+const MIGRATIONS: &[fn(&mut Value) -> Result<()>] = &[];
+
+// Archive layout: a zip holding the manifest and, optionally, the FIT it was made from
+const MANIFEST_ENTRY: &str = "project.json";
+const FIT_ENTRY: &str = "source.fit";
+const MAX_MANIFEST_BYTES: u64 = 16_000_000;
+const MAX_FIT_BYTES: u64 = crate::security::MAX_FIT_FILE_SIZE as u64;
+
+/// The FIT file a project was made from, identified by content hash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceFit {
+    pub file_name: String,
+    pub sha256: String,
+    pub size: usize,
+}
+
+/// DEM used for the reference elevation. The raster itself is not bundled (it can
+/// be hundreds of MB); its hash lets the reader check they loaded the same one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DemReference {
+    pub file_name: String,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Contents of the .tfw/.jgw world file, for plain TIFFs
+    #[serde(default)]
+    pub world_file: Option<String>,
+    /// Contents of the .prj file
+    #[serde(default)]
+    pub projection: Option<String>,
+}
+
+/// Weather the air density came from. `params.rho` holds the value actually used.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WeatherInputs {
+    #[serde(default)]
+    pub temperature_c: Option<f64>,
+    #[serde(default)]
+    pub pressure_hpa: Option<f64>,
+    #[serde(default)]
+    pub humidity_percent: Option<f64>,
+    /// Per-sample air density (weather CSV), overriding `params.rho`
+    #[serde(default, with = "crate::utils::nan_as_null::option_vec")]
+    pub rho_array: Option<Vec<f64>>,
+}
+
+/// One saved CdA/Crr result: the selection it was made on and what it showed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectAnalysis {
+    pub label: String,
+    /// 1-based lap numbers the trim region was taken from, if any
+    #[serde(default)]
+    pub laps: Vec<usize>,
+    pub trim_start: usize,
+    pub trim_end: usize,
+    pub cda: f64,
+    pub crr: f64,
+    pub metrics: AnalysisMetrics,
+}
+
+/// Contents of `project.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectManifest {
+    pub format_version: u32,
+    /// Version of this crate that wrote the project
+    pub crate_version: String,
+    pub name: String,
+    #[serde(default)]
+    pub notes: String,
+    pub source: SourceFit,
+    #[serde(default)]
+    pub dem: Option<DemReference>,
+    #[serde(default)]
+    pub weather: Option<WeatherInputs>,
+    #[serde(default = "no_calibration")]
+    pub air_speed_calibration: f64,
+    pub params: VEParameters,
+    #[serde(default)]
+    pub analyses: Vec<ProjectAnalysis>,
+}
+
+fn no_calibration() -> f64 {
+    1.0
+}
+
+impl ProjectManifest {
+    /// Parse a manifest of any supported format version, migrating it to the current one
+    pub fn from_json(json: &str) -> Result<ProjectManifest> {
+        let value: Value = utils::from_json(json, "project manifest")?;
+        let value = migrate(value)?;
+        serde_json::from_value(value)
+            .map_err(|e| Error::Project(format!("Invalid project manifest: {}", e)))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        utils::to_json(self, "project manifest")
+    }
+}
+
+fn migrate(mut manifest: Value) -> Result<Value> {
+    let version = manifest.get("format_version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| Error::Project("Manifest has no format_version".to_string()))?;
+    if version == 0 || version > PROJECT_FORMAT_VERSION as u64 {
+        return Err(Error::Project(format!(
+            "Unsupported project format version {} (this build reads up to {})",
+            version, PROJECT_FORMAT_VERSION
+        )));
+    }
+
+    for step in &MIGRATIONS[(version - 1) as usize..] {
+        step(&mut manifest)?;
+    }
+    Ok(manifest)
+}
+
+/// A shareable .veproj project: the manifest plus, if embedded, the source FIT bytes
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
+pub struct VeProject {
+    manifest: ProjectManifest,
+    fit_bytes: Option<Vec<u8>>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl VeProject {
+    /// Start a project for a FIT file. With `embed_fit` false only its hash is
+    /// stored, and the reader has to supply the same file to re-open it.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(name: String, fit_file_name: String, fit_bytes: Vec<u8>, embed_fit: bool, params: VEParameters) -> VeProject {
+        let source = SourceFit {
+            file_name: fit_file_name,
+            sha256: utils::sha256_hex(&fit_bytes),
+            size: fit_bytes.len(),
+        };
+
+        VeProject {
+            manifest: ProjectManifest {
+                format_version: PROJECT_FORMAT_VERSION,
+                crate_version: env!("CARGO_PKG_VERSION").to_string(),
+                name,
+                notes: String::new(),
+                source,
+                dem: None,
+                weather: None,
+                air_speed_calibration: 1.0,
+                params,
+                analyses: Vec::new(),
+            },
+            fit_bytes: embed_fit.then_some(fit_bytes),
+        }
+    }
+
+    /// The manifest as JSON, for display
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn manifest_json(&self) -> Result<String> {
+        self.manifest.to_json()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn name(&self) -> String {
+        self.manifest.name.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn notes(&self) -> String {
+        self.manifest.notes.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter))]
+    pub fn set_notes(&mut self, notes: String) {
+        self.manifest.notes = notes;
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn params(&self) -> VEParameters {
+        self.manifest.params.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter))]
+    pub fn set_params(&mut self, params: VEParameters) {
+        self.manifest.params = params;
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn air_speed_calibration(&self) -> f64 {
+        self.manifest.air_speed_calibration
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(setter))]
+    pub fn set_air_speed_calibration(&mut self, calibration: f64) {
+        self.manifest.air_speed_calibration = calibration;
+    }
+
+    /// Record the weather behind `params.rho` (any of the values may be unknown)
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_weather(&mut self, temperature_c: Option<f64>, pressure_hpa: Option<f64>, humidity_percent: Option<f64>) {
+        let weather = self.manifest.weather.get_or_insert_with(WeatherInputs::default);
+        weather.temperature_c = temperature_c;
+        weather.pressure_hpa = pressure_hpa;
+        weather.humidity_percent = humidity_percent;
+    }
+
+    /// Per-sample air density used instead of `params.rho`
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_rho_array(&mut self, rho_array: Vec<f64>) {
+        self.manifest.weather.get_or_insert_with(WeatherInputs::default).rho_array = Some(rho_array);
+    }
+
+    /// Reference the DEM used for elevation; pass its bytes to store a hash for checking
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_dem_reference(
+        &mut self,
+        file_name: String,
+        dem_bytes: Option<Vec<u8>>,
+        world_file: Option<String>,
+        projection: Option<String>,
+    ) {
+        self.manifest.dem = Some(DemReference {
+            file_name,
+            sha256: dem_bytes.map(|bytes| utils::sha256_hex(&bytes)),
+            world_file,
+            projection,
+        });
+    }
+
+    /// True when `dem_bytes` is the DEM the project references (or no hash was stored)
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn matches_dem(&self, dem_bytes: &[u8]) -> bool {
+        match self.manifest.dem.as_ref().and_then(|dem| dem.sha256.as_ref()) {
+            Some(sha256) => *sha256 == utils::sha256_hex(dem_bytes),
+            None => self.manifest.dem.is_some(),
+        }
+    }
+
+    /// Save a result along with the lap/trim selection it was made on
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_analysis(
+        &mut self,
+        label: String,
+        laps: Vec<usize>,
+        trim_start: usize,
+        trim_end: usize,
+        cda: f64,
+        crr: f64,
+        result: &VEResult,
+    ) {
+        self.manifest.analyses.push(ProjectAnalysis {
+            label,
+            laps,
+            trim_start,
+            trim_end,
+            cda,
+            crr,
+            metrics: AnalysisMetrics::from(result),
+        });
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn analysis_count(&self) -> usize {
+        self.manifest.analyses.len()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn has_embedded_fit(&self) -> bool {
+        self.fit_bytes.is_some()
+    }
+
+    /// The embedded FIT file, if the project carries one
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn source_fit(&self) -> Option<Vec<u8>> {
+        self.fit_bytes.clone()
+    }
+
+    /// True when `fit_bytes` is the file the project was made from
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn matches_fit(&self, fit_bytes: &[u8]) -> bool {
+        fit_bytes.len() == self.manifest.source.size && utils::sha256_hex(fit_bytes) == self.manifest.source.sha256
+    }
+
+    /// Re-open the ride with the project's parameters, calibration and air density.
+    /// Uses the embedded FIT unless `fit_bytes` is given; either must match the source hash.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn open_session(&self, fit_bytes: Option<Vec<u8>>) -> Result<AnalysisSession> {
+        let fit_bytes = fit_bytes.as_deref()
+            .or(self.fit_bytes.as_deref())
+            .ok_or_else(|| Error::Project(format!(
+                "Project does not embed its FIT file; open {} to continue",
+                self.manifest.source.file_name
+            )))?;
+        if !self.matches_fit(fit_bytes) {
+            return Err(Error::Project(format!(
+                "FIT file does not match {} (sha256 {})",
+                self.manifest.source.file_name, self.manifest.source.sha256
+            )));
+        }
+
+        let mut session = AnalysisSession::new(parse_fit_file(fit_bytes)?, self.manifest.params.clone());
+        session.set_air_speed_calibration(self.manifest.air_speed_calibration);
+        if let Some(rho_array) = self.manifest.weather.as_ref().and_then(|w| w.rho_array.clone()) {
+            session.set_rho_array(rho_array);
+        }
+        Ok(session)
+    }
+
+    /// Write the .veproj archive
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_archive(&self) -> Result<Vec<u8>> {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+        writer.start_file(MANIFEST_ENTRY, options).map_err(zip_error)?;
+        writer.write_all(self.manifest.to_json()?.as_bytes()).map_err(io_error)?;
+        if let Some(fit_bytes) = &self.fit_bytes {
+            writer.start_file(FIT_ENTRY, options).map_err(zip_error)?;
+            writer.write_all(fit_bytes).map_err(io_error)?;
+        }
+
+        Ok(writer.finish().map_err(zip_error)?.into_inner())
+    }
+
+    /// Read a .veproj archive, migrating older manifests and checking the embedded FIT
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_archive(bytes: &[u8]) -> Result<VeProject> {
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(zip_error)?;
+
+        let manifest = read_entry(&mut archive, MANIFEST_ENTRY, MAX_MANIFEST_BYTES)?
+            .ok_or_else(|| Error::Project(format!("Archive has no {}", MANIFEST_ENTRY)))?;
+        let manifest = String::from_utf8(manifest)
+            .map_err(|_| Error::Project(format!("{} is not UTF-8", MANIFEST_ENTRY)))?;
+        let project = VeProject {
+            manifest: ProjectManifest::from_json(&manifest)?,
+            fit_bytes: read_entry(&mut archive, FIT_ENTRY, MAX_FIT_BYTES)?,
+        };
+
+        if let Some(fit_bytes) = &project.fit_bytes {
+            if !project.matches_fit(fit_bytes) {
+                return Err(Error::Project("Embedded FIT file does not match its recorded hash".to_string()));
+            }
+        }
+        Ok(project)
+    }
+}
+
+impl VeProject {
+    pub fn manifest(&self) -> &ProjectManifest {
+        &self.manifest
+    }
+
+    pub fn manifest_mut(&mut self) -> &mut ProjectManifest {
+        &mut self.manifest
+    }
+}
+
+/// Read a whole entry, refusing anything larger than `limit` however it's compressed
+fn read_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str, limit: u64) -> Result<Option<Vec<u8>>> {
+    let entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(zip_error(e)),
+    };
+    if entry.size() > limit {
+        return Err(Error::Project(format!("{} is too large ({} bytes)", name, entry.size())));
+    }
+
+    let mut contents = Vec::with_capacity(entry.size() as usize);
+    entry.take(limit + 1).read_to_end(&mut contents).map_err(io_error)?;
+    if contents.len() as u64 > limit {
+        return Err(Error::Project(format!("{} is too large", name)));
+    }
+    Ok(Some(contents))
+}
+
+fn zip_error(error: ZipError) -> Error {
+    Error::Project(format!("Invalid archive: {}", error))
+}
+
+fn io_error(error: std::io::Error) -> Error {
+    Error::Project(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{rolling_course, CDA, CRR};
+
+    fn project(embed_fit: bool) -> VeProject {
+        let (calculator, _) = rolling_course(&[9.0], 1.0, 0.0);
+        let result = calculator.calculate_virtual_elevation(CDA, CRR, 1, 399);
+
+        let mut project = VeProject::new("Track day".to_string(), "ride.fit".to_string(), b"not really a FIT".to_vec(), embed_fit, calculator.params().clone());
+        project.set_weather(Some(18.0), Some(1010.0), None);
+        project.set_rho_array(vec![1.2, f64::NAN, 1.21]);
+        project.set_dem_reference("dem.tif".to_string(), Some(b"raster".to_vec()), None, None);
+        project.add_analysis("Lap 2".to_string(), vec![2], 1, 399, CDA, CRR, &result);
+        project
+    }
+
+    #[test]
+    fn test_archive_round_trip() {
+        let original = project(true);
+        let restored = VeProject::from_archive(&original.to_archive().unwrap()).unwrap();
+
+        assert_eq!(restored.manifest_json().unwrap(), original.manifest_json().unwrap());
+        assert_eq!(restored.source_fit().as_deref(), Some(&b"not really a FIT"[..]));
+        assert!(restored.matches_dem(b"raster"));
+        assert!(!restored.matches_dem(b"other raster"));
+        assert!(restored.manifest().weather.as_ref().unwrap().rho_array.as_ref().unwrap()[1].is_nan());
+        assert_eq!(restored.manifest().analyses[0].laps, vec![2]);
+    }
+
+    #[test]
+    fn test_hash_only_project_needs_matching_fit() {
+        let restored = VeProject::from_archive(&project(false).to_archive().unwrap()).unwrap();
+        assert!(!restored.has_embedded_fit());
+        assert!(restored.matches_fit(b"not really a FIT"));
+
+        assert!(matches!(restored.open_session(None), Err(Error::Project(_))));
+        assert!(matches!(restored.open_session(Some(b"another file".to_vec())), Err(Error::Project(_))));
+    }
+
+    #[test]
+    fn test_manifest_version_checked() {
+        let mut manifest: Value = serde_json::from_str(&project(false).manifest_json().unwrap()).unwrap();
+        assert!(ProjectManifest::from_json(&manifest.to_string()).is_ok());
+
+        manifest["format_version"] = (PROJECT_FORMAT_VERSION + 1).into();
+        assert!(matches!(ProjectManifest::from_json(&manifest.to_string()), Err(Error::Project(_))));
+
+        manifest.as_object_mut().unwrap().remove("format_version");
+        assert!(matches!(ProjectManifest::from_json(&manifest.to_string()), Err(Error::Project(_))));
+    }
+}
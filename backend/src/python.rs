@@ -0,0 +1,1127 @@
+//! Python adapter: everything here exists only for the PyO3 build.
+//! The analysis types are `#[pyclass]` behind the `python` feature; this module
+//! adds the Python-facing methods, taking and returning NumPy arrays so results
+//! can be compared sample for sample with the web app.
+
+use numpy::{IntoPyArray, PyArray1, PyReadonlyArray1};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use crate::air_density::AirDensityCalculator;
+use crate::analysis_record::{AnalysisMetrics, AnalysisRecord};
+use crate::analysis_session::AnalysisSession;
+use crate::csv_import::{self, CsvImport};
+use crate::dem_processor::{DEMProcessor, DemLimits, DemMemoryEstimate};
+use crate::error::Error;
+use crate::export::{ExportOptions, ExportUnits, TrackElevation, VeExport};
+use crate::fit_encoder::FitRewrite;
+use crate::fit_parser::{
+    self, DeviceInfo, FileId, FitData, FitDiagnostics, FitFingerprint, FitParseMode, LapData, ParsedFitFile,
+    ParsingStatistics, SessionData, UserProfile,
+};
+use crate::project::VeProject;
+use crate::security::{FindingKind, FindingSeverity, FitSecurityReport, SecurityFinding, SecurityValidator};
+use crate::sensor_merge::{self, SensorMerge, SensorMergeOptions};
+use crate::track_import;
+use crate::virtual_elevation::{CdaCrrFit, VEData, VEParameters, VEResult, VirtualElevationCalculator};
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> PyErr {
+        PyValueError::new_err(error.to_string())
+    }
+}
+
+fn to_numpy(py: Python<'_>, values: Vec<f64>) -> Bound<'_, PyArray1<f64>> {
+    values.into_pyarray(py)
+}
+
+fn to_vec(array: Option<PyReadonlyArray1<'_, f64>>) -> Vec<f64> {
+    array.map(|a| a.as_array().to_vec()).unwrap_or_default()
+}
+
+/// Parse FIT file bytes (e.g. `open(path, "rb").read()`); `mode` selects CRC checking
+/// or recovery of truncated files
+#[pyfunction]
+#[pyo3(name = "parse_fit_file", signature = (data, mode = FitParseMode::Lenient))]
+fn py_parse_fit_file(data: &[u8], mode: FitParseMode) -> PyResult<ParsedFitFile> {
+    Ok(fit_parser::parse_fit_file_with_mode(data, mode)?)
+}
+
+/// Parse GPX file bytes into the same structure as `parse_fit_file`
+#[pyfunction]
+#[pyo3(name = "parse_gpx_file")]
+fn py_parse_gpx_file(data: &[u8]) -> PyResult<ParsedFitFile> {
+    Ok(track_import::parse_gpx_file(data)?)
+}
+
+/// Parse Garmin TCX file bytes into the same structure as `parse_fit_file`
+#[pyfunction]
+#[pyo3(name = "parse_tcx_file")]
+fn py_parse_tcx_file(data: &[u8]) -> PyResult<ParsedFitFile> {
+    Ok(track_import::parse_tcx_file(data)?)
+}
+
+/// Parse FIT, GPX or TCX bytes, detecting the format from the content
+#[pyfunction]
+#[pyo3(name = "parse_activity_file")]
+fn py_parse_activity_file(data: &[u8]) -> PyResult<ParsedFitFile> {
+    Ok(track_import::parse_activity_file(data)?)
+}
+
+/// Parse CSV sensor-log bytes; `config` is a CsvImportConfig as JSON, the Gibli
+/// aero sensor layout when omitted
+#[pyfunction]
+#[pyo3(name = "parse_csv_file", signature = (data, config = None))]
+fn py_parse_csv_file(data: &[u8], config: Option<&str>) -> PyResult<CsvImport> {
+    Ok(match config {
+        Some(config) => csv_import::parse_csv_file_with_config(data, config)?,
+        None => csv_import::parse_csv_file(data)?,
+    })
+}
+
+/// Align a CSV sensor log to the ride and resample it onto the FIT records
+#[pyfunction]
+#[pyo3(name = "merge_sensor_csv", signature = (fit_data, log, options = None))]
+fn py_merge_sensor_csv(fit_data: &FitData, log: &CsvImport, options: Option<SensorMergeOptions>) -> PyResult<SensorMerge> {
+    Ok(sensor_merge::merge_sensor_csv(fit_data, log, &options.unwrap_or_default())?)
+}
+
+/// Align a second FIT recording to the ride and resample it onto the FIT records
+#[pyfunction]
+#[pyo3(name = "merge_sensor_fit", signature = (fit_data, log, options = None))]
+fn py_merge_sensor_fit(fit_data: &FitData, log: &ParsedFitFile, options: Option<SensorMergeOptions>) -> PyResult<SensorMerge> {
+    Ok(sensor_merge::merge_sensor_fit(fit_data, log, &options.unwrap_or_default())?)
+}
+
+/// SHA-256 of the FIT data section plus the FileId, for recognising re-imported rides
+#[pyfunction]
+#[pyo3(name = "fingerprint_fit_file")]
+fn py_fingerprint_fit_file(data: &[u8]) -> PyResult<FitFingerprint> {
+    Ok(fit_parser::fingerprint_fit_file(data)?)
+}
+
+/// Structural and header checks without parsing; `report.accepts(recover)` says whether
+/// `parse_fit_file` would take the file
+#[pyfunction]
+#[pyo3(name = "inspect_fit_file")]
+fn py_inspect_fit_file(data: &[u8]) -> FitSecurityReport {
+    SecurityValidator::new().inspect_fit_data(data)
+}
+
+#[pymethods]
+impl FitSecurityReport {
+    #[pyo3(name = "accepts", signature = (recover = false))]
+    fn py_accepts(&self, recover: bool) -> bool {
+        self.accepts(recover)
+    }
+}
+
+#[pymethods]
+impl CsvImport {
+    #[getter(parsed)]
+    fn py_parsed(&self) -> ParsedFitFile {
+        self.parsed()
+    }
+
+    #[getter(fit_data)]
+    fn py_fit_data(&self) -> FitData {
+        self.fit_data()
+    }
+
+    #[getter(laps)]
+    fn py_laps(&self) -> Vec<LapData> {
+        self.laps()
+    }
+
+    #[getter(wind_angle)]
+    fn py_wind_angle<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.wind_angle())
+    }
+
+    #[getter(temperature)]
+    fn py_temperature<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.temperature())
+    }
+
+    #[getter(humidity)]
+    fn py_humidity<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.humidity())
+    }
+
+    #[getter(pressure)]
+    fn py_pressure<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.pressure())
+    }
+
+    #[getter(cda_reference)]
+    fn py_cda_reference<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.cda_reference())
+    }
+
+    #[getter(lap_number)]
+    fn py_lap_number<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.lap_number())
+    }
+
+    #[getter(has_environmental_data)]
+    fn py_has_environmental_data(&self) -> bool {
+        self.has_environmental_data()
+    }
+
+    #[getter(average_cda_reference)]
+    fn py_average_cda_reference(&self) -> Option<f64> {
+        self.average_cda_reference()
+    }
+
+    #[getter(skipped_rows)]
+    fn py_skipped_rows(&self) -> usize {
+        self.skipped_rows()
+    }
+
+    #[getter(resampled)]
+    fn py_resampled(&self) -> bool {
+        self.resampled()
+    }
+}
+
+#[pymethods]
+impl SensorMergeOptions {
+    #[new]
+    #[pyo3(signature = (
+        offset_seconds = 0.0,
+        align_by_speed = true,
+        max_lag_seconds = 300.0,
+        max_gap_seconds = 5.0,
+        humidity_percent = 50.0
+    ))]
+    fn py_new(
+        offset_seconds: f64,
+        align_by_speed: bool,
+        max_lag_seconds: f64,
+        max_gap_seconds: f64,
+        humidity_percent: f64,
+    ) -> SensorMergeOptions {
+        SensorMergeOptions { offset_seconds, align_by_speed, max_lag_seconds, max_gap_seconds, humidity_percent }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SensorMergeOptions(offset_seconds={}, align_by_speed={}, max_lag_seconds={}, max_gap_seconds={}, humidity_percent={})",
+            self.offset_seconds,
+            if self.align_by_speed { "True" } else { "False" },
+            self.max_lag_seconds,
+            self.max_gap_seconds,
+            self.humidity_percent
+        )
+    }
+}
+
+#[pymethods]
+impl SensorMerge {
+    #[getter(fit_data)]
+    fn py_fit_data(&self) -> FitData {
+        self.fit_data()
+    }
+
+    #[getter(ve_data)]
+    fn py_ve_data(&self) -> VEData {
+        self.ve_data()
+    }
+
+    #[getter(offset_seconds)]
+    fn py_offset_seconds(&self) -> f64 {
+        self.offset_seconds()
+    }
+
+    #[getter(correlation)]
+    fn py_correlation(&self) -> Option<f64> {
+        self.correlation()
+    }
+
+    #[getter(matched_records)]
+    fn py_matched_records(&self) -> usize {
+        self.matched_records()
+    }
+
+    #[getter(air_speed)]
+    fn py_air_speed<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.air_speed())
+    }
+
+    #[getter(wind_angle)]
+    fn py_wind_angle<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.wind_angle())
+    }
+
+    #[getter(temperature)]
+    fn py_temperature<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.temperature())
+    }
+
+    #[getter(pressure)]
+    fn py_pressure<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.pressure())
+    }
+
+    #[getter(humidity)]
+    fn py_humidity<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.humidity())
+    }
+
+    #[getter(rho)]
+    fn py_rho<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.rho())
+    }
+}
+
+#[pymethods]
+impl ExportOptions {
+    #[new]
+    #[pyo3(signature = (units = ExportUnits::Si, delimiter = ',', trim_only = false))]
+    fn py_new(units: ExportUnits, delimiter: char, trim_only: bool) -> ExportOptions {
+        ExportOptions { units, delimiter, trim_only }
+    }
+}
+
+#[pymethods]
+impl VeExport {
+    #[new]
+    #[pyo3(signature = (fit_data, result, params, cda, crr, trim_start, trim_end, rho_array = None, file_name = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        fit_data: FitData,
+        result: VEResult,
+        params: VEParameters,
+        cda: f64,
+        crr: f64,
+        trim_start: usize,
+        trim_end: usize,
+        rho_array: Option<PyReadonlyArray1<f64>>,
+        file_name: Option<String>,
+    ) -> VeExport {
+        let mut export = VeExport::new(fit_data, result, params, cda, crr, trim_start, trim_end);
+        if let Some(rho_array) = rho_array {
+            export.set_rho_array(rho_array.as_array().to_vec());
+        }
+        if let Some(file_name) = file_name {
+            export.set_file_name(file_name);
+        }
+        export
+    }
+
+    /// Header lines and per-sample rows as CSV text
+    #[pyo3(name = "write_csv", signature = (options = None))]
+    fn py_write_csv(&self, options: Option<ExportOptions>) -> PyResult<String> {
+        Ok(self.write_csv(&options.unwrap_or_default())?)
+    }
+
+    /// Header, units and per-sample rows as JSON text
+    #[pyo3(name = "write_json", signature = (options = None))]
+    fn py_write_json(&self, options: Option<ExportOptions>) -> PyResult<String> {
+        Ok(self.write_json(&options.unwrap_or_default())?)
+    }
+
+    /// Laps whose starts are marked in the KML export
+    #[pyo3(name = "set_laps")]
+    fn py_set_laps(&mut self, laps: Vec<LapData>) {
+        self.set_laps(laps);
+    }
+
+    /// Per-record DEM elevation for TrackElevation.Dem
+    #[pyo3(name = "set_dem_elevation")]
+    fn py_set_dem_elevation(&mut self, dem_elevation: PyReadonlyArray1<'_, f64>) {
+        self.set_dem_elevation(dem_elevation.as_array().to_vec());
+    }
+
+    /// Trim-region track as GPX text
+    #[pyo3(name = "write_gpx", signature = (elevation = TrackElevation::Actual))]
+    fn py_write_gpx(&self, elevation: TrackElevation) -> PyResult<String> {
+        Ok(self.write_gpx(elevation)?)
+    }
+
+    /// Trim-region track coloured by VE residual, with trim and lap markers, as KML text
+    #[pyo3(name = "write_kml", signature = (elevation = TrackElevation::Actual))]
+    fn py_write_kml(&self, elevation: TrackElevation) -> PyResult<String> {
+        Ok(self.write_kml(elevation)?)
+    }
+}
+
+#[pymethods]
+impl FitRewrite {
+    /// Altitude is taken from `fit_data`; `virtual_elevation` and `cda` add developer fields
+    #[new]
+    #[pyo3(signature = (fit_data, virtual_elevation = None, cda = None))]
+    fn py_new(fit_data: &FitData, virtual_elevation: Option<PyReadonlyArray1<f64>>, cda: Option<PyReadonlyArray1<f64>>) -> FitRewrite {
+        let mut rewrite = FitRewrite::new(fit_data);
+        if let Some(virtual_elevation) = virtual_elevation {
+            rewrite.set_virtual_elevation(virtual_elevation.as_array().to_vec());
+        }
+        if let Some(cda) = cda {
+            rewrite.set_cda(cda.as_array().to_vec());
+        }
+        rewrite
+    }
+
+    /// Bytes of the source FIT file with the replacements and fresh CRCs
+    #[pyo3(name = "write")]
+    fn py_write(&self, source: &[u8]) -> PyResult<Vec<u8>> {
+        Ok(self.write(source)?)
+    }
+}
+
+#[pymethods]
+impl ParsedFitFile {
+    #[getter(fit_data)]
+    fn py_fit_data(&self) -> FitData {
+        self.fit_data()
+    }
+
+    #[getter(laps)]
+    fn py_laps(&self) -> Vec<LapData> {
+        self.laps()
+    }
+
+    #[getter(parsing_statistics)]
+    fn py_parsing_statistics(&self) -> ParsingStatistics {
+        self.parsing_statistics()
+    }
+
+    #[getter(diagnostics)]
+    fn py_diagnostics(&self) -> FitDiagnostics {
+        self.diagnostics()
+    }
+
+    #[getter(file_id)]
+    fn py_file_id(&self) -> Option<FileId> {
+        self.file_id()
+    }
+
+    #[getter(devices)]
+    fn py_devices(&self) -> Vec<DeviceInfo> {
+        self.devices()
+    }
+
+    #[getter(sessions)]
+    fn py_sessions(&self) -> Vec<SessionData> {
+        self.sessions()
+    }
+
+    #[getter(user_profile)]
+    fn py_user_profile(&self) -> Option<UserProfile> {
+        self.user_profile()
+    }
+
+    #[getter(power_meter)]
+    fn py_power_meter(&self) -> Option<DeviceInfo> {
+        self.power_meter()
+    }
+
+    #[pyo3(name = "find_session")]
+    fn py_find_session(&self, sport: &str) -> Option<usize> {
+        self.find_session(sport)
+    }
+
+    /// Records and laps of one session only, e.g. the bike leg of a triathlon
+    #[pyo3(name = "session")]
+    fn py_session(&self, index: usize) -> PyResult<ParsedFitFile> {
+        Ok(self.session(index)?)
+    }
+}
+
+#[pymethods]
+impl FitDiagnostics {
+    #[getter(header_crc_valid)]
+    fn py_header_crc_valid(&self) -> Option<bool> {
+        self.header_crc_valid()
+    }
+
+    #[getter(data_crc_valid)]
+    fn py_data_crc_valid(&self) -> Option<bool> {
+        self.data_crc_valid()
+    }
+
+    #[getter(recovered)]
+    fn py_recovered(&self) -> bool {
+        self.recovered()
+    }
+
+    fn __repr__(&self) -> String {
+        self.summary()
+    }
+}
+
+#[pymethods]
+impl FitData {
+    #[getter(timestamps)]
+    fn py_timestamps<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.timestamps())
+    }
+
+    #[getter(power)]
+    fn py_power<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.power())
+    }
+
+    #[getter(velocity)]
+    fn py_velocity<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.velocity())
+    }
+
+    #[getter(position_lat)]
+    fn py_position_lat<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.position_lat())
+    }
+
+    #[getter(position_long)]
+    fn py_position_long<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.position_long())
+    }
+
+    #[getter(altitude)]
+    fn py_altitude<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.altitude())
+    }
+
+    #[setter(altitude)]
+    fn py_set_altitude(&mut self, altitude: PyReadonlyArray1<'_, f64>) {
+        self.set_altitude(altitude.as_array().to_vec());
+    }
+
+    #[getter(distance)]
+    fn py_distance<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.distance())
+    }
+
+    #[getter(air_speed)]
+    fn py_air_speed<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.air_speed())
+    }
+
+    #[getter(wind_speed)]
+    fn py_wind_speed<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.wind_speed())
+    }
+
+    #[getter(battery_soc)]
+    fn py_battery_soc<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.battery_soc())
+    }
+
+    #[getter(heart_rate)]
+    fn py_heart_rate<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.heart_rate())
+    }
+
+    #[getter(cadence)]
+    fn py_cadence<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.cadence())
+    }
+
+    #[getter(temperature)]
+    fn py_temperature<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.temperature())
+    }
+
+    fn __len__(&self) -> usize {
+        self.record_count()
+    }
+}
+
+#[pymethods]
+impl VEParameters {
+    #[new]
+    #[pyo3(signature = (
+        system_mass = 75.0, rho = 1.225, eta = 0.97, cda = None, crr = None,
+        cda_min = 0.15, cda_max = 0.50, crr_min = 0.002, crr_max = 0.015,
+        wind_speed = None, wind_direction = None, velodrome = false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        system_mass: f64,
+        rho: f64,
+        eta: f64,
+        cda: Option<f64>,
+        crr: Option<f64>,
+        cda_min: f64,
+        cda_max: f64,
+        crr_min: f64,
+        crr_max: f64,
+        wind_speed: Option<f64>,
+        wind_direction: Option<f64>,
+        velodrome: bool,
+    ) -> VEParameters {
+        VEParameters {
+            system_mass,
+            rho,
+            eta,
+            cda,
+            crr,
+            cda_min,
+            cda_max,
+            crr_min,
+            crr_max,
+            wind_speed,
+            wind_direction,
+            velodrome,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    #[pyo3(name = "to_json")]
+    fn py_to_json(&self) -> PyResult<String> {
+        Ok(self.to_json()?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_json")]
+    fn py_from_json(json: &str) -> PyResult<VEParameters> {
+        Ok(VEParameters::from_json(json)?)
+    }
+}
+
+#[pymethods]
+impl VEData {
+    /// Arrays are 1-D float64; channels the ride doesn't have may be omitted.
+    /// `rho` is an optional per-sample air density overriding `VEParameters.rho`.
+    #[new]
+    #[pyo3(signature = (
+        timestamps, power, velocity, altitude, *, position_lat = None, position_long = None,
+        distance = None, air_speed = None, wind_speed = None, rho = None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        timestamps: PyReadonlyArray1<'_, f64>,
+        power: PyReadonlyArray1<'_, f64>,
+        velocity: PyReadonlyArray1<'_, f64>,
+        altitude: PyReadonlyArray1<'_, f64>,
+        position_lat: Option<PyReadonlyArray1<'_, f64>>,
+        position_long: Option<PyReadonlyArray1<'_, f64>>,
+        distance: Option<PyReadonlyArray1<'_, f64>>,
+        air_speed: Option<PyReadonlyArray1<'_, f64>>,
+        wind_speed: Option<PyReadonlyArray1<'_, f64>>,
+        rho: Option<PyReadonlyArray1<'_, f64>>,
+    ) -> VEData {
+        let mut data = VEData::new(
+            timestamps.as_array().to_vec(),
+            power.as_array().to_vec(),
+            velocity.as_array().to_vec(),
+            to_vec(position_lat),
+            to_vec(position_long),
+            altitude.as_array().to_vec(),
+            to_vec(distance),
+            to_vec(air_speed),
+            to_vec(wind_speed),
+        );
+        if let Some(rho) = rho {
+            data.set_rho_array(rho.as_array().to_vec());
+        }
+        data
+    }
+
+    /// Build VE input data from a parsed FIT file's records
+    #[staticmethod]
+    fn from_fit_data(fit_data: FitData) -> VEData {
+        fit_data.into()
+    }
+
+    #[pyo3(name = "set_rho_array")]
+    fn py_set_rho_array(&mut self, rho: PyReadonlyArray1<'_, f64>) {
+        self.set_rho_array(rho.as_array().to_vec());
+    }
+
+    #[pyo3(name = "to_json")]
+    fn py_to_json(&self) -> PyResult<String> {
+        Ok(self.to_json()?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_json")]
+    fn py_from_json(json: &str) -> PyResult<VEData> {
+        Ok(VEData::from_json(json)?)
+    }
+}
+
+#[pymethods]
+impl VEResult {
+    #[getter(virtual_elevation)]
+    fn py_virtual_elevation<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.virtual_elevation())
+    }
+
+    #[getter(virtual_slope)]
+    fn py_virtual_slope<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.virtual_slope())
+    }
+
+    #[getter(acceleration)]
+    fn py_acceleration<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.acceleration())
+    }
+
+    #[getter(effective_wind)]
+    fn py_effective_wind<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.effective_wind())
+    }
+
+    #[getter(apparent_velocity)]
+    fn py_apparent_velocity<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        to_numpy(py, self.apparent_velocity())
+    }
+
+    #[getter(r2)]
+    fn py_r2(&self) -> f64 {
+        self.r2()
+    }
+
+    #[getter(rmse)]
+    fn py_rmse(&self) -> f64 {
+        self.rmse()
+    }
+
+    #[getter(ve_elevation_diff)]
+    fn py_ve_elevation_diff(&self) -> f64 {
+        self.ve_elevation_diff()
+    }
+
+    #[getter(actual_elevation_diff)]
+    fn py_actual_elevation_diff(&self) -> f64 {
+        self.actual_elevation_diff()
+    }
+
+    #[getter(virtual_distance_air)]
+    fn py_virtual_distance_air(&self) -> f64 {
+        self.virtual_distance_air()
+    }
+
+    #[getter(virtual_distance_ground)]
+    fn py_virtual_distance_ground(&self) -> f64 {
+        self.virtual_distance_ground()
+    }
+
+    #[getter(vd_difference_percent)]
+    fn py_vd_difference_percent(&self) -> f64 {
+        self.vd_difference_percent()
+    }
+
+    #[pyo3(name = "to_json")]
+    fn py_to_json(&self) -> PyResult<String> {
+        Ok(self.to_json()?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_json")]
+    fn py_from_json(json: &str) -> PyResult<VEResult> {
+        Ok(VEResult::from_json(json)?)
+    }
+}
+
+#[pymethods]
+impl AnalysisRecord {
+    #[new]
+    #[pyo3(signature = (data, params, cda, crr, trim_start, trim_end, air_speed_calibration = 1.0))]
+    fn py_new(
+        data: VEData,
+        params: VEParameters,
+        cda: f64,
+        crr: f64,
+        trim_start: usize,
+        trim_end: usize,
+        air_speed_calibration: f64,
+    ) -> AnalysisRecord {
+        AnalysisRecord::new(data, params, air_speed_calibration, cda, crr, trim_start, trim_end)
+    }
+
+    #[getter(schema_version)]
+    fn py_schema_version(&self) -> u32 {
+        self.schema_version()
+    }
+
+    #[getter(crate_version)]
+    fn py_crate_version(&self) -> String {
+        self.crate_version()
+    }
+
+    #[getter(data)]
+    fn py_data(&self) -> VEData {
+        self.data()
+    }
+
+    #[getter(params)]
+    fn py_params(&self) -> VEParameters {
+        self.params()
+    }
+
+    #[getter(cda)]
+    fn py_cda(&self) -> f64 {
+        self.cda()
+    }
+
+    #[getter(crr)]
+    fn py_crr(&self) -> f64 {
+        self.crr()
+    }
+
+    #[getter(trim_start)]
+    fn py_trim_start(&self) -> usize {
+        self.trim_start()
+    }
+
+    #[getter(trim_end)]
+    fn py_trim_end(&self) -> usize {
+        self.trim_end()
+    }
+
+    #[getter(metrics)]
+    fn py_metrics(&self) -> AnalysisMetrics {
+        self.metrics()
+    }
+
+    #[pyo3(name = "rerun")]
+    fn py_rerun(&self) -> VEResult {
+        self.rerun()
+    }
+
+    #[pyo3(name = "reproduces")]
+    fn py_reproduces(&self) -> bool {
+        self.reproduces()
+    }
+
+    #[pyo3(name = "to_json")]
+    fn py_to_json(&self) -> PyResult<String> {
+        Ok(self.to_json()?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_json")]
+    fn py_from_json(json: &str) -> PyResult<AnalysisRecord> {
+        Ok(AnalysisRecord::from_json(json)?)
+    }
+}
+
+#[pymethods]
+impl CdaCrrFit {
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl VirtualElevationCalculator {
+    fn default_trim_end(&self, trim_end: Option<usize>) -> usize {
+        trim_end.unwrap_or_else(|| self.record_count().saturating_sub(1))
+    }
+}
+
+#[pymethods]
+impl VirtualElevationCalculator {
+    #[new]
+    fn py_new(data: VEData, params: VEParameters) -> VirtualElevationCalculator {
+        VirtualElevationCalculator::new(data, params)
+    }
+
+    #[pyo3(name = "set_air_speed_calibration")]
+    fn py_set_air_speed_calibration(&mut self, calibration: f64) {
+        self.set_air_speed_calibration(calibration);
+    }
+
+    /// VE over records `trim_start..=trim_end` (defaults to the whole ride)
+    #[pyo3(name = "calculate_virtual_elevation", signature = (cda, crr, trim_start = 0, trim_end = None))]
+    fn py_calculate_virtual_elevation(&self, cda: f64, crr: f64, trim_start: usize, trim_end: Option<usize>) -> VEResult {
+        let trim_end = self.default_trim_end(trim_end);
+        self.calculate_virtual_elevation(cda, crr, trim_start, trim_end)
+    }
+
+    #[pyo3(
+        name = "calculate_virtual_elevation_with_cda_array",
+        signature = (cda, crr, trim_start = 0, trim_end = None)
+    )]
+    fn py_calculate_virtual_elevation_with_cda_array(
+        &self,
+        cda: PyReadonlyArray1<'_, f64>,
+        crr: f64,
+        trim_start: usize,
+        trim_end: Option<usize>,
+    ) -> VEResult {
+        let cda = cda.as_array().to_vec();
+        let trim_end = self.default_trim_end(trim_end);
+        self.calculate_virtual_elevation_with_cda_array(&cda, crr, trim_start, trim_end)
+    }
+
+    #[pyo3(name = "optimize_cda_crr", signature = (trim_start = 0, trim_end = None))]
+    fn py_optimize_cda_crr(&self, trim_start: usize, trim_end: Option<usize>) -> PyResult<CdaCrrFit> {
+        let trim_end = self.default_trim_end(trim_end);
+        Ok(self.optimize_cda_crr(trim_start, trim_end)?)
+    }
+}
+
+#[pymethods]
+impl AnalysisSession {
+    #[new]
+    fn py_new(fit_file: ParsedFitFile, params: VEParameters) -> AnalysisSession {
+        AnalysisSession::new(fit_file, params)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_ve_data")]
+    fn py_from_ve_data(data: VEData, params: VEParameters) -> AnalysisSession {
+        AnalysisSession::from_ve_data(data, params)
+    }
+
+    #[getter(laps)]
+    fn py_laps(&self) -> Vec<LapData> {
+        self.laps()
+    }
+
+    #[getter(params)]
+    fn py_params(&self) -> VEParameters {
+        self.params()
+    }
+
+    fn __len__(&self) -> usize {
+        self.record_count()
+    }
+
+    #[pyo3(name = "update_params")]
+    fn py_update_params(&mut self, params: VEParameters) {
+        self.update_params(params);
+    }
+
+    #[pyo3(name = "set_air_speed_calibration")]
+    fn py_set_air_speed_calibration(&mut self, calibration: f64) {
+        self.set_air_speed_calibration(calibration);
+    }
+
+    #[pyo3(name = "set_rho_array")]
+    fn py_set_rho_array(&mut self, rho: PyReadonlyArray1<'_, f64>) {
+        self.set_rho_array(rho.as_array().to_vec());
+    }
+
+    #[pyo3(name = "set_altitude")]
+    fn py_set_altitude(&mut self, altitude: PyReadonlyArray1<'_, f64>) {
+        self.set_altitude(altitude.as_array().to_vec());
+    }
+
+    #[pyo3(name = "evaluate", signature = (cda, crr, trim_start = 0, trim_end = None))]
+    fn py_evaluate(&self, cda: f64, crr: f64, trim_start: usize, trim_end: Option<usize>) -> VEResult {
+        let trim_end = trim_end.unwrap_or_else(|| self.record_count().saturating_sub(1));
+        self.evaluate(cda, crr, trim_start, trim_end)
+    }
+
+    #[pyo3(name = "optimize_cda_crr", signature = (trim_start = 0, trim_end = None))]
+    fn py_optimize_cda_crr(&self, trim_start: usize, trim_end: Option<usize>) -> PyResult<CdaCrrFit> {
+        let trim_end = trim_end.unwrap_or_else(|| self.record_count().saturating_sub(1));
+        Ok(self.optimize_cda_crr(trim_start, trim_end)?)
+    }
+}
+
+#[pymethods]
+impl VeProject {
+    /// Read a .veproj archive (e.g. `open(path, "rb").read()`)
+    #[staticmethod]
+    #[pyo3(name = "from_archive")]
+    fn py_from_archive(data: &[u8]) -> PyResult<VeProject> {
+        Ok(VeProject::from_archive(data)?)
+    }
+
+    #[pyo3(name = "to_archive")]
+    fn py_to_archive(&self) -> PyResult<Vec<u8>> {
+        Ok(self.to_archive()?)
+    }
+
+    #[getter(name)]
+    fn py_name(&self) -> String {
+        self.name()
+    }
+
+    #[getter(params)]
+    fn py_params(&self) -> VEParameters {
+        self.params()
+    }
+
+    #[pyo3(name = "manifest_json")]
+    fn py_manifest_json(&self) -> PyResult<String> {
+        Ok(self.manifest_json()?)
+    }
+
+    /// Session over the project's ride; pass the FIT bytes if the project doesn't embed them
+    #[pyo3(name = "open_session", signature = (fit_data = None))]
+    fn py_open_session(&self, fit_data: Option<Vec<u8>>) -> PyResult<AnalysisSession> {
+        Ok(self.open_session(fit_data)?)
+    }
+}
+
+#[pymethods]
+impl DemLimits {
+    #[new]
+    #[pyo3(signature = (max_pixels = None, max_file_bytes = None, max_decoded_bytes = None))]
+    fn py_new(max_pixels: Option<usize>, max_file_bytes: Option<usize>, max_decoded_bytes: Option<usize>) -> DemLimits {
+        let defaults = DemLimits::new();
+        DemLimits {
+            max_pixels: max_pixels.unwrap_or(defaults.max_pixels),
+            max_file_bytes: max_file_bytes.unwrap_or(defaults.max_file_bytes),
+            max_decoded_bytes: max_decoded_bytes.unwrap_or(defaults.max_decoded_bytes),
+        }
+    }
+
+    /// The first limit the estimate exceeds, or None
+    #[pyo3(name = "violation")]
+    fn py_violation(&self, estimate: &DemMemoryEstimate) -> Option<String> {
+        self.violation(estimate)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "DemLimits(max_pixels={}, max_file_bytes={}, max_decoded_bytes={})",
+            self.max_pixels, self.max_file_bytes, self.max_decoded_bytes
+        )
+    }
+}
+
+#[pymethods]
+impl DEMProcessor {
+    /// GeoTIFF bytes, plus the text of a .tfw/.jgw world file and .prj for plain TIFFs
+    #[new]
+    #[pyo3(signature = (data, filename = None, world_file = None, prj = None, limits = None))]
+    fn py_new(
+        data: &[u8],
+        filename: Option<String>,
+        world_file: Option<String>,
+        prj: Option<String>,
+        limits: Option<DemLimits>,
+    ) -> PyResult<DEMProcessor> {
+        let limits = limits.unwrap_or_default();
+        Ok(DEMProcessor::new_with_limits(data, filename, world_file, prj, &limits)?)
+    }
+
+    /// Raster size and decoding memory from the TIFF tags alone
+    #[staticmethod]
+    #[pyo3(name = "estimate_memory", signature = (data, limits = None))]
+    fn py_estimate_memory(data: &[u8], limits: Option<DemLimits>) -> PyResult<DemMemoryEstimate> {
+        Ok(DEMProcessor::estimate_memory(data, &limits.unwrap_or_default())?)
+    }
+
+    /// Elevation at each WGS84 point; NaN outside the raster or on nodata
+    #[pyo3(name = "batch_lookup")]
+    fn py_batch_lookup<'py>(
+        &mut self,
+        py: Python<'py>,
+        lats: PyReadonlyArray1<'_, f64>,
+        lons: PyReadonlyArray1<'_, f64>,
+    ) -> PyResult<Bound<'py, PyArray1<f64>>> {
+        let altitudes = self.batch_lookup(lats.as_array().to_vec(), lons.as_array().to_vec())?;
+        Ok(to_numpy(py, altitudes))
+    }
+
+    /// (min_x, min_y, max_x, max_y) in the DEM's coordinate system
+    #[getter(bounds)]
+    fn py_bounds(&self) -> (f64, f64, f64, f64) {
+        let bounds = self.get_bounds();
+        (bounds[0], bounds[1], bounds[2], bounds[3])
+    }
+
+    #[getter(metadata)]
+    fn py_metadata(&self) -> String {
+        self.get_metadata()
+    }
+}
+
+#[pymethods]
+impl AirDensityCalculator {
+    #[staticmethod]
+    #[pyo3(name = "saturation_vapor_pressure")]
+    fn py_saturation_vapor_pressure(temp_c: f64) -> f64 {
+        AirDensityCalculator::saturation_vapor_pressure(temp_c)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "calculate_dew_point")]
+    fn py_calculate_dew_point(temp_c: f64, humidity_percent: f64) -> PyResult<f64> {
+        Ok(AirDensityCalculator::calculate_dew_point(temp_c, humidity_percent)?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "calculate_air_density")]
+    fn py_calculate_air_density(temp_c: f64, pressure_hpa: f64, dew_point_c: f64) -> PyResult<f64> {
+        Ok(AirDensityCalculator::calculate_air_density(temp_c, pressure_hpa, dew_point_c)?)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "calculate_air_density_from_humidity")]
+    fn py_calculate_air_density_from_humidity(temp_c: f64, pressure_hpa: f64, humidity_percent: f64) -> PyResult<f64> {
+        Ok(AirDensityCalculator::calculate_air_density_from_humidity(temp_c, pressure_hpa, humidity_percent)?)
+    }
+
+    /// Per-sample air density from weather station series of equal length
+    #[staticmethod]
+    fn air_density_series<'py>(
+        py: Python<'py>,
+        temp_c: PyReadonlyArray1<'_, f64>,
+        pressure_hpa: PyReadonlyArray1<'_, f64>,
+        humidity_percent: PyReadonlyArray1<'_, f64>,
+    ) -> PyResult<Bound<'py, PyArray1<f64>>> {
+        let (temp_c, pressure_hpa, humidity_percent) =
+            (temp_c.as_array(), pressure_hpa.as_array(), humidity_percent.as_array());
+        if temp_c.len() != pressure_hpa.len() || temp_c.len() != humidity_percent.len() {
+            return Err(Error::InvalidInput("temperature, pressure and humidity must have the same length".to_string()).into());
+        }
+
+        let rho = temp_c
+            .iter()
+            .zip(pressure_hpa.iter())
+            .zip(humidity_percent.iter())
+            .map(|((&t, &p), &h)| AirDensityCalculator::calculate_air_density_from_humidity(t, p, h))
+            .collect::<crate::error::Result<Vec<f64>>>()?;
+        Ok(to_numpy(py, rho))
+    }
+}
+
+#[pymodule]
+fn virtual_elevation_analyzer(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(py_parse_fit_file, m)?)?;
+    m.add_function(wrap_pyfunction!(py_parse_gpx_file, m)?)?;
+    m.add_function(wrap_pyfunction!(py_parse_tcx_file, m)?)?;
+    m.add_function(wrap_pyfunction!(py_parse_activity_file, m)?)?;
+    m.add_function(wrap_pyfunction!(py_parse_csv_file, m)?)?;
+    m.add_function(wrap_pyfunction!(py_fingerprint_fit_file, m)?)?;
+    m.add_class::<ParsedFitFile>()?;
+    m.add_class::<FitData>()?;
+    m.add_class::<LapData>()?;
+    m.add_class::<ParsingStatistics>()?;
+    m.add_class::<CsvImport>()?;
+    m.add_function(wrap_pyfunction!(py_merge_sensor_csv, m)?)?;
+    m.add_function(wrap_pyfunction!(py_merge_sensor_fit, m)?)?;
+    m.add_class::<SensorMergeOptions>()?;
+    m.add_class::<SensorMerge>()?;
+    m.add_function(wrap_pyfunction!(py_inspect_fit_file, m)?)?;
+    m.add_class::<FitSecurityReport>()?;
+    m.add_class::<SecurityFinding>()?;
+    m.add_class::<FindingKind>()?;
+    m.add_class::<FindingSeverity>()?;
+    m.add_class::<FitParseMode>()?;
+    m.add_class::<FitDiagnostics>()?;
+    m.add_class::<FileId>()?;
+    m.add_class::<DeviceInfo>()?;
+    m.add_class::<SessionData>()?;
+    m.add_class::<UserProfile>()?;
+    m.add_class::<FitFingerprint>()?;
+    m.add_class::<VEParameters>()?;
+    m.add_class::<VEData>()?;
+    m.add_class::<VEResult>()?;
+    m.add_class::<ExportUnits>()?;
+    m.add_class::<ExportOptions>()?;
+    m.add_class::<VeExport>()?;
+    m.add_class::<TrackElevation>()?;
+    m.add_class::<FitRewrite>()?;
+    m.add_class::<CdaCrrFit>()?;
+    m.add_class::<VirtualElevationCalculator>()?;
+    m.add_class::<AnalysisSession>()?;
+    m.add_class::<AnalysisRecord>()?;
+    m.add_class::<AnalysisMetrics>()?;
+    m.add_class::<VeProject>()?;
+    m.add_class::<DEMProcessor>()?;
+    m.add_class::<DemLimits>()?;
+    m.add_class::<DemMemoryEstimate>()?;
+    m.add_class::<AirDensityCalculator>()?;
+    Ok(())
+}
@@ -1,43 +1,239 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
-use web_sys::console;
+use byteorder::{ByteOrder, LittleEndian};
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::fit_messages::{Message, MessageError, Messages};
+use crate::utils;
 
-#[wasm_bindgen]
+/// Largest FIT file accepted
+pub const MAX_FIT_FILE_SIZE: usize = 50_000_000;
+/// Largest GPX, TCX or CSV file accepted. The web UI already refuses uploads from 50 MB, so
+/// this only limits the CLI and Python callers. The parsers hold the whole text plus the
+/// parsed records and a vector per channel, which keeps peak memory to a few hundred MB.
+pub const MAX_TRACK_FILE_SIZE: usize = 100_000_000;
+/// Definition plus data messages; a day-long 1 Hz recording has ~100k records
+const MAX_FIT_MESSAGES: usize = 1_000_000;
+/// Regular plus developer fields in one definition message
+const MAX_FIT_FIELDS_PER_MESSAGE: usize = 255;
+
+/// How serious a [`SecurityFinding`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(eq, eq_int))]
+pub enum FindingSeverity {
+    /// Unusual but harmless
+    Warning,
+    /// Damage that recovery mode can work around (truncation, bad header CRC)
+    Recoverable,
+    /// Malformed or hostile; the file is never parsed
+    Fatal,
+}
+
+/// What a [`SecurityFinding`] is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(eq, eq_int))]
+pub enum FindingKind {
+    FileTooSmall,
+    FileTooLarge,
+    HeaderSize,
+    Signature,
+    ProtocolVersion,
+    ProfileVersion,
+    HeaderCrc,
+    DataSize,
+    MessageStructure,
+    MessageCount,
+    FieldCount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
+pub struct SecurityFinding {
+    kind: FindingKind,
+    severity: FindingSeverity,
+    message: String,
+    offset: Option<usize>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl SecurityFinding {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn kind(&self) -> FindingKind {
+        self.kind
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn severity(&self) -> FindingSeverity {
+        self.severity
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    /// Byte offset in the file the finding refers to, where there is one
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+}
+
+/// Everything [`SecurityValidator::inspect_fit_data`] found, plus the message counts
+/// from its structural scan
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
+pub struct FitSecurityReport {
+    findings: Vec<SecurityFinding>,
+    definition_messages: usize,
+    data_messages: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl FitSecurityReport {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn findings(&self) -> Vec<SecurityFinding> {
+        self.findings.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn definition_messages(&self) -> usize {
+        self.definition_messages
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn data_messages(&self) -> usize {
+        self.data_messages
+    }
+
+    /// Whether the file may be parsed; `recover` also lets recoverable damage through
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn accepts(&self, recover: bool) -> bool {
+        self.rejection(recover).is_none()
+    }
+}
+
+impl FitSecurityReport {
+    /// The most severe finding that rules out parsing
+    pub fn rejection(&self, recover: bool) -> Option<&SecurityFinding> {
+        let threshold = if recover { FindingSeverity::Fatal } else { FindingSeverity::Recoverable };
+        self.findings.iter()
+            .filter(|finding| finding.severity >= threshold)
+            .max_by_key(|finding| finding.severity)
+    }
+
+    /// Err with the rejecting finding's message; warnings go to the log
+    pub fn check(&self, recover: bool) -> Result<()> {
+        if let Some(finding) = self.rejection(recover) {
+            return Err(Error::Validation(finding.message.clone()));
+        }
+        for finding in &self.findings {
+            if finding.severity == FindingSeverity::Warning {
+                utils::warn(&finding.message);
+            }
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, kind: FindingKind, severity: FindingSeverity, offset: Option<usize>, message: String) {
+        self.findings.push(SecurityFinding { kind, severity, message, offset });
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct SecurityValidator;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl SecurityValidator {
     pub fn new() -> SecurityValidator {
         SecurityValidator
     }
 
-    pub fn validate_fit_data(&self, data: &[u8]) -> Result<(), JsValue> {
+    /// Reject anything that isn't an intact FIT file within the size and structure limits
+    pub fn validate_fit_data(&self, data: &[u8]) -> Result<()> {
+        self.inspect_fit_data(data).check(false)
+    }
+
+    /// Header checks and a structural scan of the message stream (record headers and
+    /// definitions only, no field decoding), reported as findings
+    pub fn inspect_fit_data(&self, data: &[u8]) -> FitSecurityReport {
+        use FindingKind::*;
+        use FindingSeverity::*;
+
+        let mut report = FitSecurityReport::default();
         if data.len() < 12 {
-            return Err(JsValue::from_str("Invalid FIT file: too small"));
+            report.add(FileTooSmall, Fatal, None, "Invalid FIT file: too small".to_string());
+            return report;
+        }
+
+        // File size validation (reasonable limits)
+        if data.len() > MAX_FIT_FILE_SIZE {
+            report.add(FileTooLarge, Fatal, None, "FIT file too large".to_string());
+            return report;
         }
 
-        // Check file header and size constraints
+        // Only the legacy 12-byte and the current 14-byte (with CRC) headers exist
         let header_size = data[0] as usize;
-        if header_size < 12 || header_size > data.len() {
-            return Err(JsValue::from_str("Invalid FIT file: corrupted header"));
+        if (header_size != 12 && header_size != 14) || header_size > data.len() {
+            report.add(HeaderSize, Fatal, Some(0), format!(
+                "Invalid FIT file: corrupted header (size {})", header_size
+            ));
+            return report;
         }
 
-        // Validate protocol version
+        // Check for FIT signature
+        if &data[8..12] != b".FIT" {
+            report.add(Signature, Fatal, Some(8), "Invalid FIT file: missing signature".to_string());
+            return report;
+        }
+
+        // Protocol version is major << 4 | minor; 1.x and 2.x are defined
         let protocol_version = data[1];
-        if protocol_version > 20 {
-            console::warn_1(&"Unknown FIT protocol version".into());
+        match protocol_version >> 4 {
+            1 | 2 => {}
+            0 => report.add(ProtocolVersion, Warning, Some(1), format!(
+                "FIT protocol version {}.{} is not set", protocol_version >> 4, protocol_version & 0x0F
+            )),
+            major => report.add(ProtocolVersion, Fatal, Some(1), format!(
+                "Unsupported FIT protocol version {}.{}", major, protocol_version & 0x0F
+            )),
         }
 
-        // Check for FIT signature
-        if data.len() >= 12 && &data[8..12] != b".FIT" {
-            return Err(JsValue::from_str("Invalid FIT file: missing signature"));
+        // Profile version is major * 100 + minor (major * 1000 + minor since 21.x); early SDKs wrote 0.x
+        if LittleEndian::read_u16(&data[2..4]) == 0 {
+            report.add(ProfileVersion, Warning, Some(2), "FIT profile version is not set".to_string());
         }
 
-        // File size validation (reasonable limits)
-        if data.len() > 50_000_000 {  // 50MB limit
-            return Err(JsValue::from_str("FIT file too large"));
+        if header_size == 14 {
+            let stored = LittleEndian::read_u16(&data[12..14]);
+            let computed = utils::fit_crc(0, &data[..12]);
+            // Zero means the writer didn't compute one
+            if stored != 0 && stored != computed {
+                report.add(HeaderCrc, Recoverable, Some(12), format!(
+                    "FIT header CRC mismatch: stored 0x{:04X}, computed 0x{:04X}", stored, computed
+                ));
+            }
         }
 
-        Ok(())
+        let data_size = LittleEndian::read_u32(&data[4..8]) as usize;
+        let available = data.len() - header_size;
+        if data_size == 0 {
+            report.add(DataSize, Recoverable, Some(4), "FIT header declares no data".to_string());
+        } else if data_size > available {
+            report.add(DataSize, Recoverable, Some(4), format!(
+                "FIT file truncated: header declares {} data bytes, only {} present", data_size, available
+            ));
+        } else if data_size + 2 > available {
+            report.add(DataSize, Warning, Some(header_size + data_size), "FIT file is missing its data CRC".to_string());
+        }
+
+        let data_end = if data_size == 0 { data.len() } else { (header_size + data_size).min(data.len()) };
+        self.scan_messages(&data[..data_end], header_size, &mut report);
+        report
     }
 
     pub fn sanitize_numeric_input(&self, value: f64) -> f64 {
@@ -45,6 +241,137 @@ impl SecurityValidator {
             return 0.0;
         }
         // Clamp to reasonable ranges for cycling data
-        value.max(-1000.0).min(10000.0)
+        value.clamp(-1000.0, 10000.0)
+    }
+}
+
+impl SecurityValidator {
+    /// Walk the record headers of the first FIT file in `data`, checking that every data
+    /// message has a definition and that message and field counts stay bounded. A message
+    /// cut off at the end is left to the data size check.
+    fn scan_messages(&self, data: &[u8], header_size: usize, report: &mut FitSecurityReport) {
+        use FindingKind::*;
+        use FindingSeverity::*;
+
+        let mut messages = Messages::new(&data[header_size..]);
+        loop {
+            let message_start = header_size + messages.position();
+            let Some(message) = messages.next() else { return };
+
+            if report.definition_messages + report.data_messages >= MAX_FIT_MESSAGES {
+                report.add(MessageCount, Fatal, Some(message_start), format!(
+                    "FIT file has more than {} messages", MAX_FIT_MESSAGES
+                ));
+                return;
+            }
+
+            match message {
+                Ok(Message::Definition { regular, developer, .. }) => {
+                    // Field definitions are (number, size, base type) triples
+                    let fields = (regular.len() + developer.len()) / 3;
+                    if fields > MAX_FIT_FIELDS_PER_MESSAGE {
+                        report.add(FieldCount, Fatal, Some(message_start), format!(
+                            "FIT definition with {} fields (limit {})", fields, MAX_FIT_FIELDS_PER_MESSAGE
+                        ));
+                        return;
+                    }
+                    report.definition_messages += 1;
+                }
+                Ok(Message::Data { .. }) => report.data_messages += 1,
+                Err(MessageError::UndefinedLocalType(local_type)) => {
+                    report.add(MessageStructure, Fatal, Some(message_start), format!(
+                        "FIT data message at byte {} uses local type {} before its definition",
+                        message_start, local_type
+                    ));
+                    return;
+                }
+                Err(MessageError::Truncated) => return,
+            }
+        }
+    }
+}
+
+impl Default for SecurityValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{base_type, mesg_num, FitWriter};
+
+    fn ride() -> Vec<u8> {
+        let mut writer = FitWriter::new();
+        writer.message(mesg_num::FILE_ID, &[(0, base_type::ENUM, &[4])]);
+        for t in 0..3u32 {
+            writer.message(mesg_num::RECORD, &[(253, base_type::UINT32, &t.to_le_bytes())]);
+        }
+        writer.finish(14, None)
+    }
+
+    fn kinds(report: &FitSecurityReport) -> Vec<(FindingKind, FindingSeverity)> {
+        report.findings().iter().map(|f| (f.kind(), f.severity())).collect()
+    }
+
+    #[test]
+    fn test_intact_file_has_no_findings() {
+        let report = SecurityValidator::new().inspect_fit_data(&ride());
+        assert!(report.findings().is_empty());
+        assert_eq!((report.definition_messages(), report.data_messages()), (4, 4));
+    }
+
+    #[test]
+    fn test_header_findings() {
+        let validator = SecurityValidator::new();
+
+        let mut file = ride();
+        file[0] = 13;
+        assert_eq!(kinds(&validator.inspect_fit_data(&file)), [(FindingKind::HeaderSize, FindingSeverity::Fatal)]);
+
+        let mut file = ride();
+        file[1] = 0x30;
+        assert!(!validator.inspect_fit_data(&file).accepts(true));
+
+        let mut file = ride();
+        file[12] ^= 0xFF;
+        let report = validator.inspect_fit_data(&file);
+        assert_eq!(kinds(&report), [(FindingKind::HeaderCrc, FindingSeverity::Recoverable)]);
+        assert!(!report.accepts(false) && report.accepts(true));
+        assert!(matches!(validator.validate_fit_data(&file), Err(Error::Validation(_))));
+    }
+
+    #[test]
+    fn test_data_size_against_file_length() {
+        let file = ride();
+        let report = SecurityValidator::new().inspect_fit_data(&file[..file.len() - 4]);
+        assert_eq!(kinds(&report), [(FindingKind::DataSize, FindingSeverity::Recoverable)]);
+
+        let report = SecurityValidator::new().inspect_fit_data(&file[..file.len() - 2]);
+        assert_eq!(kinds(&report), [(FindingKind::DataSize, FindingSeverity::Warning)]);
+        assert!(report.accepts(false));
+    }
+
+    #[test]
+    fn test_message_structure_limits() {
+        // Data message on a local type that was never defined
+        let mut file = ride();
+        file[14] = 0x05;
+        let report = SecurityValidator::new().inspect_fit_data(&file);
+        assert!(report.findings().iter().any(|f| f.kind() == FindingKind::MessageStructure && f.offset() == Some(14)));
+
+        // 200 regular + 100 developer fields in one definition
+        let mut writer = FitWriter::new();
+        let fields: Vec<(u8, u8, &[u8])> = (0..200).map(|n| (n as u8, base_type::ENUM, &[0u8][..])).collect();
+        writer.message(mesg_num::RECORD, &fields);
+        let mut file = writer.finish(12, None);
+        file[12] |= 0x20;
+        let developer_count = 12 + 6 + 3 * 200;
+        file.splice(developer_count..developer_count, std::iter::once(100).chain(std::iter::repeat_n(0, 300)));
+        let data_size = LittleEndian::read_u32(&file[4..8]) + 301;
+        LittleEndian::write_u32(&mut file[4..8], data_size);
+        let report = SecurityValidator::new().inspect_fit_data(&file);
+        assert!(report.findings().iter().any(|f| f.kind() == FindingKind::FieldCount));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,298 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::error::Result;
+use crate::virtual_elevation::{CdaCrrFit, PowerCorrection, VirtualElevationCalculator};
+
+/// Analysis inputs with a measurement uncertainty, shared by the sensitivity and Monte Carlo analyses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum UncertainInput {
+    SystemMass,
+    Eta,
+    Rho,
+    WindSpeed,
+    WindDirection,
+    AirSpeedCalibration,
+    PowerScale,
+}
+
+impl UncertainInput {
+    pub(crate) const ALL: [UncertainInput; 7] = [
+        UncertainInput::SystemMass,
+        UncertainInput::Eta,
+        UncertainInput::Rho,
+        UncertainInput::WindSpeed,
+        UncertainInput::WindDirection,
+        UncertainInput::AirSpeedCalibration,
+        UncertainInput::PowerScale,
+    ];
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            UncertainInput::SystemMass => "system_mass",
+            UncertainInput::Eta => "eta",
+            UncertainInput::Rho => "rho",
+            UncertainInput::WindSpeed => "wind_speed",
+            UncertainInput::WindDirection => "wind_direction",
+            UncertainInput::AirSpeedCalibration => "air_speed_calibration",
+            UncertainInput::PowerScale => "power_scale",
+        }
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            UncertainInput::SystemMass => "kg",
+            UncertainInput::Rho => "kg/m³",
+            UncertainInput::WindSpeed => "m/s",
+            UncertainInput::WindDirection => "°",
+            UncertainInput::Eta | UncertainInput::AirSpeedCalibration | UncertainInput::PowerScale => "",
+        }
+    }
+
+    pub(crate) fn base_value(&self, calculator: &VirtualElevationCalculator) -> f64 {
+        let params = calculator.params();
+        match self {
+            UncertainInput::SystemMass => params.system_mass,
+            UncertainInput::Eta => params.eta,
+            UncertainInput::Rho => params.rho,
+            UncertainInput::WindSpeed => params.wind_speed.unwrap_or(0.0),
+            UncertainInput::WindDirection => params.wind_direction.unwrap_or(0.0),
+            UncertainInput::AirSpeedCalibration => calculator.air_speed_calibration(),
+            UncertainInput::PowerScale => 1.0,
+        }
+    }
+
+    /// Typical measurement error: 1 kg, 1% for efficiencies/scales/density, 0.5 m/s, 10°
+    fn step(&self, base_value: f64) -> f64 {
+        match self {
+            UncertainInput::SystemMass => 1.0,
+            UncertainInput::Eta => 0.01,
+            UncertainInput::Rho => 0.01 * base_value,
+            UncertainInput::WindSpeed => 0.5,
+            UncertainInput::WindDirection => 10.0,
+            UncertainInput::AirSpeedCalibration | UncertainInput::PowerScale => 0.01,
+        }
+    }
+
+    /// Inputs that can't influence the fit for this data are left out of the report
+    pub(crate) fn applies_to(&self, calculator: &VirtualElevationCalculator) -> bool {
+        let params = calculator.params();
+        match self {
+            UncertainInput::WindSpeed => calculator.uses_wind_parameters(),
+            UncertainInput::WindDirection => {
+                calculator.uses_wind_parameters()
+                    && params.wind_speed.unwrap_or(0.0) != 0.0
+                    && params.wind_direction.is_some()
+            }
+            UncertainInput::AirSpeedCalibration => calculator.has_air_speed(),
+            _ => true,
+        }
+    }
+
+    /// Masses, densities, efficiencies and scale factors can't be zero or negative
+    pub(crate) fn is_strictly_positive(&self) -> bool {
+        !matches!(self, UncertainInput::WindSpeed | UncertainInput::WindDirection)
+    }
+
+    /// Set this input to `value` on the calculator (or the power correction)
+    pub(crate) fn apply(&self, calculator: &mut VirtualElevationCalculator, power: &mut PowerCorrection, value: f64) {
+        match self {
+            UncertainInput::SystemMass => calculator.params_mut().system_mass = value,
+            UncertainInput::Eta => calculator.params_mut().eta = value,
+            UncertainInput::Rho => {
+                let factor = value / calculator.params().rho;
+                calculator.params_mut().rho = value;
+                calculator.scale_rho_array(factor);
+            }
+            UncertainInput::WindSpeed => calculator.params_mut().wind_speed = Some(value),
+            UncertainInput::WindDirection => calculator.params_mut().wind_direction = Some(value.rem_euclid(360.0)),
+            UncertainInput::AirSpeedCalibration => calculator.set_air_speed_calibration(value),
+            UncertainInput::PowerScale => power.scale = value,
+        }
+    }
+}
+
+/// Effect of one input on the fitted CdA/Crr (one row of the sensitivity table / tornado chart)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct SensitivityEntry {
+    parameter: String,
+    unit: String,
+    base_value: f64,
+    step: f64,
+    cda_low: f64,
+    cda_high: f64,
+    crr_low: f64,
+    crr_high: f64,
+    cda_derivative: f64,
+    crr_derivative: f64,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl SensitivityEntry {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn parameter(&self) -> String { self.parameter.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn unit(&self) -> String { self.unit.clone() }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn base_value(&self) -> f64 { self.base_value }
+
+    /// Perturbation applied either side of the base value
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn step(&self) -> f64 { self.step }
+
+    /// Fitted CdA at base_value - step
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda_low(&self) -> f64 { self.cda_low }
+
+    /// Fitted CdA at base_value + step
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda_high(&self) -> f64 { self.cda_high }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr_low(&self) -> f64 { self.crr_low }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr_high(&self) -> f64 { self.crr_high }
+
+    /// ∂CdA/∂parameter (central difference, per unit of the parameter)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda_derivative(&self) -> f64 { self.cda_derivative }
+
+    /// ∂Crr/∂parameter (central difference, per unit of the parameter)
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr_derivative(&self) -> f64 { self.crr_derivative }
+
+    /// Width of the tornado bar: |cda_high - cda_low|
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda_swing(&self) -> f64 { (self.cda_high - self.cda_low).abs() }
+}
+
+/// One-at-a-time sensitivity of the fitted CdA/Crr to the analysis inputs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct SensitivityReport {
+    base_cda: f64,
+    base_crr: f64,
+    base_rmse: f64,
+    entries: Vec<SensitivityEntry>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl SensitivityReport {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn base_cda(&self) -> f64 { self.base_cda }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn base_crr(&self) -> f64 { self.base_crr }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn base_rmse(&self) -> f64 { self.base_rmse }
+
+    /// Rows sorted by descending CdA swing, ready to draw as a tornado chart
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn entries(&self) -> Vec<SensitivityEntry> { self.entries.clone() }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl VirtualElevationCalculator {
+    /// Perturb each input by a typical measurement error, re-fit CdA/Crr over the
+    /// trim region and report the partial derivatives and tornado-chart swings
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn sensitivity_analysis(&self, trim_start: usize, trim_end: usize) -> Result<SensitivityReport> {
+        self.analyze_sensitivity(&[(trim_start, trim_end)])
+    }
+}
+
+impl VirtualElevationCalculator {
+    /// Sensitivity analysis over one or more (start, end) trim regions
+    pub fn analyze_sensitivity(&self, segments: &[(usize, usize)]) -> Result<SensitivityReport> {
+        let base = self.fit_cda_crr(segments, PowerCorrection::NONE)?;
+
+        let mut entries = Vec::new();
+        for input in UncertainInput::ALL {
+            if !input.applies_to(self) {
+                continue;
+            }
+
+            let base_value = input.base_value(self);
+            let step = input.step(base_value);
+            let fit_at = |value: f64| -> Result<CdaCrrFit> {
+                let mut perturbed = self.clone();
+                let mut power = PowerCorrection::NONE;
+                input.apply(&mut perturbed, &mut power, value);
+                perturbed.fit_cda_crr(segments, power)
+            };
+            let low = fit_at(base_value - step)?;
+            let high = fit_at(base_value + step)?;
+
+            entries.push(SensitivityEntry {
+                parameter: input.name().to_string(),
+                unit: input.unit().to_string(),
+                base_value,
+                step,
+                cda_low: low.cda(),
+                cda_high: high.cda(),
+                crr_low: low.crr(),
+                crr_high: high.crr(),
+                cda_derivative: (high.cda() - low.cda()) / (2.0 * step),
+                crr_derivative: (high.crr() - low.crr()) / (2.0 * step),
+            });
+        }
+
+        entries.sort_by(|a, b| b.cda_swing().total_cmp(&a.cda_swing()));
+
+        Ok(SensitivityReport {
+            base_cda: base.cda(),
+            base_crr: base.crr(),
+            base_rmse: base.rmse(),
+            entries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{rolling_course, CDA, RHO};
+
+    fn entry<'a>(report: &'a SensitivityReport, name: &str) -> &'a SensitivityEntry {
+        report.entries.iter().find(|e| e.parameter == name).unwrap()
+    }
+
+    #[test]
+    fn test_rho_trades_off_against_cda() {
+        let (calculator, segments) = rolling_course(&[7.0, 11.0], 1.0, 0.0);
+        let report = calculator.analyze_sensitivity(&segments).unwrap();
+
+        assert!((report.base_cda - CDA).abs() < 0.002);
+        // CdA·rho is what VE actually measures, so ∂CdA/∂rho ≈ -CdA/rho
+        let rho = entry(&report, "rho");
+        assert!((rho.cda_derivative + CDA / RHO).abs() < 0.02, "dCdA/drho {}", rho.cda_derivative);
+    }
+
+    #[test]
+    fn test_power_scale_matches_eta() {
+        let (calculator, segments) = rolling_course(&[7.0, 11.0], 1.0, 0.0);
+        let report = calculator.analyze_sensitivity(&segments).unwrap();
+
+        // eta = 1.0 here, so a 1% eta change and a 1% power scale change are the same thing
+        let eta = entry(&report, "eta");
+        let power = entry(&report, "power_scale");
+        assert!(power.cda_derivative > 0.0);
+        assert!((eta.cda_derivative - power.cda_derivative).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_entries_sorted_and_filtered() {
+        let (calculator, segments) = rolling_course(&[7.0, 11.0], 1.0, 0.0);
+        let report = calculator.analyze_sensitivity(&segments).unwrap();
+
+        // No air speed channel and no wind direction set
+        assert!(report.entries.iter().all(|e| e.parameter != "air_speed_calibration"));
+        assert!(report.entries.iter().all(|e| e.parameter != "wind_direction"));
+        assert!(report.entries.windows(2).all(|w| w[0].cda_swing() >= w[1].cda_swing()));
+    }
+}
@@ -0,0 +1,585 @@
+//! Merging an external sensor log (an aero sensor's CSV, or a second FIT file) onto
+//! the head unit's FIT timeline. The log's clock is aligned by timestamp, optionally
+//! refined by cross-correlating the two speed channels, then each channel is
+//! resampled onto the FIT records so it can feed [`VEData`].
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::air_density::AirDensityCalculator;
+use crate::csv_import::CsvImport;
+use crate::error::{Error, Result};
+use crate::fit_parser::{FitData, ParsedFitFile};
+use crate::utils::{self, RELATIVE_CLOCK_LIMIT};
+use crate::virtual_elevation::VEData;
+
+/// Fewest overlapping 1 Hz speed samples a candidate lag is scored on
+const MIN_OVERLAP_SECONDS: usize = 30;
+/// Longest ride or log the speed alignment grids cover: eleven days at 1 Hz, and a bound on
+/// what one badly stamped record (e.g. at the FIT epoch before GPS time) can make it allocate
+const MAX_ALIGN_SECONDS: f64 = 1_000_000.0;
+
+/// How a sensor log is lined up with the FIT records
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all, set_all))]
+pub struct SensorMergeOptions {
+    /// Seconds added to the log's clock. A log whose timer starts near zero is first
+    /// moved to the start of the ride, so this is then the log's delay after it
+    pub offset_seconds: f64,
+    /// Refine the offset by cross-correlating the log's speed with the FIT speed
+    pub align_by_speed: bool,
+    /// Largest correction the speed alignment may make, seconds
+    pub max_lag_seconds: f64,
+    /// FIT records further than this from every log sample get no merged values, seconds
+    pub max_gap_seconds: f64,
+    /// Relative humidity (%) for air density when the log has none
+    pub humidity_percent: f64,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl SensorMergeOptions {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> SensorMergeOptions {
+        SensorMergeOptions {
+            offset_seconds: 0.0,
+            align_by_speed: true,
+            max_lag_seconds: 300.0,
+            max_gap_seconds: 5.0,
+            humidity_percent: 50.0,
+        }
+    }
+}
+
+impl Default for SensorMergeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An external time series. Channels the logger doesn't record are left empty;
+/// the others hold one value per timestamp, NaN where a sample is missing
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SensorLog {
+    /// Seconds, ascending; Unix time or the logger's own timer
+    pub timestamps: Vec<f64>,
+    /// Ground speed for alignment, m/s
+    pub speed: Vec<f64>,
+    /// Apparent wind speed, m/s
+    pub air_speed: Vec<f64>,
+    /// Yaw of the apparent wind, degrees from straight ahead
+    pub wind_angle: Vec<f64>,
+    /// °C
+    pub temperature: Vec<f64>,
+    /// hPa
+    pub pressure: Vec<f64>,
+    /// % RH
+    pub humidity: Vec<f64>,
+}
+
+impl SensorLog {
+    pub fn from_csv(import: &CsvImport) -> SensorLog {
+        let data = import.fit_data();
+        let has_air_speed = !import.wind_angle().is_empty() || data.air_speed_slice().iter().any(|&v| v != 0.0);
+        SensorLog {
+            timestamps: data.timestamps(),
+            speed: data.velocity(),
+            air_speed: if has_air_speed { data.air_speed() } else { Vec::new() },
+            wind_angle: import.wind_angle(),
+            temperature: import.temperature(),
+            pressure: import.pressure(),
+            humidity: import.humidity(),
+        }
+    }
+
+    /// A second FIT recording. Yaw comes back from the wind_speed/air_speed ratio
+    /// (unsigned, which is all the headwind component needs); FIT has no pressure
+    pub fn from_fit(parsed: &ParsedFitFile) -> SensorLog {
+        let data = parsed.fit_data();
+        let recorded = |values: &[f64]| values.iter().any(|&v| v != 0.0);
+        let (air_speed, wind_speed) = (data.air_speed_slice(), data.wind_speed_slice());
+
+        let wind_angle = if recorded(air_speed) && recorded(wind_speed) {
+            air_speed.iter().zip(wind_speed).map(|(&air, &wind)| {
+                if air > 0.0 { (wind / air).clamp(-1.0, 1.0).acos().to_degrees() } else { f64::NAN }
+            }).collect()
+        } else {
+            Vec::new()
+        };
+
+        SensorLog {
+            timestamps: data.timestamps(),
+            speed: data.velocity(),
+            air_speed: if recorded(air_speed) { air_speed.to_vec() } else { Vec::new() },
+            wind_angle,
+            temperature: if recorded(data.temperature_slice()) { data.temperature() } else { Vec::new() },
+            pressure: Vec::new(),
+            humidity: Vec::new(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.timestamps.len() < 2 {
+            return Err(Error::InsufficientData("Sensor log needs at least two samples".to_string()));
+        }
+        if !self.timestamps.windows(2).all(|pair| pair[1] > pair[0]) {
+            return Err(Error::InvalidInput("Sensor log timestamps must be increasing".to_string()));
+        }
+        let channels = [
+            ("speed", &self.speed),
+            ("air_speed", &self.air_speed),
+            ("wind_angle", &self.wind_angle),
+            ("temperature", &self.temperature),
+            ("pressure", &self.pressure),
+            ("humidity", &self.humidity),
+        ];
+        for (name, values) in channels {
+            if !values.is_empty() && values.len() != self.timestamps.len() {
+                return Err(Error::InvalidInput(format!(
+                    "Sensor log {} has {} values for {} timestamps",
+                    name,
+                    values.len(),
+                    self.timestamps.len()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The log resampled onto the FIT records, and the FitData with its channels merged in
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
+pub struct SensorMerge {
+    fit_data: FitData,
+    offset_seconds: f64,
+    correlation: Option<f64>,
+    matched_records: usize,
+    air_speed: Vec<f64>,
+    wind_angle: Vec<f64>,
+    temperature: Vec<f64>,
+    pressure: Vec<f64>,
+    humidity: Vec<f64>,
+    rho: Vec<f64>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl SensorMerge {
+    /// The FIT records with the log's air speed, yaw and temperature merged in
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn fit_data(&self) -> FitData {
+        self.fit_data.clone()
+    }
+
+    /// Merged records plus per-record air density when the log has temperature and pressure
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn ve_data(&self) -> VEData {
+        let mut data = VEData::from(self.fit_data.clone());
+        if !self.rho.is_empty() {
+            data.set_rho_array(self.rho.clone());
+        }
+        data
+    }
+
+    /// Seconds added to the log's timestamps to land on the FIT clock
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn offset_seconds(&self) -> f64 {
+        self.offset_seconds
+    }
+
+    /// Pearson correlation of the two speed channels at the chosen offset;
+    /// None when aligned by timestamp only
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn correlation(&self) -> Option<f64> {
+        self.correlation
+    }
+
+    /// FIT records within `max_gap_seconds` of a log sample
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn matched_records(&self) -> usize {
+        self.matched_records
+    }
+
+    /// Log air speed per FIT record, m/s; NaN where unmatched, empty if the log has none
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn air_speed(&self) -> Vec<f64> {
+        self.air_speed.clone()
+    }
+
+    /// Yaw per FIT record, degrees
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn wind_angle(&self) -> Vec<f64> {
+        self.wind_angle.clone()
+    }
+
+    /// °C
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn temperature(&self) -> Vec<f64> {
+        self.temperature.clone()
+    }
+
+    /// hPa
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn pressure(&self) -> Vec<f64> {
+        self.pressure.clone()
+    }
+
+    /// % RH
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn humidity(&self) -> Vec<f64> {
+        self.humidity.clone()
+    }
+
+    /// Air density per FIT record, kg/m³; gaps hold the mean. Empty without temperature and pressure
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn rho(&self) -> Vec<f64> {
+        self.rho.clone()
+    }
+}
+
+/// Merge a CSV sensor log (see [`crate::parse_csv_file`]) onto the FIT records
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn merge_sensor_csv(fit_data: &FitData, log: &CsvImport, options: &SensorMergeOptions) -> Result<SensorMerge> {
+    merge_sensor_log(fit_data, &SensorLog::from_csv(log), options)
+}
+
+/// Merge a second FIT recording (e.g. from the aero sensor itself) onto the FIT records
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn merge_sensor_fit(fit_data: &FitData, log: &ParsedFitFile, options: &SensorMergeOptions) -> Result<SensorMerge> {
+    merge_sensor_log(fit_data, &SensorLog::from_fit(log), options)
+}
+
+/// Align `log` to the FIT clock and resample it onto `fit_data`'s records
+pub fn merge_sensor_log(fit_data: &FitData, log: &SensorLog, options: &SensorMergeOptions) -> Result<SensorMerge> {
+    log.validate()?;
+    let times = fit_data.timestamps_slice();
+    if times.is_empty() {
+        return Err(Error::InsufficientData("No FIT records to merge onto".to_string()));
+    }
+    if !options.offset_seconds.is_finite() {
+        return Err(Error::InvalidInput(format!("Merge offset must be finite, got {}", options.offset_seconds)));
+    }
+    let limits = [options.max_lag_seconds, options.max_gap_seconds];
+    if !limits.iter().all(|limit| limit.is_finite() && *limit >= 0.0) {
+        return Err(Error::InvalidInput("Merge lag and gap limits must be finite and not negative".to_string()));
+    }
+
+    // A logger timer is anchored to the ride start before the manual offset applies
+    let mut offset = options.offset_seconds;
+    if log.timestamps[0] < RELATIVE_CLOCK_LIMIT && times[0] >= RELATIVE_CLOCK_LIMIT {
+        offset += times[0] - log.timestamps[0];
+    }
+
+    let mut correlation = None;
+    if options.align_by_speed {
+        if log.speed.is_empty() {
+            return Err(Error::InsufficientData("Sensor log has no speed channel to align on".to_string()));
+        }
+        let (lag, r) = speed_lag(fit_data, log, offset, options.max_lag_seconds)?;
+        offset += lag;
+        correlation = Some(r);
+    }
+
+    let shifted: Vec<f64> = log.timestamps.iter().map(|t| t + offset).collect();
+    let matched: Vec<bool> = times.iter().map(|&t| nearest_gap(&shifted, t) <= options.max_gap_seconds).collect();
+    let matched_records = matched.iter().filter(|&&m| m).count();
+    if matched_records == 0 {
+        return Err(Error::InsufficientData(format!(
+            "Sensor log does not overlap the ride after shifting it by {:.1} s",
+            offset
+        )));
+    }
+
+    let resample = |values: &Vec<f64>| -> Vec<f64> {
+        if values.is_empty() {
+            return Vec::new();
+        }
+        utils::interpolate(&shifted, values, times).into_iter().zip(&matched)
+            .map(|(value, &matched)| if matched { value } else { f64::NAN })
+            .collect()
+    };
+    let air_speed = resample(&log.air_speed);
+    let temperature = resample(&log.temperature);
+    let pressure = resample(&log.pressure);
+    let humidity = resample(&log.humidity);
+    // Blend angles through their sine and cosine so ±180° doesn't average to 0
+    let wind_angle = if log.wind_angle.is_empty() {
+        Vec::new()
+    } else {
+        let sin = resample(&log.wind_angle.iter().map(|a| a.to_radians().sin()).collect());
+        let cos = resample(&log.wind_angle.iter().map(|a| a.to_radians().cos()).collect());
+        sin.iter().zip(&cos).map(|(s, c)| s.atan2(*c).to_degrees()).collect()
+    };
+
+    let mut merged = fit_data.clone();
+    if !air_speed.is_empty() {
+        let velocity = fit_data.velocity_slice();
+        let (fit_air, fit_wind) = (fit_data.air_speed_slice(), fit_data.wind_speed_slice());
+        // Outside the log, keep the FIT's own air speed, else ground speed plus its wind
+        let merged_air: Vec<f64> = (0..times.len()).map(|i| {
+            if air_speed[i].is_finite() {
+                return air_speed[i];
+            }
+            match fit_air.get(i) {
+                Some(&air) if air.is_finite() && air != 0.0 => air,
+                _ => velocity.get(i).copied().unwrap_or(0.0)
+                    + fit_wind.get(i).copied().filter(|w| w.is_finite()).unwrap_or(0.0),
+            }
+        }).collect();
+        let merged_wind: Vec<f64> = (0..times.len()).map(|i| {
+            let fit_wind = fit_wind.get(i).copied().unwrap_or(0.0);
+            match wind_angle.get(i) {
+                Some(angle) if air_speed[i].is_finite() && angle.is_finite() => angle.to_radians().cos() * air_speed[i],
+                _ => fit_wind,
+            }
+        }).collect();
+        merged.set_air_data(merged_air, merged_wind);
+    }
+    if !temperature.is_empty() {
+        let fit_temperature = fit_data.temperature_slice();
+        merged.set_temperature((0..times.len()).map(|i| {
+            if temperature[i].is_finite() { temperature[i] } else { fit_temperature.get(i).copied().unwrap_or(0.0) }
+        }).collect());
+    }
+
+    let rho = air_density(&temperature, &pressure, &humidity, options.humidity_percent);
+
+    Ok(SensorMerge {
+        fit_data: merged,
+        offset_seconds: offset,
+        correlation,
+        matched_records,
+        air_speed,
+        wind_angle,
+        temperature,
+        pressure,
+        humidity,
+        rho,
+    })
+}
+
+/// Distance from `t` to the closest of the ascending `times`
+fn nearest_gap(times: &[f64], t: f64) -> f64 {
+    let index = times.partition_point(|&time| time < t);
+    let after = times.get(index).map_or(f64::INFINITY, |&time| time - t);
+    let before = index.checked_sub(1).map_or(f64::INFINITY, |i| t - times[i]);
+    after.min(before)
+}
+
+/// Lag (seconds, added to `offset`) maximising the correlation of the two speed
+/// channels on a 1 Hz grid, refined to sub-second by a parabola through the peak
+fn speed_lag(fit_data: &FitData, log: &SensorLog, offset: f64, max_lag: f64) -> Result<(f64, f64)> {
+    let times = fit_data.timestamps_slice();
+    let start = times[0];
+    let (log_start, log_end) = (log.timestamps[0], log.timestamps[log.timestamps.len() - 1]);
+    for (what, span) in [("Ride", times[times.len() - 1] - start), ("Sensor log", log_end - log_start)] {
+        if !(0.0..=MAX_ALIGN_SECONDS).contains(&span) {
+            return Err(Error::InvalidInput(format!(
+                "{} spans {:.0} s, more than the {:.0} s speed alignment covers; check for out-of-place timestamps",
+                what, span, MAX_ALIGN_SECONDS
+            )));
+        }
+    }
+    let count = (times[times.len() - 1] - start).floor() as usize + 1;
+    let grid: Vec<f64> = (0..count).map(|k| start + k as f64).collect();
+    let fit_speed = utils::interpolate(times, fit_data.velocity_slice(), &grid);
+
+    // Log speed at log time (start - offset - max_lag + j), NaN outside the log. Lags beyond
+    // the two recordings' combined span cannot overlap them, so the search stops there
+    let max_lag = max_lag.min(count as f64 + (log_end - log_start).ceil()).floor() as usize;
+    let log_grid: Vec<f64> = (0..count + 2 * max_lag)
+        .map(|j| start - offset - max_lag as f64 + j as f64)
+        .collect();
+    let log_speed: Vec<f64> = utils::interpolate(&log.timestamps, &log.speed, &log_grid).into_iter()
+        .zip(&log_grid)
+        .map(|(speed, &t)| if t >= log_start && t <= log_end { speed } else { f64::NAN })
+        .collect();
+
+    // Lag L pairs FIT sample k with log sample k - L + max_lag
+    let scores: Vec<Option<f64>> = (0..=2 * max_lag).map(|shift| {
+        let pairs = fit_speed.iter().zip(&log_speed[2 * max_lag - shift..])
+            .filter(|(a, b)| a.is_finite() && b.is_finite())
+            .map(|(&a, &b)| (a, b));
+        pearson(pairs)
+    }).collect();
+
+    let (best, r) = scores.iter().enumerate()
+        .filter_map(|(shift, score)| score.map(|r| (shift, r)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .ok_or_else(|| Error::InsufficientData(format!(
+            "Sensor log and ride share less than {} s of speed data; set offset_seconds closer",
+            MIN_OVERLAP_SECONDS
+        )))?;
+
+    let neighbour = |shift: Option<usize>| shift.and_then(|s| scores.get(s).copied().flatten());
+    let refinement = match (neighbour(best.checked_sub(1)), neighbour(Some(best + 1))) {
+        (Some(before), Some(after)) if before - 2.0 * r + after < 0.0 => {
+            0.5 * (before - after) / (before - 2.0 * r + after)
+        }
+        _ => 0.0,
+    };
+    Ok((best as f64 - max_lag as f64 + refinement, r))
+}
+
+/// Pearson correlation, None below the minimum overlap or for a constant series
+fn pearson(pairs: impl Iterator<Item = (f64, f64)>) -> Option<f64> {
+    let (mut n, mut sx, mut sy, mut sxx, mut syy, mut sxy) = (0usize, 0.0, 0.0, 0.0, 0.0, 0.0);
+    for (x, y) in pairs {
+        n += 1;
+        sx += x;
+        sy += y;
+        sxx += x * x;
+        syy += y * y;
+        sxy += x * y;
+    }
+    if n < MIN_OVERLAP_SECONDS {
+        return None;
+    }
+    let n = n as f64;
+    let covariance = sxy - sx * sy / n;
+    let variance = (sxx - sx * sx / n) * (syy - sy * sy / n);
+    (variance > 0.0).then(|| covariance / variance.sqrt())
+}
+
+/// Per-record density from the merged weather channels; invalid records take the mean
+fn air_density(temperature: &[f64], pressure: &[f64], humidity: &[f64], default_humidity: f64) -> Vec<f64> {
+    if temperature.is_empty() || pressure.is_empty() {
+        return Vec::new();
+    }
+    let rho: Vec<Option<f64>> = temperature.iter().zip(pressure).enumerate().map(|(i, (&t, &p))| {
+        let h = humidity.get(i).copied().filter(|h| h.is_finite()).unwrap_or(default_humidity);
+        if !(t.is_finite() && p.is_finite()) {
+            return None;
+        }
+        AirDensityCalculator::calculate_air_density_from_humidity(t, p, h).ok()
+    }).collect();
+
+    let valid: Vec<f64> = rho.iter().flatten().copied().collect();
+    if valid.is_empty() {
+        return Vec::new();
+    }
+    let mean = valid.iter().sum::<f64>() / valid.len() as f64;
+    rho.into_iter().map(|r| r.unwrap_or(mean)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::RideBuilder;
+
+    const RIDE_START: f64 = 1_700_000_000.0;
+
+    fn speed_at(t: f64) -> f64 {
+        10.0 + 2.0 * (t / 17.0).sin() + 1.5 * (t / 5.3 + 1.0).sin()
+    }
+
+    /// 1 Hz head-unit recording with Unix timestamps
+    fn ride(seconds: usize) -> FitData {
+        RideBuilder::new(RIDE_START, seconds).speed(|k| speed_at(k as f64)).power(|_| 250.0).build()
+    }
+
+    /// 2 Hz aero log whose timer reads `delay` at the ride start
+    fn log(seconds: usize, delay: f64) -> SensorLog {
+        let timestamps: Vec<f64> = (0..2 * seconds).map(|i| i as f64 * 0.5).collect();
+        let ride_time: Vec<f64> = timestamps.iter().map(|t| t - delay).collect();
+        SensorLog {
+            speed: ride_time.iter().map(|&t| speed_at(t)).collect(),
+            air_speed: ride_time.iter().map(|&t| speed_at(t) + 2.0).collect(),
+            wind_angle: vec![60.0; timestamps.len()],
+            temperature: vec![20.0; timestamps.len()],
+            pressure: vec![1013.25; timestamps.len()],
+            humidity: Vec::new(),
+            timestamps,
+        }
+    }
+
+    #[test]
+    fn test_speed_correlation_recovers_clock_offset() {
+        let fit = ride(600);
+        let merge = merge_sensor_log(&fit, &log(650, 12.4), &SensorMergeOptions::new()).unwrap();
+
+        assert!((merge.offset_seconds() - (RIDE_START - 12.4)).abs() < 0.1, "{}", merge.offset_seconds() - RIDE_START);
+        assert!(merge.correlation().unwrap() > 0.99);
+        // The log starts 12.4 s early and runs past the end, so every record matches
+        assert_eq!(merge.matched_records(), 600);
+
+        let merged = merge.fit_data();
+        for k in [0, 100, 599] {
+            assert!((merged.air_speed()[k] - (speed_at(k as f64) + 2.0)).abs() < 0.05);
+            assert!((merged.wind_speed()[k] - 0.5 * merged.air_speed()[k]).abs() < 1e-9);
+        }
+        assert_eq!(merged.temperature()[300], 20.0);
+    }
+
+    #[test]
+    fn test_timestamp_alignment_masks_records_outside_the_log() {
+        let fit = ride(120);
+        // Started 10 s into the ride and stopped after 60 s
+        let options = SensorMergeOptions { offset_seconds: 10.0, align_by_speed: false, ..SensorMergeOptions::new() };
+        let merge = merge_sensor_log(&fit, &log(60, 0.0), &options).unwrap();
+
+        assert_eq!(merge.offset_seconds(), RIDE_START + 10.0);
+        assert_eq!(merge.correlation(), None);
+        // Records 5..=74 are within max_gap (5 s) of a sample at 10..=69.5
+        assert_eq!(merge.matched_records(), 70);
+        assert!(merge.air_speed()[0].is_nan());
+        assert!(merge.air_speed()[40].is_finite());
+
+        // Unmatched records fall back to ground speed; the FIT had no air speed
+        let merged = merge.fit_data();
+        assert_eq!(merged.air_speed()[0], fit.velocity()[0]);
+        assert_eq!(merged.wind_speed()[0], 0.0);
+    }
+
+    #[test]
+    fn test_weather_channels_feed_air_density() {
+        let fit = ride(120);
+        let mut sensor = log(60, 0.0);
+        sensor.temperature[10] = f64::NAN;
+        let options = SensorMergeOptions { align_by_speed: false, ..SensorMergeOptions::new() };
+        let merge = merge_sensor_log(&fit, &sensor, &options).unwrap();
+
+        let expected = AirDensityCalculator::calculate_air_density_from_humidity(20.0, 1013.25, 50.0).unwrap();
+        let rho = merge.rho();
+        assert_eq!(rho.len(), 120);
+        // Unmatched records and the blank temperature take the mean, here the same value
+        assert!(rho.iter().all(|r| (r - expected).abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_rejects_unbounded_limits() {
+        let fit = ride(60);
+        for options in [
+            SensorMergeOptions { max_lag_seconds: f64::INFINITY, ..SensorMergeOptions::new() },
+            SensorMergeOptions { max_gap_seconds: f64::NAN, ..SensorMergeOptions::new() },
+            SensorMergeOptions { offset_seconds: f64::NEG_INFINITY, ..SensorMergeOptions::new() },
+        ] {
+            assert!(matches!(merge_sensor_log(&fit, &log(60, 0.0), &options), Err(Error::InvalidInput(_))));
+        }
+
+        // A huge but finite lag is cut down to the recordings' span
+        let options = SensorMergeOptions { max_lag_seconds: 1e15, ..SensorMergeOptions::new() };
+        let merge = merge_sensor_log(&fit, &log(60, 3.0), &options).unwrap();
+        assert!((merge.offset_seconds() - (RIDE_START - 3.0)).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_rejects_out_of_place_first_record() {
+        // Stamped at the FIT epoch before the head unit had GPS time
+        let fit = RideBuilder::new(RIDE_START, 60)
+            .timestamps(|k| if k == 0 { 631_065_600.0 } else { RIDE_START + k as f64 })
+            .speed(|k| speed_at(k as f64))
+            .build();
+        let error = merge_sensor_log(&fit, &log(60, 0.0), &SensorMergeOptions::new()).unwrap_err();
+        assert!(matches!(error, Error::InvalidInput(_)));
+        assert!(error.to_string().contains("Ride spans"));
+    }
+
+    #[test]
+    fn test_rejects_mismatched_channels() {
+        let mut sensor = log(60, 0.0);
+        sensor.pressure.pop();
+        let error = merge_sensor_log(&ride(60), &sensor, &SensorMergeOptions::new()).unwrap_err();
+        assert!(error.to_string().contains("pressure"));
+    }
+}
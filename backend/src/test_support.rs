@@ -0,0 +1,214 @@
+//! Synthetic rides with known CdA/Crr for the fitting and analysis tests
+
+use crate::fit_parser::{FitData, FitDiagnostics, ParsedFitFile};
+use crate::fitparser_wrapper::{FitMessages, FitRecord};
+use crate::utils::fit_crc;
+use crate::virtual_elevation::{VEData, VEParameters, VirtualElevationCalculator};
+
+pub const MASS: f64 = 80.0;
+pub const CDA: f64 = 0.25;
+pub const CRR: f64 = 0.005;
+pub const RHO: f64 = 1.2;
+pub const SAMPLES_PER_RUN: usize = 400;
+
+/// Repeated rolling loop, one run per speed, gentle enough that power never goes negative.
+/// Power is solved from the VE equation so the true power reproduces the altitude
+/// exactly, then distorted by a meter error (measured = (true - offset) / scale).
+/// Returns the calculator and the trim region of each run.
+pub fn rolling_course(speeds: &[f64], meter_scale: f64, meter_offset: f64) -> (VirtualElevationCalculator, Vec<(usize, usize)>) {
+    let g = 9.807;
+    let mut velocity = Vec::new();
+    for &speed in speeds {
+        for i in 0..SAMPLES_PER_RUN {
+            velocity.push(speed + 0.3 * (i as f64 * 0.05).sin());
+        }
+    }
+
+    let n = velocity.len();
+    let mut distance = vec![0.0; n];
+    for i in 1..n {
+        distance[i] = distance[i - 1] + velocity[i];
+    }
+    let altitude: Vec<f64> = distance.iter()
+        .map(|d| 100.0 + (d / 1000.0 * std::f64::consts::TAU).sin())
+        .collect();
+
+    let mut power = vec![0.0; n];
+    for i in 1..n {
+        let v = velocity[i];
+        let a = (v.powi(2) - velocity[i - 1].powi(2)) / (2.0 * v);
+        let slope = ((altitude[i] - altitude[i - 1]) / v).asin().tan();
+        let true_power = v * MASS * g * (slope + CDA * RHO * v * v / (2.0 * MASS * g) + CRR + a / g);
+        power[i] = (true_power - meter_offset) / meter_scale;
+    }
+
+    let data = VEData::new(
+        (0..n).map(|i| i as f64).collect(),
+        power,
+        velocity,
+        vec![],
+        vec![],
+        altitude,
+        distance,
+        vec![],
+        vec![],
+    );
+    let mut params = VEParameters::new();
+    params.system_mass = MASS;
+    params.rho = RHO;
+    params.eta = 1.0;
+
+    let segments = (0..speeds.len())
+        .map(|k| (k * SAMPLES_PER_RUN + 1, (k + 1) * SAMPLES_PER_RUN - 1))
+        .collect();
+
+    (VirtualElevationCalculator::new(data, params), segments)
+}
+
+/// 1 Hz [`FitData`] from per-record channel functions, for tests that need a ride
+/// rather than a known CdA/Crr. Channels that are never set stay blank.
+pub struct RideBuilder {
+    records: Vec<FitRecord>,
+}
+
+impl RideBuilder {
+    /// `seconds` records starting at `start` (Unix seconds)
+    pub fn new(start: f64, seconds: usize) -> Self {
+        let records = (0..seconds).map(|k| FitRecord {
+            timestamp: start + k as f64,
+            distance: None,
+            position_lat: None,
+            position_long: None,
+            altitude: None,
+            speed: None,
+            power: None,
+            heart_rate: None,
+            cadence: None,
+            grade: None,
+            temperature: None,
+            gps_accuracy: None,
+            calories: None,
+            air_speed: None,
+            wind_speed: None,
+            battery_soc: None,
+        }).collect();
+        RideBuilder { records }
+    }
+
+    /// Replace the 1 Hz timestamp of record k
+    pub fn timestamps(mut self, timestamp: impl Fn(usize) -> f64) -> Self {
+        for (k, record) in self.records.iter_mut().enumerate() {
+            record.timestamp = timestamp(k);
+        }
+        self
+    }
+
+    /// Speed (m/s) of record k; distance accumulates it
+    pub fn speed(mut self, speed: impl Fn(usize) -> f64) -> Self {
+        let mut distance = 0.0;
+        for (k, record) in self.records.iter_mut().enumerate() {
+            let v = speed(k);
+            if k > 0 {
+                distance += v;
+            }
+            record.speed = Some(v);
+            record.distance = Some(distance);
+        }
+        self
+    }
+
+    pub fn power(mut self, power: impl Fn(usize) -> f64) -> Self {
+        for (k, record) in self.records.iter_mut().enumerate() {
+            record.power = Some(power(k));
+        }
+        self
+    }
+
+    pub fn altitude(mut self, altitude: impl Fn(usize) -> f64) -> Self {
+        for (k, record) in self.records.iter_mut().enumerate() {
+            record.altitude = Some(altitude(k));
+        }
+        self
+    }
+
+    /// Latitude and longitude (degrees) of record k
+    pub fn position(mut self, position: impl Fn(usize) -> (f64, f64)) -> Self {
+        for (k, record) in self.records.iter_mut().enumerate() {
+            let (lat, lon) = position(k);
+            record.position_lat = Some(lat);
+            record.position_long = Some(lon);
+        }
+        self
+    }
+
+    pub fn build(self) -> FitData {
+        let messages = FitMessages { records: self.records, ..FitMessages::default() };
+        ParsedFitFile::from_messages(messages, 0, FitDiagnostics::imported(0)).fit_data()
+    }
+}
+
+/// FIT base types used by [`FitWriter`] fields
+pub mod base_type {
+    pub const ENUM: u8 = 0x00;
+    pub const UINT8: u8 = 0x02;
+    pub const UINT16: u8 = 0x84;
+    pub const UINT32: u8 = 0x86;
+    pub const UINT32Z: u8 = 0x8C;
+}
+
+/// FIT global message numbers used in the tests
+pub mod mesg_num {
+    pub const FILE_ID: u16 = 0;
+    pub const USER_PROFILE: u16 = 3;
+    pub const SESSION: u16 = 18;
+    pub const LAP: u16 = 19;
+    pub const RECORD: u16 = 20;
+    pub const DEVICE_INFO: u16 = 23;
+}
+
+/// Minimal FIT encoder for building test files: every message gets its own
+/// definition on local type 0, values are little-endian.
+#[derive(Default)]
+pub struct FitWriter {
+    data: Vec<u8>,
+}
+
+impl FitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a definition and one data message; fields are (number, base type, value bytes)
+    pub fn message(&mut self, global: u16, fields: &[(u8, u8, &[u8])]) -> &mut Self {
+        self.data.extend_from_slice(&[0x40, 0, 0]);
+        self.data.extend_from_slice(&global.to_le_bytes());
+        self.data.push(fields.len() as u8);
+        for &(number, base_type, value) in fields {
+            self.data.extend_from_slice(&[number, value.len() as u8, base_type]);
+        }
+        self.data.push(0x00);
+        for &(_, _, value) in fields {
+            self.data.extend_from_slice(value);
+        }
+        self
+    }
+
+    /// Complete file with a 12- or 14-byte header. A 14-byte header gets `header_crc`,
+    /// or its correct CRC if None; the data CRC is always correct.
+    pub fn finish(&self, header_size: u8, header_crc: Option<u16>) -> Vec<u8> {
+        let mut file = vec![header_size, 0x20];
+        file.extend_from_slice(&2132u16.to_le_bytes());
+        file.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        file.extend_from_slice(b".FIT");
+        if header_size == 14 {
+            let header_crc = header_crc.unwrap_or_else(|| fit_crc(0, &file));
+            file.extend_from_slice(&header_crc.to_le_bytes());
+        }
+        // A header with its own CRC is left out of the data CRC
+        let data_start = if file.len() == 14 && file[12..14] != [0, 0] { 14 } else { 0 };
+        file.extend_from_slice(&self.data);
+        let crc = fit_crc(0, &file[data_start..]);
+        file.extend_from_slice(&crc.to_le_bytes());
+        file
+    }
+}
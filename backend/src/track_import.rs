@@ -0,0 +1,631 @@
+//! GPX and TCX import. Trackpoints become the same raw records a FIT file decodes to,
+//! so the rest of the analysis can't tell which format a ride came from.
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use chrono::{DateTime, NaiveDateTime};
+use geo::{HaversineDistance, Point};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use crate::error::{Error, Result};
+use crate::fit_parser::{parse_fit_file, FitDiagnostics, ParsedFitFile};
+use crate::fitparser_wrapper::{FitFileId, FitLap, FitMessages, FitRecord, FitSession};
+use crate::security::MAX_TRACK_FILE_SIZE;
+
+/// Parse a GPX file. Power, heart rate, cadence, temperature and speed are read from
+/// Garmin TrackPointExtension/PowerExtension and the plain `<power>` most apps write.
+/// Each `<trk>` becomes one lap and one session.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn parse_gpx_file(file_data: &[u8]) -> Result<ParsedFitFile> {
+    parse_track(file_data, TrackFormat::Gpx)
+}
+
+/// Parse a Garmin TCX file, with power and speed from the ActivityExtension `TPX`/`LX`
+/// elements. Laps and activities map to FIT laps and sessions.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn parse_tcx_file(file_data: &[u8]) -> Result<ParsedFitFile> {
+    parse_track(file_data, TrackFormat::Tcx)
+}
+
+/// Parse a FIT, GPX or TCX file, telling them apart by content rather than extension
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn parse_activity_file(file_data: &[u8]) -> Result<ParsedFitFile> {
+    if file_data.get(8..12) == Some(b".FIT") {
+        return parse_fit_file(file_data);
+    }
+
+    // The root element is near the start, after the XML declaration and maybe a comment
+    let head = String::from_utf8_lossy(&file_data[..file_data.len().min(4096)]);
+    if head.contains("<gpx") {
+        parse_gpx_file(file_data)
+    } else if head.contains("<TrainingCenterDatabase") {
+        parse_tcx_file(file_data)
+    } else {
+        Err(Error::Validation("Unrecognized activity file: expected FIT, GPX or TCX".to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TrackFormat {
+    Gpx,
+    Tcx,
+}
+
+fn parse_track(file_data: &[u8], format: TrackFormat) -> Result<ParsedFitFile> {
+    if file_data.len() > MAX_TRACK_FILE_SIZE {
+        return Err(Error::Validation(format!(
+            "File too large: {} bytes (maximum {} bytes)",
+            file_data.len(),
+            MAX_TRACK_FILE_SIZE
+        )));
+    }
+
+    let mut builder = TrackBuilder::new(format);
+    builder.read(file_data)?;
+    let messages = builder.finish()?;
+
+    Ok(ParsedFitFile::from_messages(messages, file_data.len(), FitDiagnostics::imported(file_data.len())))
+}
+
+#[derive(Debug, Default)]
+struct Trackpoint {
+    time: Option<f64>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    altitude: Option<f64>,
+    distance: Option<f64>,
+    speed: Option<f64>,
+    power: Option<f64>,
+    heart_rate: Option<f64>,
+    cadence: Option<f64>,
+    temperature: Option<f64>,
+}
+
+/// Lap totals as the file states them (TCX); anything missing is computed from the records
+#[derive(Debug, Default)]
+struct LapSummary {
+    start_time: Option<f64>,
+    total_time: Option<f64>,
+    distance: Option<f64>,
+    avg_speed: Option<f64>,
+    max_speed: Option<f64>,
+    avg_power: Option<f64>,
+    max_power: Option<f64>,
+    avg_heart_rate: Option<f64>,
+    max_heart_rate: Option<f64>,
+    avg_cadence: Option<f64>,
+    calories: Option<f64>,
+}
+
+struct TrackBuilder {
+    format: TrackFormat,
+    /// Local names of the open elements, root first
+    path: Vec<String>,
+    file_id: FitFileId,
+    records: Vec<FitRecord>,
+    point: Option<Trackpoint>,
+    lap: Option<(LapSummary, usize)>,
+    laps: Vec<(LapSummary, std::ops::Range<usize>)>,
+    session: Option<(Option<String>, usize)>,
+    sessions: Vec<(Option<String>, std::ops::Range<usize>)>,
+    untimed_points: usize,
+}
+
+impl TrackBuilder {
+    fn new(format: TrackFormat) -> Self {
+        TrackBuilder {
+            format,
+            path: Vec::new(),
+            file_id: FitFileId { file_type: Some("activity".to_string()), ..FitFileId::default() },
+            records: Vec::new(),
+            point: None,
+            lap: None,
+            laps: Vec::new(),
+            session: None,
+            sessions: Vec::new(),
+            untimed_points: 0,
+        }
+    }
+
+    fn read(&mut self, file_data: &[u8]) -> Result<()> {
+        let mut reader = Reader::from_reader(file_data);
+        reader.config_mut().trim_text(true);
+
+        loop {
+            let position = reader.buffer_position();
+            let event = reader.read_event().map_err(|e| {
+                Error::TrackParse(format!("Invalid XML at byte {}: {}", position, e))
+            })?;
+            match event {
+                Event::Start(element) => {
+                    self.open(&element);
+                    self.path.push(local_name(&element));
+                }
+                Event::Empty(element) => {
+                    self.open(&element);
+                    self.close(&local_name(&element));
+                }
+                Event::End(_) => {
+                    if let Some(name) = self.path.pop() {
+                        self.close(&name);
+                    }
+                }
+                Event::Text(text) => {
+                    if let Ok(text) = text.decode() {
+                        self.text(&text);
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn open(&mut self, element: &BytesStart) {
+        let attribute = |key: &str| {
+            element.attributes().flatten()
+                .find(|attr| attr.key.local_name().as_ref() == key.as_bytes())
+                .and_then(|attr| attr.unescape_value().ok().map(|value| value.into_owned()))
+        };
+
+        match (self.format, local_name(element).as_str()) {
+            (TrackFormat::Gpx, "gpx") => self.file_id.product = attribute("creator"),
+            (TrackFormat::Gpx, "trk") => {
+                self.session = Some((None, self.records.len()));
+                self.lap = Some((LapSummary::default(), self.records.len()));
+            }
+            (TrackFormat::Gpx, "trkpt") => {
+                self.point = Some(Trackpoint {
+                    lat: attribute("lat").as_deref().and_then(parse_number),
+                    lon: attribute("lon").as_deref().and_then(parse_number),
+                    ..Trackpoint::default()
+                });
+            }
+            (TrackFormat::Tcx, "Activity") => {
+                self.session = Some((attribute("Sport").as_deref().and_then(sport_name), self.records.len()));
+            }
+            (TrackFormat::Tcx, "Lap") => {
+                let summary = LapSummary {
+                    start_time: attribute("StartTime").as_deref().and_then(parse_time),
+                    ..LapSummary::default()
+                };
+                self.lap = Some((summary, self.records.len()));
+            }
+            (TrackFormat::Tcx, "Trackpoint") => self.point = Some(Trackpoint::default()),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let name = self.path.last().map(String::as_str).unwrap_or_default();
+        let parent = self.path.iter().rev().nth(1).map(String::as_str).unwrap_or_default();
+        let value = parse_number(text);
+
+        if let Some(point) = &mut self.point {
+            match self.format {
+                TrackFormat::Gpx => {
+                    let in_extensions = self.path.iter().any(|element| element == "extensions");
+                    match (name, in_extensions) {
+                        ("time", false) => point.time = parse_time(text),
+                        ("ele", false) => point.altitude = value,
+                        (_, true) => match name.to_ascii_lowercase().as_str() {
+                            "hr" | "heartrate" => point.heart_rate = value,
+                            "cad" | "cadence" => point.cadence = value,
+                            "atemp" | "temp" | "temperature" => point.temperature = value,
+                            "speed" => point.speed = value,
+                            "power" | "powerinwatts" | "watts" => point.power = value,
+                            "distance" => point.distance = value,
+                            _ => {}
+                        },
+                        _ => {}
+                    }
+                }
+                TrackFormat::Tcx => match (name, parent) {
+                    ("Time", _) => point.time = parse_time(text),
+                    ("LatitudeDegrees", _) => point.lat = value,
+                    ("LongitudeDegrees", _) => point.lon = value,
+                    ("AltitudeMeters", _) => point.altitude = value,
+                    ("DistanceMeters", _) => point.distance = value,
+                    ("Value", "HeartRateBpm") => point.heart_rate = value,
+                    ("Cadence", _) => point.cadence = value,
+                    ("Speed", _) => point.speed = value,
+                    ("Watts", _) => point.power = value,
+                    _ => {}
+                },
+            }
+            return;
+        }
+
+        match (self.format, name, parent) {
+            (TrackFormat::Gpx, "time", "metadata") => self.file_id.time_created = parse_time(text),
+            (TrackFormat::Gpx, "type", "trk") => {
+                if let Some((sport, _)) = &mut self.session {
+                    *sport = sport_name(text);
+                }
+            }
+            (TrackFormat::Tcx, "Id", "Activity") => self.file_id.time_created = parse_time(text),
+            (TrackFormat::Tcx, "Name", "Creator") => self.file_id.product = Some(text.to_string()),
+            (TrackFormat::Tcx, "UnitId", "Creator") => self.file_id.serial_number = text.parse().ok(),
+            (TrackFormat::Tcx, _, _) => {
+                if let Some((lap, _)) = &mut self.lap {
+                    match (name, parent) {
+                        ("TotalTimeSeconds", "Lap") => lap.total_time = value,
+                        ("DistanceMeters", "Lap") => lap.distance = value,
+                        ("MaximumSpeed", "Lap") => lap.max_speed = value,
+                        ("Calories", "Lap") => lap.calories = value,
+                        ("Cadence", "Lap") => lap.avg_cadence = value,
+                        ("Value", "AverageHeartRateBpm") => lap.avg_heart_rate = value,
+                        ("Value", "MaximumHeartRateBpm") => lap.max_heart_rate = value,
+                        ("AvgSpeed", _) => lap.avg_speed = value,
+                        ("AvgWatts", _) => lap.avg_power = value,
+                        ("MaxWatts", _) => lap.max_power = value,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        match (self.format, name) {
+            (TrackFormat::Gpx, "trkpt") | (TrackFormat::Tcx, "Trackpoint") => {
+                let Some(point) = self.point.take() else { return };
+                // Records are keyed by time; an untimed point can't be placed on the timeline
+                let Some(timestamp) = point.time else {
+                    self.untimed_points += 1;
+                    return;
+                };
+                self.records.push(FitRecord {
+                    timestamp,
+                    distance: point.distance,
+                    position_lat: point.lat,
+                    position_long: point.lon,
+                    altitude: point.altitude,
+                    speed: point.speed,
+                    power: point.power,
+                    heart_rate: point.heart_rate,
+                    cadence: point.cadence,
+                    grade: None,
+                    temperature: point.temperature,
+                    gps_accuracy: None,
+                    calories: None,
+                    air_speed: None,
+                    wind_speed: None,
+                    battery_soc: None,
+                });
+            }
+            (TrackFormat::Gpx, "trk") => {
+                self.close_lap();
+                self.close_session();
+            }
+            (TrackFormat::Tcx, "Lap") => self.close_lap(),
+            (TrackFormat::Tcx, "Activity") => self.close_session(),
+            _ => {}
+        }
+    }
+
+    fn close_lap(&mut self) {
+        if let Some((summary, start)) = self.lap.take() {
+            // A lap without trackpoints (summary-only TCX) has nothing to analyze
+            if self.records.len() > start {
+                self.laps.push((summary, start..self.records.len()));
+            }
+        }
+    }
+
+    fn close_session(&mut self) {
+        if let Some((sport, start)) = self.session.take() {
+            if self.records.len() > start {
+                self.sessions.push((sport, start..self.records.len()));
+            }
+        }
+    }
+
+    fn finish(mut self) -> Result<FitMessages> {
+        if self.records.is_empty() {
+            return Err(Error::TrackParse("No timestamped trackpoints found".to_string()));
+        }
+        if self.untimed_points > 0 {
+            crate::utils::warn(&format!("Skipped {} trackpoints without a timestamp", self.untimed_points));
+        }
+
+        fill_distance(&mut self.records);
+        fill_speed(&mut self.records);
+
+        if self.laps.is_empty() {
+            self.laps.push((LapSummary::default(), 0..self.records.len()));
+        }
+        let laps: Vec<FitLap> = self.laps.iter()
+            .map(|(summary, range)| lap_from_records(summary, &self.records[range.clone()]))
+            .collect();
+        let sessions = self.sessions.iter()
+            .map(|(sport, range)| {
+                // The session spans its laps, whose stated durations can outlast the last point
+                let session_laps: Vec<&FitLap> = self.laps.iter().zip(&laps)
+                    .filter(|((_, lap_range), _)| lap_range.start >= range.start && lap_range.end <= range.end)
+                    .map(|(_, lap)| lap)
+                    .collect();
+                session_from_records(sport.clone(), &self.records[range.clone()], &session_laps)
+            })
+            .collect();
+
+        Ok(FitMessages {
+            records: self.records,
+            laps,
+            file_id: Some(self.file_id),
+            sessions,
+            ..FitMessages::default()
+        })
+    }
+}
+
+fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    text.trim().parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+/// ISO 8601 time as Unix seconds; a time without an offset is taken as UTC
+pub(crate) fn parse_time(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let utc = DateTime::parse_from_rfc3339(text).ok().map(|time| time.to_utc())
+        .or_else(|| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f").ok().map(|time| time.and_utc()))?;
+    Some(utc.timestamp_millis() as f64 / 1000.0)
+}
+
+/// FIT sport names for the common GPX `<type>` and TCX `Sport` values
+fn sport_name(raw: &str) -> Option<String> {
+    let raw = raw.trim().to_ascii_lowercase();
+    let sport = if raw.contains("bik") || raw.contains("cycl") || raw.contains("ride") {
+        "cycling"
+    } else if raw.contains("run") {
+        "running"
+    } else if raw.contains("swim") {
+        "swimming"
+    } else {
+        return None;
+    };
+    Some(sport.to_string())
+}
+
+/// GPX has no distance channel: accumulate great-circle distance between fixes
+pub(crate) fn fill_distance(records: &mut [FitRecord]) {
+    if records.iter().any(|record| record.distance.is_some()) {
+        return;
+    }
+
+    let mut total = 0.0;
+    let mut previous: Option<Point> = None;
+    for record in records.iter_mut() {
+        if let (Some(lat), Some(lon)) = (record.position_lat, record.position_long) {
+            let point = Point::new(lon, lat);
+            if let Some(previous) = previous {
+                total += previous.haversine_distance(&point);
+            }
+            previous = Some(point);
+        }
+        record.distance = previous.map(|_| total);
+    }
+}
+
+/// Speed from distance over time for points that don't carry it
+pub(crate) fn fill_speed(records: &mut [FitRecord]) {
+    for i in 0..records.len() {
+        if records[i].speed.is_some() {
+            continue;
+        }
+        // Backward difference, forward for the first point
+        let (a, b) = if i > 0 { (i - 1, i) } else { (0, 1.min(records.len() - 1)) };
+        let dt = records[b].timestamp - records[a].timestamp;
+        if let (Some(d0), Some(d1)) = (records[a].distance, records[b].distance) {
+            if dt > 0.0 {
+                records[i].speed = Some(((d1 - d0) / dt).max(0.0));
+            }
+        }
+    }
+}
+
+/// Lap totals computed entirely from the records, for formats without lap messages
+pub(crate) fn lap_from_samples(records: &[FitRecord]) -> FitLap {
+    lap_from_records(&LapSummary::default(), records)
+}
+
+fn lap_from_records(summary: &LapSummary, records: &[FitRecord]) -> FitLap {
+    let first = &records[0];
+    let last = &records[records.len() - 1];
+    let start_time = summary.start_time.unwrap_or(first.timestamp);
+    let total_elapsed_time = summary.total_time.unwrap_or(last.timestamp - start_time);
+    let total_distance = summary.distance
+        .unwrap_or_else(|| last.distance.unwrap_or(0.0) - first.distance.unwrap_or(0.0));
+    let start = records.iter().find(|record| record.position_lat.is_some() && record.position_long.is_some());
+
+    FitLap {
+        start_time,
+        end_time: start_time + total_elapsed_time,
+        total_elapsed_time,
+        total_distance,
+        avg_speed: summary.avg_speed.unwrap_or(if total_elapsed_time > 0.0 { total_distance / total_elapsed_time } else { 0.0 }),
+        max_speed: summary.max_speed.or_else(|| max(records, |record| record.speed)).unwrap_or(0.0),
+        avg_power: summary.avg_power.or_else(|| mean(records, |record| record.power)).unwrap_or(0.0),
+        max_power: summary.max_power.or_else(|| max(records, |record| record.power)).unwrap_or(0.0),
+        start_position_lat: start.and_then(|record| record.position_lat),
+        start_position_long: start.and_then(|record| record.position_long),
+        avg_heart_rate: summary.avg_heart_rate.or_else(|| mean(records, |record| record.heart_rate)),
+        max_heart_rate: summary.max_heart_rate.or_else(|| max(records, |record| record.heart_rate)),
+        total_calories: summary.calories,
+        avg_cadence: summary.avg_cadence.or_else(|| mean(records, |record| record.cadence)),
+        max_cadence: max(records, |record| record.cadence),
+    }
+}
+
+fn session_from_records(sport: Option<String>, records: &[FitRecord], laps: &[&FitLap]) -> FitSession {
+    let lap = lap_from_samples(records);
+    let start_time = laps.iter().map(|lap| lap.start_time).fold(lap.start_time, f64::min);
+    let end_time = laps.iter().map(|lap| lap.end_time).fold(lap.end_time, f64::max);
+    FitSession {
+        sport,
+        start_time,
+        end_time,
+        total_elapsed_time: end_time - start_time,
+        total_distance: Some(lap.total_distance),
+        avg_speed: Some(lap.avg_speed),
+        max_speed: Some(lap.max_speed),
+        avg_power: mean(records, |record| record.power),
+        max_power: max(records, |record| record.power),
+        avg_heart_rate: lap.avg_heart_rate,
+        max_heart_rate: lap.max_heart_rate,
+        avg_cadence: lap.avg_cadence,
+        ..FitSession::default()
+    }
+}
+
+fn mean(records: &[FitRecord], channel: impl Fn(&FitRecord) -> Option<f64>) -> Option<f64> {
+    let values: Vec<f64> = records.iter().filter_map(channel).collect();
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+fn max(records: &[FitRecord], channel: impl Fn(&FitRecord) -> Option<f64>) -> Option<f64> {
+    records.iter().filter_map(channel).reduce(f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx creator="Wahoo ELEMNT" version="1.1" xmlns="http://www.topografix.com/GPX/1/1"
+     xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v2"
+     xmlns:pwr="http://www.garmin.com/xmlschemas/PowerExtension/v1">
+  <metadata><time>2024-06-01T10:00:00Z</time></metadata>
+  <trk>
+    <name>Track session</name>
+    <type>cycling</type>
+    <trkseg>
+      <trkpt lat="47.000000" lon="7.000000">
+        <ele>500.0</ele>
+        <time>2024-06-01T10:00:00Z</time>
+        <extensions>
+          <gpxtpx:TrackPointExtension><gpxtpx:hr>120</gpxtpx:hr><gpxtpx:cad>85</gpxtpx:cad><gpxtpx:atemp>21</gpxtpx:atemp></gpxtpx:TrackPointExtension>
+          <pwr:PowerInWatts>200</pwr:PowerInWatts>
+        </extensions>
+      </trkpt>
+      <trkpt lat="47.000090" lon="7.000000">
+        <ele>500.5</ele>
+        <time>2024-06-01T10:00:01Z</time>
+        <extensions><power>210</power><gpxtpx:TrackPointExtension><gpxtpx:speed>10.5</gpxtpx:speed></gpxtpx:TrackPointExtension></extensions>
+      </trkpt>
+      <trkpt lat="47.000180" lon="7.000000">
+        <ele>501.0</ele>
+        <time>2024-06-01T10:00:02Z</time>
+      </trkpt>
+      <trkpt lat="47.000270" lon="7.000000"><ele>501.0</ele></trkpt>
+    </trkseg>
+  </trk>
+</gpx>"#;
+
+    const TCX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2"
+    xmlns:ns3="http://www.garmin.com/xmlschemas/ActivityExtension/v2">
+  <Activities>
+    <Activity Sport="Biking">
+      <Id>2024-06-01T10:00:00.000Z</Id>
+      <Lap StartTime="2024-06-01T10:00:00.000Z">
+        <TotalTimeSeconds>2.0</TotalTimeSeconds>
+        <DistanceMeters>20.0</DistanceMeters>
+        <MaximumSpeed>10.5</MaximumSpeed>
+        <AverageHeartRateBpm><Value>121</Value></AverageHeartRateBpm>
+        <Track>
+          <Trackpoint>
+            <Time>2024-06-01T10:00:00.000Z</Time>
+            <Position><LatitudeDegrees>47.0</LatitudeDegrees><LongitudeDegrees>7.0</LongitudeDegrees></Position>
+            <AltitudeMeters>500.0</AltitudeMeters>
+            <DistanceMeters>0.0</DistanceMeters>
+            <HeartRateBpm><Value>120</Value></HeartRateBpm>
+            <Cadence>85</Cadence>
+            <Extensions><ns3:TPX><ns3:Speed>9.5</ns3:Speed><ns3:Watts>200</ns3:Watts></ns3:TPX></Extensions>
+          </Trackpoint>
+          <Trackpoint>
+            <Time>2024-06-01T10:00:01.000Z</Time>
+            <DistanceMeters>10.0</DistanceMeters>
+            <Extensions><ns3:TPX><ns3:Speed>10.5</ns3:Speed><ns3:Watts>220</ns3:Watts></ns3:TPX></Extensions>
+          </Trackpoint>
+        </Track>
+        <Extensions><ns3:LX><ns3:AvgWatts>210</ns3:AvgWatts></ns3:LX></Extensions>
+      </Lap>
+      <Lap StartTime="2024-06-01T10:00:02.000Z">
+        <TotalTimeSeconds>1.0</TotalTimeSeconds>
+        <DistanceMeters>11.0</DistanceMeters>
+        <Track>
+          <Trackpoint>
+            <Time>2024-06-01T10:00:02.000Z</Time>
+            <DistanceMeters>21.0</DistanceMeters>
+          </Trackpoint>
+        </Track>
+      </Lap>
+      <Creator><Name>Edge 530</Name><UnitId>3312345678</UnitId></Creator>
+    </Activity>
+  </Activities>
+</TrainingCenterDatabase>"#;
+
+    const T0: f64 = 1_717_236_000.0;
+
+    #[test]
+    fn test_gpx_extensions_and_derived_channels() {
+        let parsed = parse_gpx_file(GPX.as_bytes()).unwrap();
+        let data = parsed.fit_data();
+
+        // The untimed last point is dropped
+        assert_eq!(data.timestamps(), vec![T0, T0 + 1.0, T0 + 2.0]);
+        assert_eq!(data.power(), vec![200.0, 210.0, 0.0]);
+        assert_eq!(data.heart_rate()[0], 120.0);
+        assert_eq!(data.cadence()[0], 85.0);
+        assert_eq!(data.temperature()[0], 21.0);
+        assert_eq!(data.altitude(), vec![500.0, 500.5, 501.0]);
+
+        // 0.00009° of latitude is ~10 m; speed comes from the extension where present
+        let distance = data.distance();
+        assert!((distance[1] - 10.0).abs() < 0.1 && (distance[2] - 20.0).abs() < 0.1);
+        assert_eq!(data.velocity()[1], 10.5);
+        assert!((data.velocity()[2] - 10.0).abs() < 0.1);
+
+        assert_eq!(parsed.laps().len(), 1);
+        assert_eq!(parsed.laps()[0].avg_power(), 205.0);
+        assert_eq!(parsed.sessions()[0].sport().as_deref(), Some("cycling"));
+        assert_eq!(parsed.file_id().unwrap().product(), Some("Wahoo ELEMNT".to_string()));
+    }
+
+    #[test]
+    fn test_tcx_laps_and_power() {
+        let parsed = parse_tcx_file(TCX.as_bytes()).unwrap();
+        let data = parsed.fit_data();
+        assert_eq!(data.record_count(), 3);
+        assert_eq!(data.power(), vec![200.0, 220.0, 0.0]);
+        assert_eq!(data.velocity()[..2], [9.5, 10.5]);
+        // No speed in the last point: from DistanceMeters over time
+        assert_eq!(data.velocity()[2], 11.0);
+        assert_eq!(data.position_lat(), vec![47.0, 0.0, 0.0]);
+
+        let laps = parsed.laps();
+        assert_eq!(laps.len(), 2);
+        assert_eq!((laps[0].start_time(), laps[0].end_time()), (T0, T0 + 2.0));
+        assert_eq!((laps[0].avg_power(), laps[0].max_speed()), (210.0, 10.5));
+        assert_eq!(laps[1].total_distance(), 11.0);
+
+        let sessions = parsed.sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!((sessions[0].sport().as_deref(), sessions[0].record_count(), sessions[0].lap_count()), (Some("cycling"), 3, 2));
+        assert_eq!(parsed.file_id().unwrap().serial_number(), Some(3312345678));
+    }
+
+    #[test]
+    fn test_activity_file_detection() {
+        assert_eq!(parse_activity_file(GPX.as_bytes()).unwrap().fit_data().record_count(), 3);
+        assert_eq!(parse_activity_file(TCX.as_bytes()).unwrap().laps().len(), 2);
+        assert!(matches!(parse_activity_file(b"time,power\n1,200\n"), Err(Error::Validation(_))));
+        assert!(matches!(parse_gpx_file(b"<gpx><trk><trkseg></trk>"), Err(Error::TrackParse(_))));
+    }
+}
@@ -1,6 +1,115 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
+use chrono::DateTime;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::error::{Error, Result};
 
-#[wasm_bindgen]
+/// Timestamps below this are a logger's own timer, not Unix time (1973-03-03)
+pub(crate) const RELATIVE_CLOCK_LIMIT: f64 = 1e8;
+
+/// RFC 3339 time of a Unix timestamp; None for a logger's own timer (CSV imports),
+/// which has no date to show
+pub(crate) fn rfc3339(timestamp: f64) -> Option<String> {
+    if !timestamp.is_finite() || timestamp < RELATIVE_CLOCK_LIMIT {
+        return None;
+    }
+    DateTime::from_timestamp_millis((timestamp * 1000.0).round() as i64).map(|time| time.to_rfc3339())
+}
+
+/// Diagnostic message: browser console in the wasm build, dropped natively
+pub(crate) fn log(message: &str) {
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    web_sys::console::log_1(&message.into());
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    let _ = message;
+}
+
+/// Warning: browser console in the wasm build, stderr natively
+pub(crate) fn warn(message: &str) {
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    web_sys::console::warn_1(&message.into());
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    eprintln!("{}", message);
+}
+
+/// Serialize for the `to_json` methods; `what` names the type in error messages
+pub(crate) fn to_json<T: Serialize>(value: &T, what: &str) -> Result<String> {
+    serde_json::to_string(value)
+        .map_err(|e| Error::InvalidInput(format!("Could not serialize {}: {}", what, e)))
+}
+
+/// Deserialize for the `from_json` methods; `what` names the type in error messages
+pub(crate) fn from_json<T: DeserializeOwned>(json: &str, what: &str) -> Result<T> {
+    serde_json::from_str(json)
+        .map_err(|e| Error::InvalidInput(format!("Invalid {} JSON: {}", what, e)))
+}
+
+/// Lowercase hex SHA-256 digest
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// FIT CRC-16 (the nibble-table variant from the FIT SDK), continuing from `crc`
+pub(crate) fn fit_crc(crc: u16, bytes: &[u8]) -> u16 {
+    const TABLE: [u16; 16] = [
+        0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401,
+        0xA001, 0x6C00, 0x7800, 0xB401, 0x5000, 0x9C01, 0x8801, 0x4400,
+    ];
+    bytes.iter().fold(crc, |mut crc, &byte| {
+        let tmp = TABLE[(crc & 0xF) as usize];
+        crc = (crc >> 4) & 0x0FFF;
+        crc = crc ^ tmp ^ TABLE[(byte & 0xF) as usize];
+        let tmp = TABLE[(crc & 0xF) as usize];
+        crc = (crc >> 4) & 0x0FFF;
+        crc ^ tmp ^ TABLE[(byte >> 4) as usize]
+    })
+}
+
+/// CSV delimiter as the byte the csv crate takes: ASCII, and not a quote or line break,
+/// which would make the file unreadable
+pub(crate) fn csv_delimiter(delimiter: char) -> Result<u8> {
+    if !delimiter.is_ascii() || matches!(delimiter, '"' | '\n' | '\r') {
+        return Err(Error::InvalidInput(format!(
+            "CSV delimiter must be an ASCII character other than a quote or line break, got {:?}",
+            delimiter
+        )));
+    }
+    Ok(delimiter as u8)
+}
+
+/// Linear interpolation of `values` sampled at ascending `times` onto `targets`.
+/// Targets outside the samples take the first/last value; a NaN neighbour is bridged
+/// by the other one, and NaN comes out only where both are NaN.
+pub(crate) fn interpolate(times: &[f64], values: &[f64], targets: &[f64]) -> Vec<f64> {
+    if times.is_empty() {
+        return vec![f64::NAN; targets.len()];
+    }
+
+    let last = times.len() - 1;
+    let mut i = 0;
+    targets.iter().map(|&t| {
+        while i < last && times[i + 1] < t {
+            i += 1;
+        }
+        if t <= times[0] {
+            return values[0];
+        }
+        if t >= times[last] {
+            return values[last];
+        }
+        let (t0, t1, v0, v1) = (times[i], times[i + 1], values[i], values[i + 1]);
+        match (v0.is_nan(), v1.is_nan()) {
+            (true, _) => v1,
+            (false, true) => v0,
+            _ if t1 == t0 => v0,
+            _ => v0 + (t - t0) * (v1 - v0) / (t1 - t0),
+        }
+    }).collect()
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn format_duration(seconds: f64) -> String {
     let hours = (seconds / 3600.0).floor() as u32;
     let minutes = ((seconds % 3600.0) / 60.0).floor() as u32;
@@ -13,7 +122,7 @@ pub fn format_duration(seconds: f64) -> String {
     }
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn format_distance(meters: f64) -> String {
     if meters >= 1000.0 {
         format!("{:.1} km", meters / 1000.0)
@@ -22,13 +131,64 @@ pub fn format_distance(meters: f64) -> String {
     }
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn format_speed(ms: f64) -> String {
     let kmh = ms * 3.6;
     format!("{:.1} km/h", kmh)
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub fn format_power(watts: f64) -> String {
     format!("{:.0} W", watts)
-}
\ No newline at end of file
+}
+/// Serde adapters writing non-finite floats as JSON `null` and reading `null` back
+/// as NaN. serde_json already emits `null` for NaN but refuses to parse it, and
+/// ride data is full of NaN gaps (no GPS fix, dropped sensor samples).
+pub(crate) mod nan_as_null {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    fn to_option(value: f64) -> Option<f64> {
+        value.is_finite().then_some(value)
+    }
+
+    pub mod scalar {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+            to_option(*value).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+            Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::NAN))
+        }
+    }
+
+    pub mod vec {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(values: &[f64], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(values.iter().map(|&v| to_option(v)))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f64>, D::Error> {
+            let values = Vec::<Option<f64>>::deserialize(deserializer)?;
+            Ok(values.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect())
+        }
+    }
+
+    pub mod option_vec {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(values: &Option<Vec<f64>>, serializer: S) -> Result<S::Ok, S::Error> {
+            match values {
+                Some(values) => serializer.serialize_some(&values.iter().map(|&v| to_option(v)).collect::<Vec<_>>()),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<f64>>, D::Error> {
+            let values = Option::<Vec<Option<f64>>>::deserialize(deserializer)?;
+            Ok(values.map(|values| values.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect()))
+        }
+    }
+}
@@ -1,8 +1,12 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::utils;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all, set_all))]
 pub struct VEParameters {
     pub system_mass: f64,
     pub rho: f64,
@@ -18,9 +22,9 @@ pub struct VEParameters {
     pub velodrome: bool,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl VEParameters {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new() -> VEParameters {
         VEParameters {
             system_mass: 75.0,
@@ -37,27 +41,54 @@ impl VEParameters {
             velodrome: false,
         }
     }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_json(&self) -> Result<String> {
+        utils::to_json(self, "VEParameters")
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_json(json: &str) -> Result<VEParameters> {
+        utils::from_json(json, "VEParameters")
+    }
+}
+
+impl Default for VEParameters {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
 pub struct VEData {
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     timestamps: Vec<f64>,
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     power: Vec<f64>,
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     velocity: Vec<f64>, // ground speed in m/s
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     position_lat: Vec<f64>,
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     position_long: Vec<f64>,
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     altitude: Vec<f64>,
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     distance: Vec<f64>,
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     air_speed: Vec<f64>, // apparent wind velocity in m/s (if available)
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     wind_speed: Vec<f64>, // wind speed relative to rider (if available)
-    #[wasm_bindgen(skip)]
+    #[serde(default, with = "crate::utils::nan_as_null::option_vec")]
     rho_array: Option<Vec<f64>>, // per-datapoint air density (if available from environmental data)
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl VEData {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         timestamps: Vec<f64>,
         power: Vec<f64>,
@@ -84,69 +115,183 @@ impl VEData {
     }
 
     /// Set per-datapoint air density array (for use with environmental data from CSV)
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn set_rho_array(&mut self, rho_array: Vec<f64>) {
         self.rho_array = Some(rho_array);
     }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_json(&self) -> Result<String> {
+        utils::to_json(self, "VEData")
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_json(json: &str) -> Result<VEData> {
+        utils::from_json(json, "VEData")
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
 pub struct VEResult {
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     virtual_elevation: Vec<f64>,
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     virtual_slope: Vec<f64>,
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     acceleration: Vec<f64>,
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     effective_wind: Vec<f64>,
+    #[serde(with = "crate::utils::nan_as_null::vec")]
     apparent_velocity: Vec<f64>,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
     r2: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
     rmse: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
     ve_elevation_diff: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
     actual_elevation_diff: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
     virtual_distance_air: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
     virtual_distance_ground: f64,
+    #[serde(with = "crate::utils::nan_as_null::scalar")]
     vd_difference_percent: f64,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl VEResult {
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn virtual_elevation(&self) -> Vec<f64> { self.virtual_elevation.clone() }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn virtual_slope(&self) -> Vec<f64> { self.virtual_slope.clone() }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn acceleration(&self) -> Vec<f64> { self.acceleration.clone() }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn effective_wind(&self) -> Vec<f64> { self.effective_wind.clone() }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn apparent_velocity(&self) -> Vec<f64> { self.apparent_velocity.clone() }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn r2(&self) -> f64 { self.r2 }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn rmse(&self) -> f64 { self.rmse }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn ve_elevation_diff(&self) -> f64 { self.ve_elevation_diff }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn actual_elevation_diff(&self) -> f64 { self.actual_elevation_diff }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn virtual_distance_air(&self) -> f64 { self.virtual_distance_air }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn virtual_distance_ground(&self) -> f64 { self.virtual_distance_ground }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn vd_difference_percent(&self) -> f64 { self.vd_difference_percent }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_json(&self) -> Result<String> {
+        utils::to_json(self, "VEResult")
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_json(json: &str) -> Result<VEResult> {
+        utils::from_json(json, "VEResult")
+    }
+}
+
+// Borrowed access to the series; the getters above copy (a JS array has to own its data).
+// The wasm adapter builds zero-copy Float64Array views on top of these.
+impl VEResult {
+    pub fn virtual_elevation_slice(&self) -> &[f64] { &self.virtual_elevation }
+
+    pub fn virtual_slope_slice(&self) -> &[f64] { &self.virtual_slope }
+
+    pub fn acceleration_slice(&self) -> &[f64] { &self.acceleration }
+
+    pub fn effective_wind_slice(&self) -> &[f64] { &self.effective_wind }
+
+    pub fn apparent_velocity_slice(&self) -> &[f64] { &self.apparent_velocity }
+}
+
+/// CdA/Crr fitted by minimizing the VE vs actual elevation error over one or more trim regions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass(get_all))]
+pub struct CdaCrrFit {
+    cda: f64,
+    crr: f64,
+    cda_std_error: f64,
+    crr_std_error: f64,
+    rmse: f64,
+    iterations: usize,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CdaCrrFit {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda(&self) -> f64 { self.cda }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr(&self) -> f64 { self.crr }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn cda_std_error(&self) -> f64 { self.cda_std_error }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn crr_std_error(&self) -> f64 { self.crr_std_error }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn rmse(&self) -> f64 { self.rmse }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn iterations(&self) -> usize { self.iterations }
+}
+
+/// Power meter correction applied before the virtual slope equation:
+/// corrected = scale * measured + offset (offset only while pedalling)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PowerCorrection {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl PowerCorrection {
+    pub const NONE: PowerCorrection = PowerCorrection { scale: 1.0, offset: 0.0 };
+
+    fn apply(&self, power: f64) -> f64 {
+        if power > 0.0 {
+            power * self.scale + self.offset
+        } else {
+            power * self.scale
+        }
+    }
+}
+
+/// Per-sample terms of the virtual slope equation that don't depend on CdA/Crr
+#[derive(Debug, Clone)]
+pub(crate) struct SlopeInputs {
+    pub velocity: Vec<f64>,
+    pub power: Vec<f64>,
+    pub acceleration: Vec<f64>,
+    pub effective_wind: Vec<f64>,
+    pub apparent_velocity: Vec<f64>,
+    pub rho: Vec<f64>,
 }
 
-#[wasm_bindgen]
+#[derive(Clone)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "python", pyo3::pyclass)]
 pub struct VirtualElevationCalculator {
     data: VEData,
     params: VEParameters,
@@ -154,9 +299,9 @@ pub struct VirtualElevationCalculator {
     air_speed_calibration: f64, // air_speed multiplier (1.0 = no adjustment, 1.1 = +10%, 0.9 = -10%)
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl VirtualElevationCalculator {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(data: VEData, params: VEParameters) -> VirtualElevationCalculator {
         VirtualElevationCalculator {
             data,
@@ -166,8 +311,14 @@ impl VirtualElevationCalculator {
         }
     }
 
+    /// Number of samples in the ride data
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn record_count(&self) -> usize {
+        self.data.velocity.len()
+    }
+
     /// Set air speed calibration factor (1.0 = no adjustment, 1.1 = +10%, 0.9 = -10%)
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn set_air_speed_calibration(&mut self, calibration: f64) {
         self.air_speed_calibration = calibration;
     }
@@ -208,7 +359,7 @@ impl VirtualElevationCalculator {
     }
 
     /// Calculate smoothed rider directions
-    fn calculate_rider_directions(&self) -> Vec<f64> {
+    pub(crate) fn calculate_rider_directions(&self) -> Vec<f64> {
         let lat = &self.data.position_lat;
         let lon = &self.data.position_long;
         let n = lat.len();
@@ -254,6 +405,11 @@ impl VirtualElevationCalculator {
 
     /// Calculate effective wind velocity considering wind direction and rider movement
     fn calculate_effective_wind(&self) -> Vec<f64> {
+        self.effective_wind_with(None)
+    }
+
+    /// Effective wind along precomputed rider directions (computed here if None and needed)
+    pub(crate) fn effective_wind_with(&self, rider_directions: Option<&[f64]>) -> Vec<f64> {
         let wind_speed = self.params.wind_speed.unwrap_or(0.0);
 
         // If no wind speed, return zero wind
@@ -273,10 +429,17 @@ impl VirtualElevationCalculator {
             return vec![wind_speed; self.data.velocity.len()];
         }
 
-        let rider_directions = self.calculate_rider_directions();
+        let computed;
+        let rider_directions = match rider_directions {
+            Some(directions) => directions,
+            None => {
+                computed = self.calculate_rider_directions();
+                &computed
+            }
+        };
         let mut effective_wind = Vec::new();
 
-        for &rider_dir in &rider_directions {
+        for &rider_dir in rider_directions {
             // Wind direction: direction wind is COMING FROM (meteorological convention)
             // Rider direction: direction rider is MOVING TOWARDS (geographic bearing)
             //
@@ -304,7 +467,7 @@ impl VirtualElevationCalculator {
     }
 
     /// Get apparent velocity (ground + wind) with optional air_speed calibration
-    fn get_apparent_velocity(&self, effective_wind: &[f64]) -> Vec<f64> {
+    pub(crate) fn get_apparent_velocity(&self, effective_wind: &[f64]) -> Vec<f64> {
         // Prioritize air_speed data if available
         if !self.data.air_speed.is_empty() && self.data.air_speed.iter().any(|&x| !x.is_nan() && x != 0.0) {
             // Apply calibration to air_speed
@@ -375,37 +538,6 @@ impl VirtualElevationCalculator {
         (vd_air, vd_ground, vd_diff_percent)
     }
 
-    /// Calculate virtual slope
-    fn calculate_virtual_slope(&self, cda: f64, crr: f64) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
-        let acceleration = self.calculate_acceleration();
-        let effective_wind = self.calculate_effective_wind();
-        let apparent_velocity = self.get_apparent_velocity(&effective_wind);
-
-        let mut slope = Vec::new();
-
-        for i in 0..self.data.velocity.len() {
-            let v = self.data.velocity[i].max(0.001); // Avoid division by zero
-            let w = self.data.power[i] * self.params.eta;
-            let a = acceleration[i];
-            let va = apparent_velocity[i];
-
-            // Use per-datapoint rho if available, otherwise use the parameter value
-            let rho = self.data.rho_array.as_ref()
-                .and_then(|arr| arr.get(i).copied())
-                .unwrap_or(self.params.rho);
-
-            // Virtual slope calculation (Robert Chung's formula)
-            let virtual_slope = (w / (v * self.params.system_mass * 9.807))
-                - (cda * rho * va.powi(2) / (2.0 * self.params.system_mass * 9.807))
-                - crr
-                - (a / 9.807);
-
-            slope.push(if virtual_slope.is_finite() { virtual_slope } else { 0.0 });
-        }
-
-        (slope, effective_wind, apparent_velocity)
-    }
-
     /// Calculate virtual slope with per-datapoint CdA array
     fn calculate_virtual_slope_with_cda_array(&self, cda_array: &[f64], crr: f64) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
         let acceleration = self.calculate_acceleration();
@@ -444,63 +576,20 @@ impl VirtualElevationCalculator {
     }
 
     /// Calculate virtual elevation profile
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn calculate_virtual_elevation(&self, cda: f64, crr: f64, trim_start: usize, trim_end: usize) -> VEResult {
-        let (virtual_slope, effective_wind, apparent_velocity) = self.calculate_virtual_slope(cda, crr);
-        let acceleration = self.calculate_acceleration();
-
-        // Calculate elevation changes
-        let mut delta_elevation = Vec::new();
-        for i in 0..virtual_slope.len() {
-            let v = self.data.velocity[i];
-            let slope = virtual_slope[i];
-            let delta_elev = v * self.dt * slope.atan().sin();
-            delta_elevation.push(delta_elev);
-        }
-
-        // Cumulative sum to get elevation profile
-        let mut virtual_elevation = Vec::new();
-        let mut cumsum = 0.0;
-        for delta in &delta_elevation {
-            cumsum += delta;
-            virtual_elevation.push(cumsum);
-        }
-
-        // Calculate metrics if actual elevation is available
-        let (r2, rmse, ve_elevation_diff, actual_elevation_diff) =
-            self.calculate_metrics(&virtual_elevation, trim_start, trim_end);
-
-        // Calculate virtual distances within trim region
-        let (virtual_distance_air, virtual_distance_ground, vd_difference_percent) =
-            self.calculate_virtual_distances(trim_start, trim_end);
-
-        VEResult {
-            virtual_elevation,
-            virtual_slope,
-            acceleration,
-            effective_wind,
-            apparent_velocity,
-            r2,
-            rmse,
-            ve_elevation_diff,
-            actual_elevation_diff,
-            virtual_distance_air,
-            virtual_distance_ground,
-            vd_difference_percent,
-        }
+        self.ve_result(&self.slope_inputs(), cda, crr, trim_start, trim_end)
     }
 
     /// Calculate virtual elevation profile with per-datapoint CdA array
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn calculate_virtual_elevation_with_cda_array(&self, cda_array: &[f64], crr: f64, trim_start: usize, trim_end: usize) -> VEResult {
         let (virtual_slope, effective_wind, apparent_velocity) = self.calculate_virtual_slope_with_cda_array(cda_array, crr);
         let acceleration = self.calculate_acceleration();
 
         // Calculate elevation changes
         let mut delta_elevation = Vec::new();
-        for i in 0..virtual_slope.len() {
-            let v = self.data.velocity[i];
-            let slope = virtual_slope[i];
+        for (&v, &slope) in self.data.velocity.iter().zip(&virtual_slope) {
             let delta_elev = v * self.dt * slope.atan().sin();
             delta_elevation.push(delta_elev);
         }
@@ -537,6 +626,13 @@ impl VirtualElevationCalculator {
         }
     }
 
+    /// Fit CdA and Crr (within the parameter bounds) to the actual elevation in the trim region.
+    /// A CdA or Crr already set in the parameters is held fixed.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn optimize_cda_crr(&self, trim_start: usize, trim_end: usize) -> Result<CdaCrrFit> {
+        self.fit_cda_crr(&[(trim_start, trim_end)], PowerCorrection::NONE)
+    }
+
     /// Calculate R², RMSE and elevation differences within trim region
     fn calculate_metrics(&self, virtual_elevation: &[f64], trim_start: usize, trim_end: usize) -> (f64, f64, f64, f64) {
         // Check if we have actual elevation data
@@ -628,121 +724,272 @@ impl VirtualElevationCalculator {
     }
 }
 
-/// Helper function to create VE calculator from JS data
-#[wasm_bindgen]
-pub fn create_ve_calculator(
-    // Data arrays
-    timestamps: Vec<f64>,
-    power: Vec<f64>,
-    velocity: Vec<f64>,
-    position_lat: Vec<f64>,
-    position_long: Vec<f64>,
-    altitude: Vec<f64>,
-    distance: Vec<f64>,
-    air_speed: Vec<f64>,
-    wind_speed: Vec<f64>,
-    // Parameters
-    system_mass: f64,
-    rho: f64,
-    eta: f64,
-    cda: Option<f64>,
-    crr: Option<f64>,
-    cda_min: f64,
-    cda_max: f64,
-    crr_min: f64,
-    crr_max: f64,
-    wind_speed_param: Option<f64>,
-    wind_direction: Option<f64>,
-    velodrome: bool,
-) -> VirtualElevationCalculator {
-    let data = VEData::new(
-        timestamps,
-        power,
-        velocity,
-        position_lat,
-        position_long,
-        altitude,
-        distance,
-        air_speed,
-        wind_speed,
-    );
-
-    let mut params = VEParameters::new();
-    params.system_mass = system_mass;
-    params.rho = rho;
-    params.eta = eta;
-    params.cda = cda;
-    params.crr = crr;
-    params.cda_min = cda_min;
-    params.cda_max = cda_max;
-    params.crr_min = crr_min;
-    params.crr_max = crr_max;
-    params.wind_speed = wind_speed_param;
-    params.wind_direction = wind_direction;
-    params.velodrome = velodrome;
-
-    VirtualElevationCalculator::new(data, params)
+// Building blocks shared by the fitting and analysis modules (not exposed to JS)
+impl VirtualElevationCalculator {
+    pub(crate) fn params(&self) -> &VEParameters {
+        &self.params
+    }
+
+    pub(crate) fn params_mut(&mut self) -> &mut VEParameters {
+        &mut self.params
+    }
+
+    pub(crate) fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    pub(crate) fn air_speed_calibration(&self) -> f64 {
+        self.air_speed_calibration
+    }
+
+    /// True when apparent velocity comes from a measured air speed channel
+    pub(crate) fn has_air_speed(&self) -> bool {
+        !self.data.air_speed.is_empty() && self.data.air_speed.iter().any(|&x| !x.is_nan() && x != 0.0)
+    }
+
+    /// True when apparent velocity is derived from the wind_speed/wind_direction parameters
+    /// rather than measured air speed or wind channels (see `get_apparent_velocity`)
+    pub(crate) fn uses_wind_parameters(&self) -> bool {
+        let has_wind_data = !self.data.wind_speed.is_empty()
+            && self.data.wind_speed.iter().any(|&x| !x.is_nan() && x != 0.0);
+        !self.has_air_speed() && !has_wind_data
+    }
+
+    /// Scale the per-datapoint air density, if present, by a constant factor
+    pub(crate) fn scale_rho_array(&mut self, factor: f64) {
+        if let Some(rho_array) = self.data.rho_array.as_mut() {
+            rho_array.iter_mut().for_each(|rho| *rho *= factor);
+        }
+    }
+
+    /// Precompute everything in the slope equation that doesn't depend on CdA/Crr
+    pub(crate) fn slope_inputs(&self) -> SlopeInputs {
+        self.slope_inputs_with(None)
+    }
+
+    /// `slope_inputs` reusing rider directions from an earlier `calculate_rider_directions`
+    pub(crate) fn slope_inputs_with(&self, rider_directions: Option<&[f64]>) -> SlopeInputs {
+        let acceleration = self.calculate_acceleration();
+        let effective_wind = self.effective_wind_with(rider_directions);
+        let apparent_velocity = self.get_apparent_velocity(&effective_wind);
+
+        SlopeInputs {
+            velocity: self.data.velocity.clone(),
+            power: self.data.power.clone(),
+            acceleration,
+            effective_wind,
+            apparent_velocity,
+            rho: self.rho_series(),
+        }
+    }
+
+    /// Air density per sample: the per-datapoint array where present, else `params.rho`
+    pub(crate) fn rho_series(&self) -> Vec<f64> {
+        (0..self.data.velocity.len())
+            .map(|i| self.data.rho_array.as_ref()
+                .and_then(|arr| arr.get(i).copied())
+                .unwrap_or(self.params.rho))
+            .collect()
+    }
+
+    pub(crate) fn set_rho_array(&mut self, rho_array: Vec<f64>) {
+        self.data.set_rho_array(rho_array);
+    }
+
+    pub(crate) fn set_altitude(&mut self, altitude: Vec<f64>) {
+        self.data.altitude = altitude;
+    }
+
+    /// VE profile and fit metrics from precomputed slope inputs
+    pub(crate) fn ve_result(&self, inputs: &SlopeInputs, cda: f64, crr: f64, trim_start: usize, trim_end: usize) -> VEResult {
+        let virtual_slope: Vec<f64> = (0..inputs.velocity.len())
+            .map(|i| self.slope_at(inputs, i, cda, crr, PowerCorrection::NONE))
+            .collect();
+
+        // Cumulative sum of elevation changes to get elevation profile
+        let mut virtual_elevation = Vec::with_capacity(virtual_slope.len());
+        let mut cumsum = 0.0;
+        for (&v, &slope) in inputs.velocity.iter().zip(&virtual_slope) {
+            cumsum += v * self.dt * slope.atan().sin();
+            virtual_elevation.push(cumsum);
+        }
+
+        // Calculate metrics if actual elevation is available
+        let (r2, rmse, ve_elevation_diff, actual_elevation_diff) =
+            self.calculate_metrics(&virtual_elevation, trim_start, trim_end);
+
+        // Calculate virtual distances within trim region
+        let (virtual_distance_air, virtual_distance_ground, vd_difference_percent) =
+            self.calculate_virtual_distances(trim_start, trim_end);
+
+        VEResult {
+            virtual_elevation,
+            virtual_slope,
+            acceleration: inputs.acceleration.clone(),
+            effective_wind: inputs.effective_wind.clone(),
+            apparent_velocity: inputs.apparent_velocity.clone(),
+            r2,
+            rmse,
+            ve_elevation_diff,
+            actual_elevation_diff,
+            virtual_distance_air,
+            virtual_distance_ground,
+            vd_difference_percent,
+        }
+    }
+
+    /// Elevation to fit against: altitude, or flat ground in velodrome mode.
+    /// None when the file carries no usable altitude.
+    pub(crate) fn reference_elevation(&self) -> Option<Vec<f64>> {
+        if self.params.velodrome {
+            return Some(vec![0.0; self.data.velocity.len()]);
+        }
+
+        let altitude = &self.data.altitude;
+        if altitude.is_empty() || altitude.iter().all(|&x| x.is_nan() || x == 0.0) {
+            return None;
+        }
+
+        Some(altitude.clone())
+    }
+
+    /// Clamp trim regions to the data and reject ones too short to fit
+    pub(crate) fn validate_segments(&self, segments: &[(usize, usize)]) -> Result<Vec<(usize, usize)>> {
+        let n = self.data.velocity.len();
+        if n < 3 {
+            return Err(Error::InsufficientData("Not enough data points to fit".to_string()));
+        }
+        if segments.is_empty() {
+            return Err(Error::InvalidInput("At least one trim region is required".to_string()));
+        }
+
+        segments.iter()
+            .map(|&(start, end)| {
+                let end = end.min(n - 1);
+                if end < start + 2 {
+                    Err(Error::InvalidInput(format!("Trim region {}..{} is too short to fit", start, end)))
+                } else {
+                    Ok((start, end))
+                }
+            })
+            .collect()
+    }
+
+    /// Virtual slope at one sample for the given coefficients and power correction
+    pub(crate) fn slope_at(&self, inputs: &SlopeInputs, i: usize, cda: f64, crr: f64, power: PowerCorrection) -> f64 {
+        let g = 9.807;
+        let m = self.params.system_mass;
+        let v = inputs.velocity[i].max(0.001);
+        let w = power.apply(inputs.power[i]) * self.params.eta;
+        let va = inputs.apparent_velocity[i];
+
+        let slope = (w / (v * m * g))
+            - (cda * inputs.rho[i] * va.powi(2) / (2.0 * m * g))
+            - crr
+            - (inputs.acceleration[i] / g);
+
+        if slope.is_finite() { slope } else { 0.0 }
+    }
+
+    /// VE-minus-reference residuals for each trim region, with VE anchored
+    /// to the reference at the start of each region (as in `calculate_metrics`)
+    pub(crate) fn segment_residuals(
+        &self,
+        inputs: &SlopeInputs,
+        reference: &[f64],
+        segments: &[(usize, usize)],
+        cda: f64,
+        crr: f64,
+        power: PowerCorrection,
+    ) -> Vec<f64> {
+        let mut residuals = Vec::new();
+        for &(start, end) in segments {
+            let anchor = reference[start];
+            if !anchor.is_finite() {
+                continue;
+            }
+
+            let mut ve = 0.0;
+            for (i, &elevation) in reference.iter().enumerate().take(end + 1).skip(start + 1) {
+                let slope = self.slope_at(inputs, i, cda, crr, power);
+                ve += inputs.velocity[i] * self.dt * slope.atan().sin();
+
+                if elevation.is_finite() {
+                    residuals.push(ve - (elevation - anchor));
+                }
+            }
+        }
+        residuals
+    }
+
+    /// Fit CdA/Crr jointly over all trim regions for a fixed power correction
+    pub(crate) fn fit_cda_crr(&self, segments: &[(usize, usize)], power: PowerCorrection) -> Result<CdaCrrFit> {
+        let segments = self.validate_segments(segments)?;
+        let reference = self.reference_elevation()
+            .ok_or_else(|| Error::InsufficientData("No altitude data available to fit against".to_string()))?;
+        let inputs = self.slope_inputs();
+
+        let cda_fixed = self.params.cda;
+        let crr_fixed = self.params.crr;
+        let mut initial = Vec::new();
+        let mut bounds = Vec::new();
+        if cda_fixed.is_none() {
+            initial.push((self.params.cda_min + self.params.cda_max) / 2.0);
+            bounds.push((self.params.cda_min, self.params.cda_max));
+        }
+        if crr_fixed.is_none() {
+            initial.push((self.params.crr_min + self.params.crr_max) / 2.0);
+            bounds.push((self.params.crr_min, self.params.crr_max));
+        }
+
+        let unpack = |p: &[f64]| {
+            let mut free = p.iter().copied();
+            let cda = cda_fixed.unwrap_or_else(|| free.next().unwrap_or(0.0));
+            let crr = crr_fixed.unwrap_or_else(|| free.next().unwrap_or(0.0));
+            (cda, crr)
+        };
+
+        let fit = crate::optimizer::levenberg_marquardt(
+            |p| {
+                let (cda, crr) = unpack(p);
+                self.segment_residuals(&inputs, &reference, &segments, cda, crr, power)
+            },
+            &initial,
+            &bounds,
+        );
+
+        if fit.residuals.len() < 3 {
+            return Err(Error::InsufficientData("Not enough valid altitude samples in the trim region".to_string()));
+        }
+
+        let (cda, crr) = unpack(&fit.params);
+        let mut std_errors = fit.std_errors.iter().copied();
+        let cda_std_error = if cda_fixed.is_none() { std_errors.next().unwrap_or(f64::NAN) } else { 0.0 };
+        let crr_std_error = if crr_fixed.is_none() { std_errors.next().unwrap_or(f64::NAN) } else { 0.0 };
+
+        Ok(CdaCrrFit {
+            cda,
+            crr,
+            cda_std_error,
+            crr_std_error,
+            rmse: fit.rmse(),
+            iterations: fit.iterations,
+        })
+    }
 }
 
-/// Helper function to create VE calculator from JS data with optional per-datapoint rho array
-#[wasm_bindgen]
-pub fn create_ve_calculator_with_rho_array(
-    // Data arrays
-    timestamps: Vec<f64>,
-    power: Vec<f64>,
-    velocity: Vec<f64>,
-    position_lat: Vec<f64>,
-    position_long: Vec<f64>,
-    altitude: Vec<f64>,
-    distance: Vec<f64>,
-    air_speed: Vec<f64>,
-    wind_speed: Vec<f64>,
-    // Optional rho array (if None, uses the single rho parameter)
-    rho_array: Option<Vec<f64>>,
-    // Parameters
-    system_mass: f64,
-    rho: f64,
-    eta: f64,
-    cda: Option<f64>,
-    crr: Option<f64>,
-    cda_min: f64,
-    cda_max: f64,
-    crr_min: f64,
-    crr_max: f64,
-    wind_speed_param: Option<f64>,
-    wind_direction: Option<f64>,
-    velodrome: bool,
-) -> VirtualElevationCalculator {
-    let mut data = VEData::new(
-        timestamps,
-        power,
-        velocity,
-        position_lat,
-        position_long,
-        altitude,
-        distance,
-        air_speed,
-        wind_speed,
-    );
-
-    // Set rho array if provided
-    if let Some(rho_arr) = rho_array {
-        data.rho_array = Some(rho_arr);
-    }
-
-    let mut params = VEParameters::new();
-    params.system_mass = system_mass;
-    params.rho = rho;
-    params.eta = eta;
-    params.cda = cda;
-    params.crr = crr;
-    params.cda_min = cda_min;
-    params.cda_max = cda_max;
-    params.crr_min = crr_min;
-    params.crr_max = crr_max;
-    params.wind_speed = wind_speed_param;
-    params.wind_direction = wind_direction;
-    params.velodrome = velodrome;
-
-    VirtualElevationCalculator::new(data, params)
-}
\ No newline at end of file
+/// sin(road angle) per sample from elevation change over the distance ridden,
+/// clamped so altitude glitches don't turn into cliffs
+pub(crate) fn grade_from_elevation(velocity: &[f64], elevation: &[f64], dt: f64) -> Vec<f64> {
+    const MAX_GRADE: f64 = 0.3;
+
+    let mut grade = vec![0.0; velocity.len()];
+    for i in 1..velocity.len().min(elevation.len()) {
+        let ds = velocity[i] * dt;
+        let dh = elevation[i] - elevation[i - 1];
+        if ds > 0.1 && dh.is_finite() {
+            grade[i] = (dh / ds).clamp(-MAX_GRADE, MAX_GRADE);
+        }
+    }
+    grade
+}
@@ -0,0 +1,234 @@
+//! JS adapter: everything here exists only for the wasm-bindgen build.
+//! The analysis types carry their own `#[wasm_bindgen]` attributes behind the
+//! `wasm` feature; this module holds the pieces with no native counterpart.
+
+use js_sys::Float64Array;
+use wasm_bindgen::prelude::*;
+use crate::error::Error;
+use crate::fit_parser::FitData;
+use crate::virtual_elevation::{VEData, VEParameters, VEResult, VirtualElevationCalculator};
+
+impl From<Error> for JsValue {
+    fn from(error: Error) -> JsValue {
+        JsValue::from_str(&error.to_string())
+    }
+}
+
+/// Float64Array over WASM memory, without copying.
+///
+/// The view aliases the Rust-owned buffer, so it is only valid while the owning
+/// object is alive and no allocation has happened since: any call into the module
+/// can grow the memory and detach it. Read (or `.slice()`) it straight away;
+/// never hold it across calls or after `free()`.
+fn view(values: &[f64]) -> Float64Array {
+    // SAFETY: see above; the caller in JS must not keep the view past the next call.
+    unsafe { Float64Array::view(values) }
+}
+
+// Zero-copy counterparts of the VEResult/FitData getters for redraw-heavy paths.
+// Each `*_view()` follows the validity rules documented on `view`.
+#[wasm_bindgen]
+impl VEResult {
+    pub fn virtual_elevation_view(&self) -> Float64Array {
+        view(self.virtual_elevation_slice())
+    }
+
+    pub fn virtual_slope_view(&self) -> Float64Array {
+        view(self.virtual_slope_slice())
+    }
+
+    pub fn acceleration_view(&self) -> Float64Array {
+        view(self.acceleration_slice())
+    }
+
+    pub fn effective_wind_view(&self) -> Float64Array {
+        view(self.effective_wind_slice())
+    }
+
+    pub fn apparent_velocity_view(&self) -> Float64Array {
+        view(self.apparent_velocity_slice())
+    }
+}
+
+#[wasm_bindgen]
+impl FitData {
+    pub fn timestamps_view(&self) -> Float64Array {
+        view(self.timestamps_slice())
+    }
+
+    pub fn power_view(&self) -> Float64Array {
+        view(self.power_slice())
+    }
+
+    pub fn velocity_view(&self) -> Float64Array {
+        view(self.velocity_slice())
+    }
+
+    pub fn position_lat_view(&self) -> Float64Array {
+        view(self.position_lat_slice())
+    }
+
+    pub fn position_long_view(&self) -> Float64Array {
+        view(self.position_long_slice())
+    }
+
+    pub fn altitude_view(&self) -> Float64Array {
+        view(self.altitude_slice())
+    }
+
+    pub fn distance_view(&self) -> Float64Array {
+        view(self.distance_slice())
+    }
+
+    pub fn air_speed_view(&self) -> Float64Array {
+        view(self.air_speed_slice())
+    }
+
+    pub fn wind_speed_view(&self) -> Float64Array {
+        view(self.wind_speed_slice())
+    }
+
+    pub fn battery_soc_view(&self) -> Float64Array {
+        view(self.battery_soc_slice())
+    }
+
+    pub fn heart_rate_view(&self) -> Float64Array {
+        view(self.heart_rate_slice())
+    }
+
+    pub fn cadence_view(&self) -> Float64Array {
+        view(self.cadence_slice())
+    }
+
+    pub fn temperature_view(&self) -> Float64Array {
+        view(self.temperature_slice())
+    }
+}
+
+// Initialize WASM module
+#[wasm_bindgen(start)]
+pub fn init() {
+    console_error_panic_hook::set_once();
+    web_sys::console::log_1(&"Virtual Elevation Analyzer WASM module initialized".into());
+}
+
+/// Helper function to create VE calculator from JS data
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn create_ve_calculator(
+    // Data arrays
+    timestamps: Vec<f64>,
+    power: Vec<f64>,
+    velocity: Vec<f64>,
+    position_lat: Vec<f64>,
+    position_long: Vec<f64>,
+    altitude: Vec<f64>,
+    distance: Vec<f64>,
+    air_speed: Vec<f64>,
+    wind_speed: Vec<f64>,
+    // Parameters
+    system_mass: f64,
+    rho: f64,
+    eta: f64,
+    cda: Option<f64>,
+    crr: Option<f64>,
+    cda_min: f64,
+    cda_max: f64,
+    crr_min: f64,
+    crr_max: f64,
+    wind_speed_param: Option<f64>,
+    wind_direction: Option<f64>,
+    velodrome: bool,
+) -> VirtualElevationCalculator {
+    let data = VEData::new(
+        timestamps,
+        power,
+        velocity,
+        position_lat,
+        position_long,
+        altitude,
+        distance,
+        air_speed,
+        wind_speed,
+    );
+
+    let mut params = VEParameters::new();
+    params.system_mass = system_mass;
+    params.rho = rho;
+    params.eta = eta;
+    params.cda = cda;
+    params.crr = crr;
+    params.cda_min = cda_min;
+    params.cda_max = cda_max;
+    params.crr_min = crr_min;
+    params.crr_max = crr_max;
+    params.wind_speed = wind_speed_param;
+    params.wind_direction = wind_direction;
+    params.velodrome = velodrome;
+
+    VirtualElevationCalculator::new(data, params)
+}
+
+/// Helper function to create VE calculator from JS data with optional per-datapoint rho array
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn create_ve_calculator_with_rho_array(
+    // Data arrays
+    timestamps: Vec<f64>,
+    power: Vec<f64>,
+    velocity: Vec<f64>,
+    position_lat: Vec<f64>,
+    position_long: Vec<f64>,
+    altitude: Vec<f64>,
+    distance: Vec<f64>,
+    air_speed: Vec<f64>,
+    wind_speed: Vec<f64>,
+    // Optional rho array (if None, uses the single rho parameter)
+    rho_array: Option<Vec<f64>>,
+    // Parameters
+    system_mass: f64,
+    rho: f64,
+    eta: f64,
+    cda: Option<f64>,
+    crr: Option<f64>,
+    cda_min: f64,
+    cda_max: f64,
+    crr_min: f64,
+    crr_max: f64,
+    wind_speed_param: Option<f64>,
+    wind_direction: Option<f64>,
+    velodrome: bool,
+) -> VirtualElevationCalculator {
+    let mut data = VEData::new(
+        timestamps,
+        power,
+        velocity,
+        position_lat,
+        position_long,
+        altitude,
+        distance,
+        air_speed,
+        wind_speed,
+    );
+
+    // Set rho array if provided
+    if let Some(rho_arr) = rho_array {
+        data.set_rho_array(rho_arr);
+    }
+
+    let mut params = VEParameters::new();
+    params.system_mass = system_mass;
+    params.rho = rho;
+    params.eta = eta;
+    params.cda = cda;
+    params.crr = crr;
+    params.cda_min = cda_min;
+    params.cda_max = cda_max;
+    params.crr_min = crr_min;
+    params.crr_max = crr_max;
+    params.wind_speed = wind_speed_param;
+    params.wind_direction = wind_direction;
+    params.velodrome = velodrome;
+
+    VirtualElevationCalculator::new(data, params)
+}
\ No newline at end of file
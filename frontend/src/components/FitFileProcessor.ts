@@ -62,7 +62,29 @@ export class FitFileProcessor {
       const uint8Array = new Uint8Array(arrayBuffer);
 
       // Parse using WASM
-      const result = this.wasmModule.parse_fit_file(uint8Array);
+      let result;
+      const extension = file.name.toLowerCase().split('.').pop();
+      if (extension === 'gpx') {
+        result = this.wasmModule.parse_gpx_file(uint8Array);
+      } else if (extension === 'tcx') {
+        result = this.wasmModule.parse_tcx_file(uint8Array);
+      } else {
+        try {
+          result = this.wasmModule.parse_fit_file(uint8Array);
+        } catch (error) {
+          // Damaged or truncated file (e.g. head unit crash): keep what can be read
+          result = this.wasmModule.parse_fit_file_with_mode(uint8Array, this.wasmModule.FitParseMode.Recover);
+          console.warn(`${file.name}: ${result.diagnostics.summary()}`);
+        }
+      }
+
+      // Multisport (triathlon, brick) files: analyze the bike leg only
+      if (result.sessions.length > 1) {
+        const bikeSession = result.find_session('cycling');
+        if (bikeSession !== undefined) {
+          result = result.session(bikeSession);
+        }
+      }
 
       return {
         fit_data: result.fit_data,
@@ -74,4 +96,14 @@ export class FitFileProcessor {
       throw error;
     }
   }
-}
\ No newline at end of file
+
+  // Gibli aero sensor CSV: FitData and laps plus the CSV-only channels (yaw, humidity, pressure, reference CdA)
+  async processCsvFile(file: File): Promise<any> {
+    if (!this.wasmModule) {
+      throw new Error('WASM module not initialized');
+    }
+
+    const uint8Array = new Uint8Array(await file.arrayBuffer());
+    return this.wasmModule.parse_csv_file(uint8Array);
+  }
+}
@@ -9,8 +9,6 @@ import { DEMManager, ElevationProfileCache } from './utils/DEMManager';
 import { calculateTrimRegionMetadata, formatCoordinates } from './utils/GeoCalculations';
 import { WeatherAPI, WeatherAPIError } from './utils/WeatherAPI';
 import { WeatherCache, type WeatherCacheEntry } from './utils/WeatherCache';
-import { GibliCsvParser, type GibliCsvData, CsvParseError } from './utils/CsvParser';
-import { interpolateAllData, analyzeTimeIntervals } from './utils/DataInterpolation';
 import init, { create_ve_calculator, create_ve_calculator_with_rho_array, AirDensityCalculator } from '../pkg/virtual_elevation_analyzer.js';
 
 // Plotly.js type declaration
@@ -297,7 +295,7 @@ function clearDEMFile(): void {
 async function handleFileSelection(file: File) {
     // Validate file type and size
     if (!DataProtection.validateFileType(file)) {
-        showError('Please select a valid FIT or CSV file (.fit or .csv extension, under 50MB)');
+        showError('Please select a valid FIT, GPX, TCX or CSV file (under 50MB)');
         return;
     }
 
@@ -337,12 +335,12 @@ analyzeButton.addEventListener('click', async () => {
         // Detect file type
         const fileType = DataProtection.getFileType(selectedFile);
 
-        if (fileType === 'fit') {
+        if (fileType === 'fit' || fileType === 'track') {
             await processFitFile(selectedFile);
         } else if (fileType === 'csv') {
             await processCsvFile(selectedFile);
         } else {
-            showError('Unknown file type. Please select a .fit or .csv file.');
+            showError('Unknown file type. Please select a .fit, .gpx, .tcx or .csv file.');
             hideLoading();
             return;
         }
@@ -359,8 +357,9 @@ async function processFitFile(file: File) {
     try {
         showLoading('Reading FIT file...');
 
-        // Additional validation
-        const isValidMagicNumber = await DataProtection.validateFitMagicNumber(file);
+        // Additional validation; GPX/TCX are XML and checked by the parser
+        const isTrack = DataProtection.getFileType(file) === 'track';
+        const isValidMagicNumber = isTrack || await DataProtection.validateFitMagicNumber(file);
         if (!isValidMagicNumber) {
             showError('Invalid FIT file format. Please select a valid FIT file.');
             hideLoading();
@@ -460,84 +459,23 @@ async function processFitFile(file: File) {
     }
 }
 
-// Helper function to calculate cumulative distance from GPS coordinates
-function calculateDistanceArray(lats: number[], lons: number[]): number[] {
-    const distances: number[] = [0];
-    let cumulative = 0;
-
-    for (let i = 1; i < lats.length; i++) {
-        const lat1 = lats[i - 1];
-        const lon1 = lons[i - 1];
-        const lat2 = lats[i];
-        const lon2 = lons[i];
-
-        // Haversine formula
-        const R = 6371000; // Earth's radius in meters
-        const φ1 = lat1 * Math.PI / 180;
-        const φ2 = lat2 * Math.PI / 180;
-        const Δφ = (lat2 - lat1) * Math.PI / 180;
-        const Δλ = (lon2 - lon1) * Math.PI / 180;
-
-        const a = Math.sin(Δφ / 2) * Math.sin(Δφ / 2) +
-                  Math.cos(φ1) * Math.cos(φ2) *
-                  Math.sin(Δλ / 2) * Math.sin(Δλ / 2);
-        const c = 2 * Math.atan2(Math.sqrt(a), Math.sqrt(1 - a));
-        const d = R * c;
-
-        cumulative += d;
-        distances.push(cumulative);
-    }
-
-    return distances;
-}
-
-// Generate laps from CSV lap number column
-function generateLapsFromCsv(csvData: GibliCsvData): any[] {
-    if (!csvData.hasLapData || !csvData.lapNumber) {
-        return [];
-    }
-
-    const laps: any[] = [];
-    const uniqueLapNumbers = Array.from(new Set(csvData.lapNumber.filter(n => !isNaN(n)))).sort((a, b) => a - b);
-
-    for (const lapNum of uniqueLapNumbers) {
-        const indices = csvData.lapNumber
-            .map((n, i) => n === lapNum ? i : -1)
-            .filter(i => i !== -1);
-
-        if (indices.length > 0) {
-            const startIdx = indices[0];
-            const endIdx = indices[indices.length - 1];
-
-            laps.push({
-                lap_number: lapNum,
-                start_time: csvData.timestamps[startIdx],
-                end_time: csvData.timestamps[endIdx],
-                total_elapsed_time: csvData.timestamps[endIdx] - csvData.timestamps[startIdx],
-                start_index: startIdx,
-                end_index: endIdx,
-                total_distance: 0, // Will calculate if needed
-            });
-        }
-    }
-
-    return laps;
-}
-
 // Display CSV results (similar to displayResults but for CSV)
-async function displayCsvResults(csvData: GibliCsvData, result: any) {
+async function displayCsvResults(csvImport: any, result: any) {
     const stats = result.parsing_statistics;
+    const hasAltitudeData = result.fit_data.altitude.some((alt: number) => alt !== 0);
+    const hasAirSpeedData = result.fit_data.air_speed.some((as: number) => as > 0);
+    const avgCda = csvImport.average_cda_reference;
 
     fileDetails.innerHTML = `
         <div><strong>File Type:</strong> CSV (Gibli Aerosensor)</div>
-        <div><strong>Data Points:</strong> ${stats.data_points}</div>
-        <div><strong>Duration:</strong> ${(csvData.timeRangeSeconds / 60).toFixed(1)} minutes</div>
+        <div><strong>Data Points:</strong> ${stats.record_count}</div>
+        <div><strong>Duration:</strong> ${(stats.duration_seconds / 60).toFixed(1)} minutes</div>
         <div><strong>Power Data:</strong> ${stats.has_power_data ? '✅ Yes' : '❌ No'}</div>
         <div><strong>GPS Data:</strong> ${stats.has_gps_data ? '✅ Yes' : '❌ No'}</div>
-        <div><strong>Altitude Data:</strong> ${stats.has_altitude_data ? '✅ Yes' : '❌ No'}</div>
-        <div><strong>Air Speed Data:</strong> ${stats.has_air_speed_data ? '✅ Yes' : '❌ No'}</div>
-        <div><strong>Environmental Data:</strong> ${csvData.hasEnvironmentalData ? '✅ Yes (Temp, Humidity, Pressure)' : '❌ No'}</div>
-        <div><strong>CdA Reference:</strong> ${csvData.hasCdaReference ? `✅ Yes (avg: ${calculateAvgCda(csvData).toFixed(3)})` : '❌ No'}</div>
+        <div><strong>Altitude Data:</strong> ${hasAltitudeData ? '✅ Yes' : '❌ No'}</div>
+        <div><strong>Air Speed Data:</strong> ${hasAirSpeedData ? '✅ Yes' : '❌ No'}</div>
+        <div><strong>Environmental Data:</strong> ${csvImport.has_environmental_data ? '✅ Yes (Temp, Humidity, Pressure)' : '❌ No'}</div>
+        <div><strong>CdA Reference:</strong> ${avgCda !== undefined ? `✅ Yes (avg: ${avgCda.toFixed(3)})` : '❌ No'}</div>
         <div><strong>Laps:</strong> ${result.laps.length > 0 ? `✅ ${result.laps.length} lap(s)` : '❌ No lap data'}</div>
     `;
 
@@ -555,7 +493,7 @@ async function displayCsvResults(csvData: GibliCsvData, result: any) {
             parametersComponent.setParameters(savedParameters);
         } else {
             // First time loading - apply smart defaults
-            if (csvData.hasEnvironmentalData) {
+            if (csvImport.has_environmental_data) {
                 // CSV has environmental data - disable weather API
                 parametersComponent.setParameters({
                     auto_calculate_rho: false
@@ -574,14 +512,6 @@ async function displayCsvResults(csvData: GibliCsvData, result: any) {
     isLoadingParameters = false;
 }
 
-// Calculate average CdA from CSV reference data
-function calculateAvgCda(csvData: GibliCsvData): number {
-    if (!csvData.cdaReference) return 0;
-    const validCda = csvData.cdaReference.filter(v => !isNaN(v));
-    if (validCda.length === 0) return 0;
-    return validCda.reduce((sum, v) => sum + v, 0) / validCda.length;
-}
-
 // Calculate per-datapoint air density from environmental data (works for both FIT and CSV)
 function calculateRhoArrayFromFitData(fitData: any): number[] | null {
     // Check if data has all required environmental data
@@ -632,52 +562,32 @@ function calculateRhoArrayFromFitData(fitData: any): number[] | null {
 }
 
 // Initialize section 3 for CSV data
-function initializeSection3Csv(csvData: GibliCsvData, result: any) {
-    // Calculate distance from GPS coordinates
-    const distance = calculateDistanceArray(csvData.positionLat, csvData.positionLong);
-
-    // Calculate wind speed in the direction of the rider from Wind Magnitude and Wind Angle
-    // Formula: ws_rider = cos(angle_radians) * magnitude
-    const windSpeed = csvData.windAngle.map((angleDeg, i) => {
-        const magnitude = csvData.airSpeed[i]; // airSpeed is already converted to m/s
-        if (isNaN(angleDeg) || isNaN(magnitude)) {
-            return 0;
-        }
-        const angleRad = (angleDeg * Math.PI) / 180;
-        return Math.cos(angleRad) * magnitude;
-    });
-
-    console.log('📊 Calculated wind speed from CSV:', {
-        sampleAngles: csvData.windAngle.slice(0, 5),
-        sampleMagnitudes: csvData.airSpeed.slice(0, 5),
-        sampleWindSpeeds: windSpeed.slice(0, 5),
-        nonZeroCount: windSpeed.filter(ws => Math.abs(ws) > 0.1).length
-    });
+function initializeSection3Csv(csvImport: any) {
+    // Distance, laps and the head-on wind component (cos(yaw) × air speed) come from the importer
+    const fitData = csvImport.fit_data;
 
-    // Create a FitData-compatible structure
-    // This structure matches FitData from WASM, allowing all downstream code to work identically
+    // Plain object with the FitData fields plus the CSV-only channels; downstream code
+    // checks for humidity/pressure/cda_reference by presence, not file type
     currentFitData = {
-        // Required fields (same as FitData)
-        timestamps: csvData.timestamps,
-        position_lat: csvData.positionLat,
-        position_long: csvData.positionLong,
-        altitude: csvData.altitude,
-        velocity: csvData.velocity,
-        power: csvData.power,
-        air_speed: csvData.airSpeed,
-        distance: distance,
-        wind_speed: windSpeed,
-        temperature: csvData.temperature || new Array(csvData.timestamps.length).fill(0),
-        battery_soc: new Array(csvData.timestamps.length).fill(0),
-        heart_rate: new Array(csvData.timestamps.length).fill(0),
-        cadence: new Array(csvData.timestamps.length).fill(0),
-        record_count: csvData.timestamps.length,
-
-        // Extended fields (not in standard FitData, but available from CSV)
-        // These will be checked by feature detection, not file type
-        humidity: csvData.humidity,  // Optional: only present if CSV has it
-        pressure: csvData.pressure,  // Optional: only present if CSV has it
-        cda_reference: csvData.cdaReference,  // Optional: only present if CSV has it
+        timestamps: fitData.timestamps,
+        position_lat: fitData.position_lat,
+        position_long: fitData.position_long,
+        altitude: fitData.altitude,
+        velocity: fitData.velocity,
+        power: fitData.power,
+        air_speed: fitData.air_speed,
+        distance: fitData.distance,
+        wind_speed: fitData.wind_speed,
+        // The importer's copy keeps blank cells as NaN (FitData has 0), so rho falls back to 1.225 there
+        temperature: csvImport.temperature.length > 0 ? csvImport.temperature : undefined,
+        battery_soc: fitData.battery_soc,
+        heart_rate: fitData.heart_rate,
+        cadence: fitData.cadence,
+        record_count: fitData.record_count,
+
+        humidity: csvImport.humidity.length > 0 ? csvImport.humidity : undefined,
+        pressure: csvImport.pressure.length > 0 ? csvImport.pressure : undefined,
+        cda_reference: csvImport.has_cda_reference ? csvImport.cda_reference : undefined,
     };
 
     // Call regular initializeSection3
@@ -687,105 +597,36 @@ function initializeSection3Csv(csvData: GibliCsvData, result: any) {
 // Process CSV file
 async function processCsvFile(file: File) {
     try {
-        showLoading('Reading CSV file...');
-
-        // Read file content
-        const text = await file.text();
-
         showLoading('Parsing CSV data...');
 
-        // Parse CSV
-        let csvData: GibliCsvData;
+        // Column mapping, unit conversion, 1Hz resampling and lap synthesis happen in WASM
+        let csvImport: any;
         try {
-            csvData = GibliCsvParser.parse(text);
+            csvImport = await fitProcessor.processCsvFile(file);
         } catch (parseError) {
-            if (parseError instanceof CsvParseError) {
-                showError(`CSV parsing error:\n${parseError.message}`);
-            } else {
-                showError(`Failed to parse CSV file: ${parseError}`);
-            }
+            showError(`CSV parsing error:\n${parseError}`);
             hideLoading();
             return;
         }
 
-        // Show summary
-        console.log('CSV Data Summary:');
-        console.log(GibliCsvParser.getSummary(csvData));
-
-        // Analyze time intervals
-        const intervals = analyzeTimeIntervals(csvData.timestamps);
-        console.log('Time interval statistics:', intervals);
-
-        // Interpolate to 1Hz if needed
-        if (intervals.std > 0.1) {
-            showLoading('Interpolating data to 1Hz...');
-            console.log('Non-uniform time series detected, interpolating to 1Hz');
-
-            const dataToInterpolate: Record<string, number[]> = {
-                velocity: csvData.velocity,
-                power: csvData.power,
-                airSpeed: csvData.airSpeed,
-                windAngle: csvData.windAngle,
-                altitude: csvData.altitude,
-                positionLat: csvData.positionLat,
-                positionLong: csvData.positionLong,
-            };
-
-            // Add optional arrays if they exist
-            if (csvData.temperature) dataToInterpolate.temperature = csvData.temperature;
-            if (csvData.humidity) dataToInterpolate.humidity = csvData.humidity;
-            if (csvData.pressure) dataToInterpolate.pressure = csvData.pressure;
-            if (csvData.cdaReference) dataToInterpolate.cdaReference = csvData.cdaReference;
-            if (csvData.lapNumber) dataToInterpolate.lapNumber = csvData.lapNumber;
-
-            const interpolated = interpolateAllData(csvData.timestamps, dataToInterpolate);
-
-            // Replace with interpolated data
-            csvData.timestamps = interpolated.timestamps;
-            csvData.velocity = interpolated.velocity;
-            csvData.power = interpolated.power;
-            csvData.airSpeed = interpolated.airSpeed;
-            csvData.windAngle = interpolated.windAngle;
-            csvData.altitude = interpolated.altitude;
-            csvData.positionLat = interpolated.positionLat;
-            csvData.positionLong = interpolated.positionLong;
-            if (interpolated.temperature) csvData.temperature = interpolated.temperature;
-            if (interpolated.humidity) csvData.humidity = interpolated.humidity;
-            if (interpolated.pressure) csvData.pressure = interpolated.pressure;
-            if (interpolated.cdaReference) csvData.cdaReference = interpolated.cdaReference;
-            if (interpolated.lapNumber) csvData.lapNumber = interpolated.lapNumber;
-
-            console.log(`Interpolated to ${csvData.timestamps.length} data points at 1Hz`);
+        if (csvImport.resampled) {
+            console.log(`Interpolated to ${csvImport.fit_data.record_count} data points at 1Hz`);
+        }
+        if (csvImport.skipped_rows > 0) {
+            console.warn(`Skipped ${csvImport.skipped_rows} CSV rows without a usable timer value`);
         }
 
-        // Create a unified result structure similar to FIT file processing
-        showLoading('Creating data structure...');
-
-        // Calculate distance from GPS coordinates
-        const distance = calculateDistanceArray(csvData.positionLat, csvData.positionLong);
-
-        // Create wind speed array from air speed (wind magnitude is already air speed)
-        // Wind speed in this context is the environmental wind, which we'll calculate later
-        const windSpeed = new Array(csvData.timestamps.length).fill(0);
-
-        // Create a mock result structure that mirrors FIT file result
         const result = {
-            fit_data: null, // No FIT data for CSV files
-            parsing_statistics: {
-                has_power_data: csvData.power.some(p => !isNaN(p) && p > 0),
-                has_gps_data: csvData.positionLat.some(lat => !isNaN(lat)),
-                has_altitude_data: csvData.altitude.some(alt => !isNaN(alt)),
-                has_air_speed_data: csvData.airSpeed.some(as => !isNaN(as) && as > 0),
-                data_points: csvData.timestamps.length,
-            },
-            laps: csvData.hasLapData ? generateLapsFromCsv(csvData) : [],
+            fit_data: csvImport.fit_data,
+            parsing_statistics: csvImport.parsed.parsing_statistics,
+            laps: csvImport.laps,
         };
 
         currentFitResult = result;
         currentLaps = result.laps;
 
         hideLoading();
-        await displayCsvResults(csvData, result);
+        await displayCsvResults(csvImport, result);
 
         // Activate section 2 (parameters) and section 3 (map/laps)
         // CSV files work just like FIT files - both sections are active after loading
@@ -796,7 +637,7 @@ async function processCsvFile(file: File) {
             console.log('📍 Activating section 3 for CSV lap analysis...');
             activateSection(3);
             setTimeout(() => {
-                initializeSection3Csv(csvData, result);
+                initializeSection3Csv(csvImport);
                 console.log('✅ Section 3 initialized for CSV');
             }, 100);
         }
@@ -1,14 +1,15 @@
 export class DataProtection {
   static validateFileType(file: File): boolean {
-    // Accept both FIT and CSV files
+    // Accept FIT, GPX/TCX tracks and CSV files
     const fileName = file.name.toLowerCase();
-    const isValidExtension = fileName.endsWith('.fit') || fileName.endsWith('.csv');
+    const isValidExtension = ['.fit', '.gpx', '.tcx', '.csv'].some(ext => fileName.endsWith(ext));
     return isValidExtension && file.size > 0 && file.size < 50_000_000; // 50MB limit
   }
 
-  static getFileType(file: File): 'fit' | 'csv' | 'unknown' {
+  static getFileType(file: File): 'fit' | 'track' | 'csv' | 'unknown' {
     const fileName = file.name.toLowerCase();
     if (fileName.endsWith('.fit')) return 'fit';
+    if (fileName.endsWith('.gpx') || fileName.endsWith('.tcx')) return 'track';
     if (fileName.endsWith('.csv')) return 'csv';
     return 'unknown';
   }